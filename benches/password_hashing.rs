@@ -0,0 +1,89 @@
+//! Times argon2id hashing for a grid of parameters, to help pick
+//! `PASSWORD_MEMORY_KIB`, `PASSWORD_ITERATIONS` and `PASSWORD_PARALLELISM`.
+//!
+//! Run it on the production host with `cargo bench --bench password_hashing`
+//! and pick the strongest parameters that stay under the target time
+//! (`PASSWORD_TARGET_MS`, 250 ms by default).
+
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use argon2::{ThreadMode, Variant, Version};
+
+const SAMPLES: u32 = 5;
+
+fn time_hash(memory_kib: u32, iterations: u32, parallelism: u32) -> Duration {
+    let config = argon2::Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: memory_kib,
+        time_cost: iterations,
+        lanes: parallelism,
+        thread_mode: ThreadMode::from_threads(parallelism),
+        ..argon2::Config::default()
+    };
+    let start = Instant::now();
+
+    for _ in 0..SAMPLES {
+        argon2::hash_encoded(
+            b"correct horse battery",
+            b"benchmarksalt123",
+            &config,
+        )
+        .unwrap();
+    }
+
+    start.elapsed() / SAMPLES
+}
+
+fn main() {
+    let target = Duration::from_millis(
+        env::var("PASSWORD_TARGET_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(250),
+    );
+    let mut best = None;
+
+    println!(
+        "{:>10} {:>10} {:>12} {:>10}",
+        "memory", "iterations", "parallelism", "time"
+    );
+
+    for memory_kib in [19456, 47104, 65536, 131072, 262144] {
+        for iterations in [1, 2, 3, 4] {
+            for parallelism in [1, 2, 4] {
+                let elapsed = time_hash(memory_kib, iterations, parallelism);
+                let fits = elapsed <= target;
+
+                println!(
+                    "{:>7} KiB {:>10} {:>12} {:>7} ms{}",
+                    memory_kib,
+                    iterations,
+                    parallelism,
+                    elapsed.as_millis(),
+                    if fits { "" } else { "  (too slow)" }
+                );
+
+                // Prefer memory, then iterations: memory hardness is what
+                // slows down GPU attacks the most.
+                if fits && best < Some((memory_kib, iterations, parallelism)) {
+                    best = Some((memory_kib, iterations, parallelism));
+                }
+            }
+        }
+    }
+
+    match best {
+        Some((memory_kib, iterations, parallelism)) => println!(
+            "\nSuggested: PASSWORD_MEMORY_KIB={} PASSWORD_ITERATIONS={} PASSWORD_PARALLELISM={}",
+            memory_kib, iterations, parallelism
+        ),
+        None => println!(
+            "\nNo parameters fit in {} ms, raise PASSWORD_TARGET_MS.",
+            target.as_millis()
+        ),
+    }
+}
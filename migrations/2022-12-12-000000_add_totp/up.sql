@@ -0,0 +1,24 @@
+-- Your SQL goes here
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'user',
+    ADD COLUMN totp_secret VARCHAR(64),
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_step BIGINT;
+
+CREATE TABLE login_challenges (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (id)
+);
+
+CREATE TABLE recovery_codes (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(512) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+)
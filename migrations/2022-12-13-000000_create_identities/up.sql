@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE identities (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    provider VARCHAR(32) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(254),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id),
+    UNIQUE (provider, subject)
+)
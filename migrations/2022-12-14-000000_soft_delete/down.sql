@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_deleted_at_idx;
+DROP INDEX questions_deleted_at_idx;
+DROP INDEX questions_text_key;
+DROP INDEX users_email_key;
+DROP INDEX users_username_key;
+DELETE FROM votes WHERE question_id IN (SELECT id FROM questions WHERE deleted_at IS NOT NULL);
+DELETE FROM questions WHERE deleted_at IS NOT NULL;
+ALTER TABLE questions ADD CONSTRAINT questions_text_key UNIQUE (text);
+ALTER TABLE questions DROP COLUMN deleted_at;
+DELETE FROM votes WHERE user_id IN (SELECT id FROM users WHERE deleted_at IS NOT NULL)
+    OR question_id IN (SELECT questions.id FROM questions JOIN users ON users.id = questions.user_id WHERE users.deleted_at IS NOT NULL);
+DELETE FROM questions WHERE user_id IN (SELECT id FROM users WHERE deleted_at IS NOT NULL);
+DELETE FROM users WHERE deleted_at IS NOT NULL;
+ALTER TABLE users ADD CONSTRAINT users_username_key UNIQUE (username);
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+ALTER TABLE users DROP COLUMN deleted_at
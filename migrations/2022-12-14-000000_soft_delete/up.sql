@@ -0,0 +1,16 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE questions ADD COLUMN deleted_at TIMESTAMP;
+
+-- A deleted question's text can be reused until it is purged.
+ALTER TABLE questions DROP CONSTRAINT questions_text_key;
+CREATE UNIQUE INDEX questions_text_key ON questions (text) WHERE deleted_at IS NULL;
+
+-- Deleted accounts free their username and email until they are restored.
+ALTER TABLE users DROP CONSTRAINT users_username_key;
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_username_key ON users (username) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX users_email_key ON users (email) WHERE deleted_at IS NULL;
+
+CREATE INDEX questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX questions_closes_at_idx;
+DROP INDEX questions_opens_at_idx;
+ALTER TABLE questions
+    DROP CONSTRAINT questions_window_check,
+    DROP COLUMN closes_at,
+    DROP COLUMN opens_at,
+    DROP COLUMN status
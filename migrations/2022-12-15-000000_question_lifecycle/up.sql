@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE questions
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'open',
+    ADD COLUMN opens_at TIMESTAMP,
+    ADD COLUMN closes_at TIMESTAMP,
+    ADD CONSTRAINT questions_window_check CHECK (closes_at IS NULL OR opens_at IS NULL OR closes_at > opens_at);
+
+CREATE INDEX questions_opens_at_idx ON questions (opens_at) WHERE status = 'draft';
+CREATE INDEX questions_closes_at_idx ON questions (closes_at) WHERE status = 'open'
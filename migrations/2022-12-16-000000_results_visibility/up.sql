@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE questions ADD COLUMN results_visibility VARCHAR(16) NOT NULL DEFAULT 'always'
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE vote_options;
+DELETE FROM votes WHERE value IS NULL;
+ALTER TABLE votes ALTER COLUMN value SET NOT NULL;
+DROP TABLE question_options;
+ALTER TABLE questions
+    DROP COLUMN max_label,
+    DROP COLUMN min_label,
+    DROP COLUMN scale_step,
+    DROP COLUMN scale_max,
+    DROP COLUMN scale_min,
+    DROP COLUMN question_type
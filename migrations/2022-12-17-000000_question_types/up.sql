@@ -0,0 +1,31 @@
+-- Your SQL goes here
+ALTER TABLE questions
+    ADD COLUMN question_type VARCHAR(16) NOT NULL DEFAULT 'rating',
+    ADD COLUMN scale_min INTEGER,
+    ADD COLUMN scale_max INTEGER,
+    ADD COLUMN scale_step INTEGER,
+    ADD COLUMN min_label VARCHAR(64),
+    ADD COLUMN max_label VARCHAR(64);
+
+-- Existing questions keep the 0 to 5 scale they were created with.
+UPDATE questions SET scale_min = 0, scale_max = 5, scale_step = 1;
+
+CREATE TABLE question_options (
+    id uuid DEFAULT uuid_generate_v4(),
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    label VARCHAR(128) NOT NULL,
+    PRIMARY KEY (id),
+    UNIQUE (question_id, position)
+);
+
+-- Choice votes have no value, only options.
+ALTER TABLE votes ALTER COLUMN value DROP NOT NULL;
+
+CREATE TABLE vote_options (
+    vote_id uuid NOT NULL references votes(id) ON DELETE CASCADE,
+    option_id uuid NOT NULL references question_options(id) ON DELETE CASCADE,
+    PRIMARY KEY (vote_id, option_id)
+);
+
+CREATE INDEX vote_options_option_id_idx ON vote_options (option_id)
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE vote_options DROP COLUMN rank
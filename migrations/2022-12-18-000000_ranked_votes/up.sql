@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Where the option was ranked on the ballot, from 0 (first choice).
+ALTER TABLE vote_options ADD COLUMN rank INTEGER NOT NULL DEFAULT 0
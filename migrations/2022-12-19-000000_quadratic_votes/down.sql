@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE vote_options DROP COLUMN weight;
+ALTER TABLE questions DROP COLUMN credit_budget
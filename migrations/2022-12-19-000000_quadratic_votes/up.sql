@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Credits each voter can spend on a quadratic question.
+ALTER TABLE questions ADD COLUMN credit_budget INTEGER;
+
+-- Votes cast on the option. Only quadratic votes cast more than one.
+ALTER TABLE vote_options ADD COLUMN weight INTEGER NOT NULL DEFAULT 1
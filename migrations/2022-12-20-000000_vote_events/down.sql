@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE vote_events;
+DROP FUNCTION vote_events_append_only
@@ -0,0 +1,37 @@
+-- Your SQL goes here
+CREATE TABLE vote_events (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    kind VARCHAR(16) NOT NULL,
+    value INTEGER,
+    option_ids uuid[] NOT NULL DEFAULT '{}',
+    weights INTEGER[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX vote_events_user_id_idx ON vote_events (user_id, created_at DESC, id DESC);
+CREATE INDEX vote_events_question_id_idx ON vote_events (question_id, created_at);
+
+-- Events are only ever added. Rows still go away with their user or question.
+CREATE FUNCTION vote_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'vote_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER vote_events_append_only BEFORE UPDATE ON vote_events
+    FOR EACH ROW EXECUTE FUNCTION vote_events_append_only();
+
+-- Existing votes start the history as they are now.
+INSERT INTO vote_events (user_id, question_id, kind, value, option_ids, weights, created_at)
+SELECT
+    votes.user_id,
+    votes.question_id,
+    'created',
+    votes.value,
+    ARRAY(SELECT option_id FROM vote_options WHERE vote_id = votes.id ORDER BY rank),
+    ARRAY(SELECT weight FROM vote_options WHERE vote_id = votes.id ORDER BY rank),
+    votes.updated_at
+FROM votes
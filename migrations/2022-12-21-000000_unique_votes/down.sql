@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE votes DROP CONSTRAINT votes_user_id_question_id_key
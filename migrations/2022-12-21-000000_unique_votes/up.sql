@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Keep only the most recently updated vote of each user on each question.
+DELETE FROM votes a USING votes b
+WHERE a.user_id = b.user_id
+    AND a.question_id = b.question_id
+    AND (a.updated_at, a.id) < (b.updated_at, b.id);
+
+ALTER TABLE votes ADD CONSTRAINT votes_user_id_question_id_key UNIQUE (user_id, question_id)
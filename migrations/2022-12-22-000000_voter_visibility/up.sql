@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE questions ADD COLUMN voter_visibility VARCHAR(16) NOT NULL DEFAULT 'anonymous'
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX questions_search_vector_idx;
+
+ALTER TABLE questions DROP COLUMN search_vector
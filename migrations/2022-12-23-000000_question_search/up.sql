@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Questions are indexed with both English and French stems, so either
+-- language finds them. The column is only read through SQL fragments in
+-- `services::question::search` and stays out of `schema.rs`.
+ALTER TABLE questions ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (
+        to_tsvector('english', text) || to_tsvector('french', text)
+    ) STORED;
+
+CREATE INDEX questions_search_vector_idx ON questions USING GIN (search_vector)
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX questions_normalized_text_trgm_idx;
+
+ALTER TABLE questions DROP COLUMN normalized_text;
+
+DROP FUNCTION normalize_question_text(TEXT)
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- Lower case, without accents or punctuation, so near duplicates compare
+-- equal. `unaccent` is only stable because its dictionary could change,
+-- which would at worst leave older rows slightly off.
+CREATE FUNCTION normalize_question_text(TEXT) RETURNS TEXT AS $$
+    SELECT trim(regexp_replace(
+        lower(public.unaccent('public.unaccent'::regdictionary, $1)),
+        '[^[:alnum:]]+', ' ', 'g'
+    ))
+$$ LANGUAGE SQL IMMUTABLE PARALLEL SAFE STRICT;
+
+-- Only read through SQL fragments in `services::question::get_similar`, like
+-- `search_vector`.
+ALTER TABLE questions ADD COLUMN normalized_text TEXT
+    GENERATED ALWAYS AS (normalize_question_text(text)) STORED;
+
+CREATE INDEX questions_normalized_text_trgm_idx ON questions
+    USING GIN (normalized_text gin_trgm_ops)
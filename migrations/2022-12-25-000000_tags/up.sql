@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE tags (
+    id uuid DEFAULT uuid_generate_v4(),
+    name VARCHAR(32) NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+);
+
+-- Tag names are matched by prefix for autocompletion.
+CREATE INDEX tags_name_pattern_idx ON tags (name varchar_pattern_ops);
+
+CREATE TABLE question_tags (
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    tag_id uuid NOT NULL references tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX question_tags_tag_id_idx ON question_tags (tag_id)
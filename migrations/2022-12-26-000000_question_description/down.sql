@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE question_links;
+
+DROP TABLE link_previews;
+
+DROP INDEX questions_search_vector_idx;
+ALTER TABLE questions DROP COLUMN search_vector;
+ALTER TABLE questions ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (
+        to_tsvector('english', text) || to_tsvector('french', text)
+    ) STORED;
+CREATE INDEX questions_search_vector_idx ON questions USING GIN (search_vector);
+
+ALTER TABLE questions
+    DROP COLUMN description,
+    DROP COLUMN description_html,
+    DROP COLUMN source_url
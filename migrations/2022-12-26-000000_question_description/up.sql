@@ -0,0 +1,42 @@
+-- Your SQL goes here
+ALTER TABLE questions
+    ADD COLUMN description TEXT,
+    ADD COLUMN description_html TEXT,
+    ADD COLUMN source_url VARCHAR(2048);
+
+-- Descriptions are searched too, ranked below the question itself.
+DROP INDEX questions_search_vector_idx;
+ALTER TABLE questions DROP COLUMN search_vector;
+ALTER TABLE questions ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', text), 'A')
+            || setweight(to_tsvector('french', text), 'A')
+            || setweight(to_tsvector('english', coalesce(description, '')), 'B')
+            || setweight(to_tsvector('french', coalesce(description, '')), 'B')
+    ) STORED;
+CREATE INDEX questions_search_vector_idx ON questions USING GIN (search_vector);
+
+-- Previews are fetched in the background; `fetched_at` is NULL until then.
+CREATE TABLE link_previews (
+    url VARCHAR(2048) NOT NULL,
+    title VARCHAR(256),
+    description VARCHAR(1024),
+    image_url VARCHAR(2048),
+    error VARCHAR(256),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    fetched_at TIMESTAMP,
+    PRIMARY KEY (url)
+);
+
+CREATE INDEX link_previews_pending_idx ON link_previews (created_at)
+    WHERE fetched_at IS NULL;
+
+-- The links in a question's source URL and description, in order.
+CREATE TABLE question_links (
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    url VARCHAR(2048) NOT NULL references link_previews(url) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (question_id, url)
+);
+
+CREATE INDEX question_links_url_idx ON question_links (url)
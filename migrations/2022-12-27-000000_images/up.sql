@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- Uploads belong to their uploader until attached to a question. Unattached
+-- ones, including those of purged questions, are garbage collected.
+CREATE TABLE images (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    question_id uuid references questions(id) ON DELETE SET NULL,
+    position INTEGER NOT NULL DEFAULT 0,
+    content_type VARCHAR(32) NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    storage_key VARCHAR(128) NOT NULL,
+    thumbnail_key VARCHAR(128) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX images_question_id_idx ON images (question_id, position);
+CREATE INDEX images_unattached_idx ON images (created_at)
+    WHERE question_id IS NULL
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE comments (
+    id uuid DEFAULT uuid_generate_v4(),
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    parent_id uuid references comments(id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    deleted_at TIMESTAMP,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX comments_question_id_idx ON comments (question_id, created_at);
+CREATE INDEX comments_parent_id_idx ON comments (parent_id, created_at);
+CREATE INDEX comments_user_id_idx ON comments (user_id);
+
+CREATE TABLE comment_reactions (
+    comment_id uuid NOT NULL references comments(id) ON DELETE CASCADE,
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    kind VARCHAR(16) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, user_id, kind)
+);
+
+CREATE INDEX comment_reactions_user_id_idx ON comment_reactions (user_id)
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE bookmarks (
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    question_id uuid NOT NULL references questions(id) ON DELETE CASCADE,
+    follow BOOLEAN NOT NULL DEFAULT FALSE,
+    -- The highest vote threshold followers were notified of.
+    notified_votes INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, question_id)
+);
+
+CREATE INDEX bookmarks_user_id_created_at_idx ON bookmarks (user_id, created_at);
+CREATE INDEX bookmarks_followers_idx ON bookmarks (question_id) WHERE follow;
+
+CREATE TABLE notifications (
+    id uuid DEFAULT uuid_generate_v4(),
+    user_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    question_id uuid references questions(id) ON DELETE CASCADE,
+    kind VARCHAR(32) NOT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    read_at TIMESTAMP,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX notifications_user_id_created_at_idx ON notifications (user_id, created_at)
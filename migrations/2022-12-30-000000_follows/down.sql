@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX votes_question_id_created_at_idx;
+DROP TABLE follows
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE follows (
+    follower_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    followee_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (follower_id, followee_id),
+    CHECK (follower_id <> followee_id)
+);
+
+CREATE INDEX follows_followee_id_idx ON follows (followee_id, created_at);
+
+-- Recent votes rank the feed's trending questions.
+CREATE INDEX votes_question_id_created_at_idx ON votes (question_id, created_at)
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE moderation_actions;
+ALTER TABLE comments DROP COLUMN hidden_at;
+ALTER TABLE questions DROP COLUMN hidden_at;
+DROP TABLE reports
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+CREATE TABLE reports (
+    id uuid DEFAULT uuid_generate_v4(),
+    reporter_id uuid NOT NULL references users(id) ON DELETE CASCADE,
+    target_type VARCHAR(16) NOT NULL,
+    target_id uuid NOT NULL,
+    reason VARCHAR(32) NOT NULL,
+    note TEXT,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP,
+    resolved_by uuid references users(id) ON DELETE SET NULL,
+    PRIMARY KEY (id),
+    UNIQUE (reporter_id, target_type, target_id)
+);
+
+CREATE INDEX reports_pending_idx ON reports (created_at) WHERE status = 'pending';
+CREATE INDEX reports_target_idx ON reports (target_type, target_id);
+
+-- Content hidden until a moderator reviews it.
+ALTER TABLE questions ADD COLUMN hidden_at TIMESTAMP;
+ALTER TABLE comments ADD COLUMN hidden_at TIMESTAMP;
+
+-- Every moderation action, automatic ones having no moderator.
+CREATE TABLE moderation_actions (
+    id uuid DEFAULT uuid_generate_v4(),
+    moderator_id uuid references users(id) ON DELETE SET NULL,
+    action VARCHAR(16) NOT NULL,
+    target_type VARCHAR(16) NOT NULL,
+    target_id uuid NOT NULL,
+    report_id uuid references reports(id) ON DELETE SET NULL,
+    note TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX moderation_actions_created_at_idx ON moderation_actions (created_at);
+CREATE INDEX moderation_actions_target_idx ON moderation_actions (target_type, target_id)
@@ -0,0 +1,430 @@
+use dotenvy::dotenv;
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    net::IpAddr,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::validation::TEXT_COLUMN_LENGTH;
+
+static CONFIG: Lazy<Config> = Lazy::new(Config::from_env);
+
+/// Server configuration, read once from the environment (and `.env`).
+pub struct Config {
+    pub rate_limit: RateLimitConfig,
+    pub oauth: OAuthConfig,
+    pub password: PasswordConfig,
+    pub question: QuestionConfig,
+    pub deletion: DeletionConfig,
+    pub upload: UploadConfig,
+    pub notification: NotificationConfig,
+    pub feed: FeedConfig,
+    pub moderation: ModerationConfig,
+}
+
+/// A token bucket: `capacity` requests, refilled evenly over `period`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+/// `RATE_LIMIT_BACKEND` (`redis` or `memory`), `REDIS_URL`,
+/// `RATE_LIMIT_GLOBAL` (e.g. `300/1m`), `RATE_LIMITS`
+/// (e.g. `questions.create=5/1h,votes.create=60/1m`) and
+/// `RATE_LIMIT_API_TOKENS`, the tokens clients are limited by instead of
+/// their IP, and `RATE_LIMIT_TRUSTED_PROXIES`, the proxy addresses whose
+/// `X-Forwarded-For` header is believed.
+pub struct RateLimitConfig {
+    pub backend: String,
+    pub redis_url: String,
+    pub global: Option<RateLimit>,
+    pub operations: HashMap<String, RateLimit>,
+    pub api_tokens: HashSet<String>,
+    pub trusted_proxies: HashSet<IpAddr>,
+}
+
+/// `OAUTH_PROVIDERS` lists provider names (e.g. `google,github`), each
+/// configured with `OAUTH_<NAME>_*` variables, and `OAUTH_REDIRECT` is where
+/// the browser lands after logging in.
+pub struct OAuthConfig {
+    pub providers: HashMap<String, OAuthProvider>,
+    pub redirect: String,
+}
+
+/// An OAuth2 / OpenID Connect provider. Endpoints left unset are discovered
+/// from `<issuer>/.well-known/openid-configuration`.
+#[derive(Debug, Clone)]
+pub struct OAuthProvider {
+    pub client_id: String,
+    pub client_secret: String,
+    /// This server's callback URL, `.../auth/<name>/callback`
+    pub redirect_url: String,
+    pub issuer: Option<String>,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    pub scopes: String,
+    /// Link accounts by email even when the provider does not mark it as
+    /// verified (for providers that only return verified emails)
+    pub trust_email: bool,
+}
+
+/// Argon2id cost: `PASSWORD_MEMORY_KIB`, `PASSWORD_ITERATIONS` and
+/// `PASSWORD_PARALLELISM`. Run `cargo bench` to pick values for a host.
+///
+/// Policy: `PASSWORD_MIN_LENGTH`, `PASSWORD_MIN_SCORE` (zxcvbn score, 0 to
+/// 4), and `PASSWORD_BREACHED_DIR`, a directory of SHA-1 range files named
+/// by their 5 character prefix, as served by the Pwned Passwords API.
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub min_length: usize,
+    pub min_score: u8,
+    pub breached_dir: Option<PathBuf>,
+}
+
+/// `QUESTION_EDIT_WITH_VOTES`: whether authors may edit questions that
+/// already have votes. `QUESTION_DUPLICATE_SIMILARITY`: the trigram
+/// similarity (0 to 1) from which a new question is a possible duplicate.
+///
+/// `QUESTION_MIN_LENGTH` and `QUESTION_MAX_LENGTH` count characters as
+/// readers see them; the maximum cannot exceed the column's 128.
+/// `QUESTION_ALLOWED_CHARACTERS` is the inside of a regex character class
+/// (e.g. `\p{L}\p{N} ?!`) that questions and options must be made of.
+///
+/// `QUESTION_DESCRIPTION_MAX_LENGTH`: the longest description, in characters.
+/// `QUESTION_LINK_PREVIEWS`: whether linked pages are fetched for previews.
+pub struct QuestionConfig {
+    pub edit_with_votes: bool,
+    pub duplicate_similarity: f32,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub allowed_characters: String,
+    pub description_max_length: usize,
+    pub link_previews: bool,
+}
+
+/// `RESTORE_GRACE_DAYS`: how long deleted questions and users can be
+/// restored. `DELETED_RETENTION_DAYS`: when they are purged for good.
+pub struct DeletionConfig {
+    pub restore_grace_days: i64,
+    pub retention_days: i64,
+}
+
+/// Image uploads. `UPLOAD_STORAGE` picks where they are kept (`local` is
+/// the only backend so far): `UPLOAD_DIR`, served from `UPLOAD_BASE_URL`.
+/// `UPLOAD_MAX_BYTES` and `UPLOAD_MAX_DIMENSION` bound what is accepted,
+/// `UPLOAD_THUMBNAIL_SIZE` is the longest side of thumbnails, and uploads
+/// not attached to a question after `UPLOAD_UNUSED_HOURS` are deleted.
+pub struct UploadConfig {
+    pub storage: String,
+    pub dir: PathBuf,
+    pub base_url: String,
+    pub max_bytes: usize,
+    pub max_dimension: u32,
+    pub thumbnail_size: u32,
+    pub unused_hours: i64,
+}
+
+/// `FOLLOW_VOTE_THRESHOLDS` lists the vote counts (e.g. `10,100,1000`) at
+/// which the followers of a question are notified.
+pub struct NotificationConfig {
+    pub vote_thresholds: Vec<i64>,
+}
+
+/// The home feed ranks questions by their votes of the last
+/// `FEED_TRENDING_DAYS`, decaying with age. Questions by followed users
+/// count as if they had `FEED_FOLLOWED_BOOST` more votes.
+pub struct FeedConfig {
+    pub trending_days: i32,
+    pub followed_boost: f64,
+}
+
+/// `REPORT_HIDE_THRESHOLD`: how many pending reports hide a question or
+/// comment until a moderator reviews it.
+pub struct ModerationConfig {
+    pub hide_threshold: i64,
+}
+
+pub fn config() -> &'static Config {
+    &CONFIG
+}
+
+impl Config {
+    fn from_env() -> Self {
+        dotenv().ok();
+        Self {
+            rate_limit: RateLimitConfig::from_env(),
+            oauth: OAuthConfig::from_env(),
+            password: PasswordConfig::from_env(),
+            question: QuestionConfig::from_env(),
+            deletion: DeletionConfig::from_env(),
+            upload: UploadConfig::from_env(),
+            notification: NotificationConfig::from_env(),
+            feed: FeedConfig::from_env(),
+            moderation: ModerationConfig::from_env(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn from_env() -> Self {
+        let mut operations = HashMap::from([
+            ("questions.create".to_owned(), RateLimit::new(5, 3600)),
+            ("votes.create".to_owned(), RateLimit::new(60, 60)),
+            ("uploads.create".to_owned(), RateLimit::new(30, 3600)),
+        ]);
+
+        if let Ok(limits) = env::var("RATE_LIMITS") {
+            for entry in limits.split(',').filter(|e| !e.trim().is_empty()) {
+                let (operation, limit) = entry
+                    .split_once('=')
+                    .expect("RATE_LIMITS entries must be `operation=limit`.");
+                operations.insert(
+                    operation.trim().to_owned(),
+                    limit.parse().expect("Invalid rate limit in RATE_LIMITS."),
+                );
+            }
+        }
+
+        let global = match env::var("RATE_LIMIT_GLOBAL") {
+            Ok(limit) if limit == "none" => None,
+            Ok(limit) => {
+                Some(limit.parse().expect("Invalid RATE_LIMIT_GLOBAL."))
+            }
+            Err(_) => Some(RateLimit::new(300, 60)),
+        };
+
+        Self {
+            backend: var("RATE_LIMIT_BACKEND", "redis".to_owned()),
+            redis_url: var("REDIS_URL", "redis://127.0.0.1:6379".to_owned()),
+            global,
+            operations,
+            api_tokens: env::var("RATE_LIMIT_API_TOKENS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            trusted_proxies: env::var("RATE_LIMIT_TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|ip| !ip.is_empty())
+                .map(|ip| {
+                    ip.parse()
+                        .expect("Invalid IP in RATE_LIMIT_TRUSTED_PROXIES.")
+                })
+                .collect(),
+        }
+    }
+}
+
+impl OAuthConfig {
+    fn from_env() -> Self {
+        let providers = env::var("OAUTH_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let provider = OAuthProvider::from_env(&name);
+                (name, provider)
+            })
+            .collect();
+
+        Self {
+            providers,
+            redirect: var("OAUTH_REDIRECT", "/".to_owned()),
+        }
+    }
+}
+
+impl OAuthProvider {
+    fn from_env(name: &str) -> Self {
+        let prefix = format!("OAUTH_{}_", name.to_uppercase());
+        let required = |key: &str| {
+            env::var(format!("{}{}", prefix, key))
+                .unwrap_or_else(|_| panic!("{}{} must be set.", prefix, key))
+        };
+        let optional = |key: &str| env::var(format!("{}{}", prefix, key)).ok();
+
+        Self {
+            client_id: required("CLIENT_ID"),
+            client_secret: required("CLIENT_SECRET"),
+            redirect_url: required("REDIRECT_URL"),
+            issuer: optional("ISSUER"),
+            authorization_url: optional("AUTHORIZATION_URL"),
+            token_url: optional("TOKEN_URL"),
+            userinfo_url: optional("USERINFO_URL"),
+            scopes: optional("SCOPES")
+                .unwrap_or_else(|| "openid email profile".to_owned()),
+            trust_email: optional("TRUST_EMAIL").map_or(false, |v| v == "true"),
+        }
+    }
+}
+
+impl PasswordConfig {
+    fn from_env() -> Self {
+        Self {
+            memory_kib: var("PASSWORD_MEMORY_KIB", 19456),
+            iterations: var("PASSWORD_ITERATIONS", 2),
+            parallelism: var("PASSWORD_PARALLELISM", 1),
+            min_length: var("PASSWORD_MIN_LENGTH", 8),
+            min_score: var("PASSWORD_MIN_SCORE", 3),
+            breached_dir: env::var("PASSWORD_BREACHED_DIR")
+                .ok()
+                .map(PathBuf::from),
+        }
+    }
+}
+
+impl QuestionConfig {
+    fn from_env() -> Self {
+        Self {
+            edit_with_votes: var("QUESTION_EDIT_WITH_VOTES", false),
+            duplicate_similarity: var::<f32>(
+                "QUESTION_DUPLICATE_SIMILARITY",
+                0.6,
+            )
+            .clamp(0.0, 1.0),
+            min_length: var("QUESTION_MIN_LENGTH", 4),
+            max_length: var::<usize>("QUESTION_MAX_LENGTH", 128)
+                .min(TEXT_COLUMN_LENGTH),
+            // Letters, marks, digits, punctuation, symbols (emoji included),
+            // spaces and the joiner used in emoji sequences.
+            allowed_characters: var(
+                "QUESTION_ALLOWED_CHARACTERS",
+                r"\p{L}\p{M}\p{N}\p{P}\p{S}\p{Zs}\u{200D}".to_owned(),
+            ),
+            description_max_length: var(
+                "QUESTION_DESCRIPTION_MAX_LENGTH",
+                4000,
+            ),
+            link_previews: var("QUESTION_LINK_PREVIEWS", true),
+        }
+    }
+}
+
+impl DeletionConfig {
+    fn from_env() -> Self {
+        let restore_grace_days: i64 = var("RESTORE_GRACE_DAYS", 30);
+
+        Self {
+            restore_grace_days,
+            // Purging before the grace period ends would break restores.
+            retention_days: var::<i64>("DELETED_RETENTION_DAYS", 30)
+                .max(restore_grace_days),
+        }
+    }
+}
+
+impl UploadConfig {
+    fn from_env() -> Self {
+        Self {
+            storage: var("UPLOAD_STORAGE", "local".to_owned()),
+            dir: var("UPLOAD_DIR", PathBuf::from("uploads")),
+            base_url: var("UPLOAD_BASE_URL", "/uploads".to_owned())
+                .trim_end_matches('/')
+                .to_owned(),
+            max_bytes: var("UPLOAD_MAX_BYTES", 5 * 1024 * 1024),
+            max_dimension: var("UPLOAD_MAX_DIMENSION", 8192),
+            thumbnail_size: var("UPLOAD_THUMBNAIL_SIZE", 320),
+            unused_hours: var("UPLOAD_UNUSED_HOURS", 24),
+        }
+    }
+}
+
+impl NotificationConfig {
+    fn from_env() -> Self {
+        let mut vote_thresholds: Vec<i64> =
+            var("FOLLOW_VOTE_THRESHOLDS", "10,100,1000".to_owned())
+                .split(',')
+                .filter_map(|threshold| threshold.trim().parse().ok())
+                .filter(|threshold| *threshold > 0)
+                .collect();
+        vote_thresholds.sort_unstable();
+        vote_thresholds.dedup();
+
+        Self { vote_thresholds }
+    }
+}
+
+impl FeedConfig {
+    fn from_env() -> Self {
+        Self {
+            trending_days: var::<i32>("FEED_TRENDING_DAYS", 7).max(1),
+            followed_boost: var::<f64>("FEED_FOLLOWED_BOOST", 10.0).max(0.0),
+        }
+    }
+}
+
+impl ModerationConfig {
+    fn from_env() -> Self {
+        Self {
+            hide_threshold: var::<i64>("REPORT_HIDE_THRESHOLD", 3).max(1),
+        }
+    }
+}
+
+impl RateLimit {
+    pub fn new(capacity: u32, period_secs: u64) -> Self {
+        Self {
+            capacity,
+            period: Duration::from_secs(period_secs),
+        }
+    }
+
+    /// Tokens added back to the bucket per millisecond.
+    pub fn refill_per_ms(&self) -> f64 {
+        self.capacity as f64 / self.period.as_millis().max(1) as f64
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    /// Parses `<count>/<period>` where the period is a number of seconds
+    /// optionally suffixed with `s`, `m`, `h` or `d` (`60/1m`, `5/3600`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (capacity, period) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("Expected `count/period`, got `{}`.", s))?;
+        let capacity =
+            capacity.trim().parse::<u32>().map_err(|e| e.to_string())?;
+        let period = period.trim();
+        let (amount, unit) = match period.char_indices().last() {
+            Some((i, c)) if c.is_alphabetic() => (&period[..i], c),
+            _ => (period, 's'),
+        };
+        let amount = amount.parse::<u64>().map_err(|e| e.to_string())?;
+        let multiplier = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(format!("Unknown period unit `{}`.", unit)),
+        };
+
+        if capacity == 0 || amount == 0 {
+            return Err("Rate limits must be greater than zero.".to_owned());
+        }
+
+        Ok(Self::new(capacity, amount * multiplier))
+    }
+}
+
+fn var<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
@@ -13,3 +13,78 @@ pub fn get_pool() -> PostgresPool {
         .build(mgr)
         .expect("could not build connection pool")
 }
+
+/// A connection to `TEST_DATABASE_URL`, a migrated database, for the tests
+/// that need one. They are skipped when it is not set.
+#[cfg(test)]
+pub fn test_connection() -> Option<PgConnection> {
+    use diesel::Connection;
+
+    dotenv().ok();
+    let url = env::var("TEST_DATABASE_URL").ok()?;
+
+    Some(
+        PgConnection::establish(&url)
+            .expect("Failed to connect to the test database."),
+    )
+}
+
+/// Runs `test` on a connection to `TEST_DATABASE_URL`, or skips it when that
+/// is not set. Its writes are committed, so it must clean up after itself.
+#[cfg(test)]
+pub fn with_test_connection(test: impl FnOnce(&mut PgConnection)) {
+    match test_connection() {
+        Some(mut conn) => test(&mut conn),
+        None => eprintln!("TEST_DATABASE_URL is not set, skipping."),
+    }
+}
+
+/// Runs `test` in a transaction on `TEST_DATABASE_URL` that is rolled back
+/// afterwards, or skips it when that is not set.
+#[cfg(test)]
+pub fn with_test_transaction(
+    test: impl FnOnce(&mut PgConnection) -> diesel::QueryResult<()>,
+) {
+    use diesel::Connection;
+
+    with_test_connection(|conn| conn.test_transaction(test))
+}
+
+/// Creates a user who cannot log in, for tests.
+#[cfg(test)]
+pub fn create_test_user(conn: &mut PgConnection) -> uuid::Uuid {
+    use crate::{schema::users, services::password::UNUSABLE};
+    use diesel::prelude::*;
+
+    let suffix = uuid::Uuid::new_v4().to_string();
+
+    diesel::insert_into(users::table)
+        .values((
+            users::username.eq(format!("t{}", &suffix[..8])),
+            users::email.eq(format!("{}@example.com", suffix)),
+            users::password.eq(UNUSABLE),
+        ))
+        .returning(users::id)
+        .get_result(conn)
+        .unwrap()
+}
+
+/// Creates an open question by `userid`, with the defaults of the
+/// `questions` table, for tests.
+#[cfg(test)]
+pub fn create_test_question(
+    conn: &mut PgConnection,
+    userid: uuid::Uuid,
+) -> uuid::Uuid {
+    use crate::schema::questions;
+    use diesel::prelude::*;
+
+    diesel::insert_into(questions::table)
+        .values((
+            questions::text.eq(format!("Question {}?", uuid::Uuid::new_v4())),
+            questions::user_id.eq(userid),
+        ))
+        .returning(questions::id)
+        .get_result(conn)
+        .unwrap()
+}
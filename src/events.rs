@@ -0,0 +1,83 @@
+use std::fmt;
+
+use diesel::PgConnection;
+
+use crate::{
+    config::config,
+    models::{notification::NotificationKind, question::Question},
+    services,
+};
+
+/// Something that happened which other parts of the server may react to.
+pub enum Event {
+    QuestionOpened(Question),
+    QuestionClosed(Question),
+    /// A vote was cast on the question, which now has this many
+    VoteCast(Question, i64),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::QuestionOpened(question) => {
+                write!(f, "question {} opened", question.id)
+            }
+            Event::QuestionClosed(question) => {
+                write!(f, "question {} closed", question.id)
+            }
+            Event::VoteCast(question, votes) => {
+                write!(f, "question {} has {} votes", question.id, votes)
+            }
+        }
+    }
+}
+
+/// Dispatches an event to its handlers.
+pub fn emit(conn: &mut PgConnection, event: Event) {
+    log::info!("Event: {}", event);
+
+    let result = match &event {
+        Event::QuestionOpened(_) => Ok(()),
+        Event::QuestionClosed(question) => {
+            services::notification::notify_followers(
+                conn,
+                question.id,
+                NotificationKind::QuestionClosed,
+                &format!("“{}” closed.", question.text),
+            )
+            .map(|_| ())
+        }
+        Event::VoteCast(question, votes) => {
+            notify_votes(conn, question, *votes)
+        }
+    };
+
+    if let Err(e) = result {
+        log::error!("Failed to handle event {}: {}", event, e);
+    }
+}
+
+/// Notifies followers of the highest vote threshold the question reached.
+fn notify_votes(
+    conn: &mut PgConnection,
+    question: &Question,
+    votes: i64,
+) -> diesel::QueryResult<()> {
+    let threshold = config()
+        .notification
+        .vote_thresholds
+        .iter()
+        .rev()
+        .find(|threshold| **threshold <= votes);
+
+    if let Some(threshold) = threshold {
+        services::notification::notify_vote_threshold(
+            conn,
+            question,
+            *threshold as i32,
+            &format!("“{}” reached {} votes.", question.text, threshold),
+        )?;
+    }
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use crate::{
     context::Context,
     graphql::{
+        comment_resolver::CommentMutation,
+        moderation_resolver::{ModerationMutation, ModerationQuery},
         question_resolver::QuestionQuery,
+        tag_resolver::{TagMutation, TagQuery},
         user_resolver::{UserMutation, UserQuery},
     },
 };
@@ -10,7 +13,10 @@ use juniper::graphql_object;
 
 use self::question_resolver::QuestionMutation;
 
+mod comment_resolver;
+mod moderation_resolver;
 mod question_resolver;
+mod tag_resolver;
 mod user_resolver;
 mod vote_resolver;
 
@@ -31,6 +37,12 @@ impl QueryRoot {
     fn votes(&self) -> vote_resolver::VoteQuery {
         vote_resolver::VoteQuery
     }
+    fn tags(&self) -> TagQuery {
+        TagQuery
+    }
+    fn moderation(&self) -> ModerationQuery {
+        ModerationQuery
+    }
 }
 
 pub struct MutationRoot;
@@ -47,4 +59,13 @@ impl MutationRoot {
     fn votes(&self) -> vote_resolver::VoteMutation {
         vote_resolver::VoteMutation
     }
+    fn tags(&self) -> TagMutation {
+        TagMutation
+    }
+    fn comments(&self) -> CommentMutation {
+        CommentMutation
+    }
+    fn moderation(&self) -> ModerationMutation {
+        ModerationMutation
+    }
 }
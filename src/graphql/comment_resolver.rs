@@ -0,0 +1,429 @@
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use juniper::FieldResult;
+use uuid::Uuid;
+
+use crate::{
+    context::Context,
+    models::{
+        comment::{
+            Comment, CommentInput, CommentResponse, CommentSort, ReactionCount,
+            ReactionKind,
+        },
+        question::QuestionStatus,
+        types::FieldError,
+        user::User,
+    },
+    services, validation,
+};
+
+use super::question_resolver::can_see_hidden;
+
+///A comment on a question
+#[juniper::graphql_object(Context = Context)]
+impl Comment {
+    /// The comment's id (UUID)
+    fn id(&self) -> Uuid {
+        self.id
+    }
+    /// The question the comment is about
+    fn question_id(&self) -> Uuid {
+        self.question_id
+    }
+    /// The comment replied to, if any
+    fn parent_id(&self) -> Option<Uuid> {
+        self.parent_id
+    }
+    /// The user who wrote the comment, none once it is deleted or hidden
+    fn user_id(&self) -> Option<Uuid> {
+        self.is_visible().then_some(self.user_id)
+    }
+    /// The username of who wrote the comment, none once it is deleted or
+    /// hidden
+    fn username(&self, ctx: &Context) -> Option<String> {
+        if !self.is_visible() {
+            return None;
+        }
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        services::user::get_by_id(&mut conn, self.user_id)
+            .ok()
+            .map(|user| user.username)
+    }
+    /// The comment's text, in markdown, none once it is deleted or hidden
+    fn body(&self) -> Option<&str> {
+        self.is_visible().then_some(self.body.as_str())
+    }
+    /// The comment's text, rendered to sanitized HTML, none once it is
+    /// deleted or hidden
+    fn body_html(&self) -> Option<String> {
+        self.is_visible()
+            .then(|| services::markdown::render(&self.body))
+    }
+    /// The date and time the comment was written
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+    /// The date and time the comment was last edited
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+    /// Whether the comment was edited since it was written
+    fn edited(&self) -> bool {
+        self.updated_at > self.created_at
+    }
+    /// Whether the comment was deleted. Deleted comments are listed while
+    /// they have replies.
+    fn deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+    /// Whether the comment is hidden until a moderator reviews it. Hidden
+    /// comments are listed while they have replies.
+    fn hidden(&self) -> bool {
+        self.hidden_at.is_some()
+    }
+    /// The number of replies to the comment
+    fn reply_count(&self, ctx: &Context) -> FieldResult<i32> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::comment::count_replies(&mut conn, self.id)? as i32)
+    }
+    /// The replies to the comment, oldest first unless sorted otherwise
+    fn replies(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        sort: Option<CommentSort>,
+    ) -> FieldResult<Vec<Comment>> {
+        paginate(
+            ctx,
+            self.question_id,
+            Some(self.id),
+            first,
+            after,
+            sort.unwrap_or(CommentSort::Old),
+        )
+    }
+    /// The reactions to the comment, by kind
+    fn reactions(&self, ctx: &Context) -> FieldResult<Vec<ReactionCount>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let reacted = match user_id {
+            Some(user_id) => services::comment::get_reactions_by_user_id(
+                &mut conn, self.id, user_id,
+            )?,
+            None => vec![],
+        };
+
+        Ok(services::comment::count_reactions(&mut conn, self.id)?
+            .into_iter()
+            .map(|(kind, count)| ReactionCount {
+                kind,
+                count: count as i32,
+                reacted: reacted.contains(&kind),
+            })
+            .collect())
+    }
+}
+
+pub struct CommentMutation;
+
+#[juniper::graphql_object(Context = Context)]
+impl CommentMutation {
+    /// Comment on a question, or reply to one of its comments with
+    /// `parentId`. Comments are written in markdown.
+    fn create(
+        ctx: &Context,
+        question_id: String,
+        body: String,
+        parent_id: Option<String>,
+    ) -> CommentResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let user = match get_user(&mut conn, user_id) {
+            Ok(user) => user,
+            Err(e) => return CommentResponse::from_errors(vec![e]),
+        };
+
+        let question = Uuid::parse_str(&question_id)
+            .ok()
+            .and_then(|question_id| {
+                services::question::get_by_id(&mut conn, question_id).ok()
+            })
+            .filter(|question| {
+                (question.status != QuestionStatus::Draft
+                    && question.hidden_at.is_none())
+                    || question.user_id == user.id
+                    || user.can_moderate()
+            });
+
+        let question = match question {
+            Some(question) => question,
+            None => {
+                return CommentResponse::from_error(
+                    "questionId".to_owned(),
+                    "No question found with corresponding Id.".to_owned(),
+                )
+            }
+        };
+
+        let parent_id = match parent_id {
+            Some(parent_id) => {
+                let parent =
+                    Uuid::parse_str(&parent_id).ok().and_then(|parent_id| {
+                        services::comment::get_by_id_and_question_id(
+                            &mut conn,
+                            parent_id,
+                            question.id,
+                        )
+                        .ok()
+                    });
+
+                match parent {
+                    Some(parent) => Some(parent.id),
+                    None => {
+                        return CommentResponse::from_error(
+                            "parentId".to_owned(),
+                            "No comment found with corresponding Id."
+                                .to_owned(),
+                        )
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let body = match validate_body(&body) {
+            Ok(body) => body,
+            Err(e) => return CommentResponse::from_errors(vec![e]),
+        };
+
+        match services::comment::create(
+            &mut conn,
+            CommentInput {
+                question_id: question.id,
+                user_id: user.id,
+                parent_id,
+                body,
+            },
+        ) {
+            Ok(comment) => CommentResponse::from_comment(comment),
+            Err(e) => {
+                CommentResponse::from_error("comment".to_owned(), e.to_string())
+            }
+        }
+    }
+
+    /// Change a comment's text. Only its author can.
+    fn edit(
+        ctx: &Context,
+        comment_id: String,
+        body: String,
+    ) -> CommentResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let comment = match get_comment(&mut conn, &comment_id) {
+            Ok(comment) => comment,
+            Err(e) => return CommentResponse::from_errors(vec![e]),
+        };
+
+        if !services::comment::can_edit(&comment, user_id) {
+            return CommentResponse::from_error(
+                "commentId".to_owned(),
+                "Only the comment's author can edit it.".to_owned(),
+            );
+        }
+
+        let body = match validate_body(&body) {
+            Ok(body) => body,
+            Err(e) => return CommentResponse::from_errors(vec![e]),
+        };
+
+        match services::comment::update_body(&mut conn, comment.id, &body) {
+            Ok(comment) => CommentResponse::from_comment(comment),
+            Err(e) => {
+                CommentResponse::from_error("comment".to_owned(), e.to_string())
+            }
+        }
+    }
+
+    /// Delete a comment. Only its author or a moderator can. Its replies
+    /// stay.
+    fn delete(ctx: &Context, comment_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let user = get_user(&mut conn, user_id)
+            .map_err(|e| juniper::FieldError::from(e.message))?;
+        let comment = get_comment(&mut conn, &comment_id)
+            .map_err(|e| juniper::FieldError::from(e.message))?;
+
+        if !services::comment::can_delete(&comment, &user) {
+            return Err(juniper::FieldError::from(
+                "Only the comment's author or a moderator can delete it.",
+            ));
+        }
+
+        services::comment::delete(&mut conn, comment.id)?;
+
+        Ok(true)
+    }
+
+    /// React to a comment. Each kind of reaction counts once per user.
+    fn react(
+        ctx: &Context,
+        comment_id: String,
+        kind: ReactionKind,
+    ) -> CommentResponse {
+        set_reaction(ctx, &comment_id, kind, true)
+    }
+
+    /// Take back a reaction to a comment.
+    fn unreact(
+        ctx: &Context,
+        comment_id: String,
+        kind: ReactionKind,
+    ) -> CommentResponse {
+        set_reaction(ctx, &comment_id, kind, false)
+    }
+}
+
+/// The question's comments, or the replies to `parent_id`, `first` at a
+/// time after the `after` comment.
+pub(super) fn paginate(
+    ctx: &Context,
+    question_id: Uuid,
+    parent_id: Option<Uuid>,
+    first: Option<i32>,
+    after: Option<String>,
+    sort: CommentSort,
+) -> FieldResult<Vec<Comment>> {
+    let mut conn = ctx
+        .pool
+        .get()
+        .expect("Failed to get connection to database.");
+    let question = services::question::get_by_id(&mut conn, question_id)
+        .ok()
+        .filter(|question| {
+            question.hidden_at.is_none()
+                || can_see_hidden(&mut conn, ctx, question)
+        })
+        .ok_or_else(|| {
+            juniper::FieldError::from(
+                "No question found with corresponding Id.",
+            )
+        })?;
+    let first = first.unwrap_or(20).clamp(1, 100);
+    let after = match after {
+        Some(after) => Some(Uuid::parse_str(&after)?),
+        None => None,
+    };
+
+    Ok(services::comment::get_paginated(
+        &mut conn,
+        question.id,
+        parent_id,
+        sort,
+        first,
+        after,
+    )?)
+}
+
+fn set_reaction(
+    ctx: &Context,
+    comment_id: &str,
+    kind: ReactionKind,
+    reacted: bool,
+) -> CommentResponse {
+    let mut conn = ctx
+        .pool
+        .get()
+        .expect("Failed to get connection to database.");
+    let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+    let user = match get_user(&mut conn, user_id) {
+        Ok(user) => user,
+        Err(e) => return CommentResponse::from_errors(vec![e]),
+    };
+
+    let comment = match get_comment(&mut conn, comment_id) {
+        Ok(comment) => comment,
+        Err(e) => return CommentResponse::from_errors(vec![e]),
+    };
+
+    let result = if reacted {
+        services::comment::react(&mut conn, comment.id, user.id, kind)
+    } else {
+        services::comment::unreact(&mut conn, comment.id, user.id, kind)
+    };
+
+    match result {
+        Ok(()) => CommentResponse::from_comment(comment),
+        Err(e) => CommentResponse::from_error("kind".to_owned(), e.to_string()),
+    }
+}
+
+fn validate_body(body: &str) -> Result<String, FieldError> {
+    let body = validation::normalize_multiline(body);
+
+    validation::check_comment(&body, MAX_COMMENT_LENGTH)
+        .map_err(|e| FieldError::new("body".to_owned(), e))?;
+
+    Ok(body)
+}
+
+fn get_user(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+) -> Result<User, FieldError> {
+    user_id
+        .and_then(|user_id| services::user::get_by_id(conn, user_id).ok())
+        .ok_or_else(|| {
+            FieldError::new(
+                "userId".to_owned(),
+                "User not logged in.".to_owned(),
+            )
+        })
+}
+
+fn get_comment(
+    conn: &mut PgConnection,
+    comment_id: &str,
+) -> Result<Comment, FieldError> {
+    Uuid::parse_str(comment_id)
+        .ok()
+        .and_then(|comment_id| {
+            services::comment::get_by_id(conn, comment_id).ok()
+        })
+        .ok_or_else(|| {
+            FieldError::new(
+                "commentId".to_owned(),
+                "No comment found with corresponding Id.".to_owned(),
+            )
+        })
+}
+
+const MAX_COMMENT_LENGTH: usize = 2000;
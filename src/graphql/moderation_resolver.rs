@@ -0,0 +1,472 @@
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use juniper::FieldResult;
+use uuid::Uuid;
+
+use crate::{
+    config::config,
+    context::Context,
+    models::{
+        comment::Comment,
+        notification::NotificationKind,
+        question::{Question, QuestionStatus},
+        report::{
+            ModerationAction, ModerationDecision, Report, ReportInput,
+            ReportReason, ReportResponse, ReportStatus, ReportTargetType,
+        },
+        types::FieldError,
+        user::User,
+    },
+    services, validation,
+};
+
+///A user's report of a question or comment
+#[juniper::graphql_object(Context = Context)]
+impl Report {
+    /// The report's id (UUID)
+    fn id(&self) -> Uuid {
+        self.id
+    }
+    /// The user who filed the report
+    fn reporter_id(&self) -> Uuid {
+        self.reporter_id
+    }
+    /// What kind of content is reported
+    fn target_type(&self) -> ReportTargetType {
+        self.target_type
+    }
+    /// The reported question or comment's id
+    fn target_id(&self) -> Uuid {
+        self.target_id
+    }
+    /// Why the content is reported
+    fn reason(&self) -> ReportReason {
+        self.reason
+    }
+    /// The reporter's explanation
+    fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+    /// Where the report is in review
+    fn status(&self) -> ReportStatus {
+        self.status
+    }
+    /// The date and time the report was filed
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+    /// The date and time a moderator resolved the report, if one did
+    fn resolved_at(&self) -> Option<NaiveDateTime> {
+        self.resolved_at
+    }
+    /// The moderator who resolved the report
+    fn resolved_by(&self) -> Option<Uuid> {
+        self.resolved_by
+    }
+    /// The reported question, unless it was deleted
+    fn question(&self, ctx: &Context) -> Option<Question> {
+        if self.target_type != ReportTargetType::Question {
+            return None;
+        }
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        services::question::get_by_id(&mut conn, self.target_id).ok()
+    }
+    /// The reported comment, unless it was deleted
+    fn comment(&self, ctx: &Context) -> Option<Comment> {
+        if self.target_type != ReportTargetType::Comment {
+            return None;
+        }
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        services::comment::get_by_id(&mut conn, self.target_id).ok()
+    }
+    /// The number of pending reports on the same content
+    fn pending_reports(&self, ctx: &Context) -> FieldResult<i32> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::report::count_pending_for_target(
+            &mut conn,
+            self.target_type,
+            self.target_id,
+        )? as i32)
+    }
+}
+
+pub struct ModerationQuery;
+
+#[juniper::graphql_object(Context = Context)]
+impl ModerationQuery {
+    /// Pending reports, oldest first. Pass the last one's id as `after` for
+    /// the next page. Only moderators can see them.
+    fn queue(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<Vec<Report>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        get_moderator(&mut conn, user_id)
+            .map_err(|e| juniper::FieldError::from(e.message))?;
+
+        let first = first.unwrap_or(20).clamp(1, 100);
+        let after = match after {
+            Some(after) => {
+                let report = services::report::get_by_id(
+                    &mut conn,
+                    Uuid::parse_str(&after)?,
+                )
+                .map_err(|_| {
+                    juniper::FieldError::from(
+                        "No report found with corresponding Id.",
+                    )
+                })?;
+                Some((report.created_at, report.id))
+            }
+            None => None,
+        };
+
+        Ok(services::report::get_pending(&mut conn, first, after)?)
+    }
+
+    /// The moderation audit trail, newest first, only about `targetId` if
+    /// given. Pass the last action's id as `after` for the next page. Only
+    /// moderators can see it.
+    fn log(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        target_id: Option<String>,
+    ) -> FieldResult<Vec<ModerationAction>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        get_moderator(&mut conn, user_id)
+            .map_err(|e| juniper::FieldError::from(e.message))?;
+
+        let first = first.unwrap_or(20).clamp(1, 100);
+        let target_id = match target_id {
+            Some(target_id) => Some(Uuid::parse_str(&target_id)?),
+            None => None,
+        };
+        let after = match after {
+            Some(after) => {
+                let action = services::report::get_action_by_id(
+                    &mut conn,
+                    Uuid::parse_str(&after)?,
+                )
+                .map_err(|_| {
+                    juniper::FieldError::from(
+                        "No action found with corresponding Id.",
+                    )
+                })?;
+                Some((action.created_at, action.id))
+            }
+            None => None,
+        };
+
+        Ok(services::report::get_actions(
+            &mut conn, target_id, first, after,
+        )?)
+    }
+}
+
+pub struct ModerationMutation;
+
+#[juniper::graphql_object(Context = Context)]
+impl ModerationMutation {
+    /// Report a question or comment to the moderators. Content with enough
+    /// pending reports is hidden until a moderator reviews it.
+    fn report(
+        ctx: &Context,
+        target_type: ReportTargetType,
+        target_id: String,
+        reason: ReportReason,
+        note: Option<String>,
+    ) -> ReportResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let user = match get_user(&mut conn, user_id) {
+            Ok(user) => user,
+            Err(e) => return ReportResponse::from_errors(vec![e]),
+        };
+
+        let target = match get_target(&mut conn, target_type, &target_id) {
+            Ok(target) => target,
+            Err(e) => return ReportResponse::from_errors(vec![e]),
+        };
+
+        if target.author_id == user.id {
+            return ReportResponse::from_error(
+                "targetId".to_owned(),
+                "You cannot report your own content.".to_owned(),
+            );
+        }
+
+        let note = match validate_note(note, reason == ReportReason::Other) {
+            Ok(note) => note,
+            Err(e) => return ReportResponse::from_errors(vec![e]),
+        };
+
+        if let Ok(Some(_)) = services::report::get_by_reporter(
+            &mut conn,
+            user.id,
+            target_type,
+            target.id,
+        ) {
+            return ReportResponse::from_error(
+                "targetId".to_owned(),
+                "You already reported this.".to_owned(),
+            );
+        }
+
+        let result = services::report::create(
+            &mut conn,
+            ReportInput {
+                reporter_id: user.id,
+                target_type,
+                target_id: target.id,
+                reason,
+                note,
+            },
+            config().moderation.hide_threshold,
+        );
+
+        match result {
+            Ok(report) => ReportResponse::from_report(report),
+            Err(e) => {
+                ReportResponse::from_error("report".to_owned(), e.to_string())
+            }
+        }
+    }
+
+    /// Resolve a pending report. Approving keeps the content and removing
+    /// deletes it, both closing every pending report on it; dismissing
+    /// closes this report only. Reporters are notified of the outcome. Only
+    /// moderators can.
+    fn resolve(
+        ctx: &Context,
+        report_id: String,
+        decision: ModerationDecision,
+        note: Option<String>,
+    ) -> ReportResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let moderator = match get_moderator(&mut conn, user_id) {
+            Ok(moderator) => moderator,
+            Err(e) => return ReportResponse::from_errors(vec![e]),
+        };
+
+        let report = Uuid::parse_str(&report_id)
+            .ok()
+            .and_then(|report_id| {
+                services::report::get_by_id(&mut conn, report_id).ok()
+            })
+            .filter(|report| report.status == ReportStatus::Pending);
+
+        let report = match report {
+            Some(report) => report,
+            None => {
+                return ReportResponse::from_error(
+                    "reportId".to_owned(),
+                    "No pending report found with corresponding Id.".to_owned(),
+                )
+            }
+        };
+
+        let note = match validate_note(note, false) {
+            Ok(note) => note,
+            Err(e) => return ReportResponse::from_errors(vec![e]),
+        };
+
+        let status = match decision {
+            ModerationDecision::Approve => ReportStatus::Approved,
+            ModerationDecision::Remove => ReportStatus::Removed,
+            ModerationDecision::Dismiss => ReportStatus::Dismissed,
+        };
+
+        let resolved = match services::report::resolve(
+            &mut conn,
+            &report,
+            status,
+            moderator.id,
+            note,
+            config().moderation.hide_threshold,
+        ) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                return ReportResponse::from_error(
+                    "report".to_owned(),
+                    e.to_string(),
+                )
+            }
+        };
+
+        // Notifying reporters is best effort: the decision stands anyway.
+        let reporters: Vec<Uuid> =
+            resolved.iter().map(|report| report.reporter_id).collect();
+        let question_id = services::report::get_target_question_id(
+            &mut conn,
+            report.target_type,
+            report.target_id,
+        )
+        .ok();
+        if let Err(e) = services::notification::notify_users(
+            &mut conn,
+            &reporters,
+            question_id,
+            NotificationKind::ReportResolved,
+            outcome_message(decision),
+        ) {
+            log::error!("Failed to notify reporters of {}: {}", report.id, e);
+        }
+
+        match resolved
+            .into_iter()
+            .find(|resolved| resolved.id == report.id)
+        {
+            Some(report) => ReportResponse::from_report(report),
+            None => ReportResponse::from_error(
+                "reportId".to_owned(),
+                "No pending report found with corresponding Id.".to_owned(),
+            ),
+        }
+    }
+}
+
+/// What a report is about.
+struct Target {
+    id: Uuid,
+    author_id: Uuid,
+}
+
+fn get_target(
+    conn: &mut PgConnection,
+    target_type: ReportTargetType,
+    target_id: &str,
+) -> Result<Target, FieldError> {
+    let target_id = Uuid::parse_str(target_id).ok();
+
+    let target = match target_type {
+        ReportTargetType::Question => target_id
+            .and_then(|target_id| {
+                services::question::get_by_id(conn, target_id).ok()
+            })
+            .filter(|question| question.status != QuestionStatus::Draft)
+            .map(|question| Target {
+                id: question.id,
+                author_id: question.user_id,
+            }),
+        ReportTargetType::Comment => target_id
+            .and_then(|target_id| {
+                services::comment::get_by_id(conn, target_id).ok()
+            })
+            .map(|comment| Target {
+                id: comment.id,
+                author_id: comment.user_id,
+            }),
+    };
+
+    target.ok_or_else(|| {
+        FieldError::new(
+            "targetId".to_owned(),
+            "No content found with corresponding Id.".to_owned(),
+        )
+    })
+}
+
+fn outcome_message(decision: ModerationDecision) -> &'static str {
+    match decision {
+        ModerationDecision::Approve => {
+            "A moderator reviewed the content you reported and kept it."
+        }
+        ModerationDecision::Remove => {
+            "A moderator removed the content you reported. Thank you."
+        }
+        ModerationDecision::Dismiss => {
+            "A moderator reviewed your report and dismissed it."
+        }
+    }
+}
+
+/// Normalizes a report or moderator note, empty ones being none.
+fn validate_note(
+    note: Option<String>,
+    required: bool,
+) -> Result<Option<String>, FieldError> {
+    let note = note
+        .map(|note| validation::normalize_multiline(&note))
+        .filter(|note| !note.is_empty());
+
+    match &note {
+        Some(note) => validation::check_report_note(note, MAX_NOTE_LENGTH)
+            .map_err(|e| FieldError::new("note".to_owned(), e))?,
+        None if required => {
+            return Err(FieldError::new(
+                "note".to_owned(),
+                "Explain what is wrong when the reason is Other.".to_owned(),
+            ))
+        }
+        None => (),
+    }
+
+    Ok(note)
+}
+
+fn get_user(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+) -> Result<User, FieldError> {
+    user_id
+        .and_then(|user_id| services::user::get_by_id(conn, user_id).ok())
+        .ok_or_else(|| {
+            FieldError::new(
+                "userId".to_owned(),
+                "User not logged in.".to_owned(),
+            )
+        })
+}
+
+fn get_moderator(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+) -> Result<User, FieldError> {
+    let user = get_user(conn, user_id)?;
+
+    if !user.can_moderate() {
+        return Err(FieldError::new(
+            "userId".to_owned(),
+            "Only moderators can review reports.".to_owned(),
+        ));
+    }
+
+    Ok(user)
+}
+
+const MAX_NOTE_LENGTH: usize = 500;
@@ -1,12 +1,249 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Connection, PgConnection, QueryResult};
 use juniper::FieldResult;
 use uuid::Uuid;
 
 use crate::{
+    config::config,
     context::Context,
-    models::question::{QuestionInput, QuestionResponse, QuestionsResponse},
+    events::{self, Event},
+    models::{
+        bookmark::{Bookmark, BookmarkedQuestion},
+        comment::{Comment, CommentSort},
+        image::Image,
+        question::{
+            FeedItem, LinkPreview, Question, QuestionInput,
+            QuestionOptionsResponse, QuestionResponse, QuestionSearchResult,
+            QuestionSort, QuestionStatus, QuestionType, QuestionsResponse,
+            ResultsVisibility, ScaleInput, VoterVisibility,
+        },
+        report::ReportTargetType,
+        tag::Tag,
+        types::FieldError,
+        vote::Voter,
+    },
     services::{self, question::get_by_id},
+    storage::storage,
+    validation,
 };
 
+use super::{
+    comment_resolver, tag_resolver::validate_tags,
+    vote_resolver::check_results_visible,
+};
+
+///A question
+#[juniper::graphql_object(Context = Context)]
+impl Question {
+    /// The question's id (UUID)
+    fn id(&self) -> Uuid {
+        self.id
+    }
+    /// The question's text
+    fn text(&self) -> &str {
+        &self.text
+    }
+    /// The date and time the question was created
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+    /// The date and time the question was last updated
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+    /// The user who created the question
+    fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+    /// The date and time the question was deleted, if it was
+    fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+    /// Where the question is in its lifecycle
+    fn status(&self) -> QuestionStatus {
+        self.status
+    }
+    /// The date and time the question opens for votes, if scheduled
+    fn opens_at(&self) -> Option<NaiveDateTime> {
+        self.opens_at
+    }
+    /// The date and time the question stops accepting votes, if any
+    fn closes_at(&self) -> Option<NaiveDateTime> {
+        self.closes_at
+    }
+    /// Who can see the question's results, and when
+    fn results_visibility(&self) -> ResultsVisibility {
+        self.results_visibility
+    }
+    /// How the question is answered
+    fn question_type(&self) -> QuestionType {
+        self.question_type
+    }
+    /// The lowest accepted value, for ratings and numbers
+    fn scale_min(&self) -> Option<i32> {
+        self.scale_min
+    }
+    /// The highest accepted value, for ratings and numbers
+    fn scale_max(&self) -> Option<i32> {
+        self.scale_max
+    }
+    /// The interval between accepted ratings
+    fn scale_step(&self) -> Option<i32> {
+        self.scale_step
+    }
+    /// The label of the lowest rating
+    fn min_label(&self) -> Option<&str> {
+        self.min_label.as_deref()
+    }
+    /// The label of the highest rating
+    fn max_label(&self) -> Option<&str> {
+        self.max_label.as_deref()
+    }
+    /// The credits each voter can spend, for quadratic questions
+    fn credit_budget(&self) -> Option<i32> {
+        self.credit_budget
+    }
+    /// Who can see who voted what
+    fn voter_visibility(&self) -> VoterVisibility {
+        self.voter_visibility
+    }
+    /// The question's description, in markdown
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    /// The question's description, rendered to sanitized HTML
+    fn description_html(&self) -> Option<&str> {
+        self.description_html.as_deref()
+    }
+    /// Where the question comes from
+    fn source_url(&self) -> Option<&str> {
+        self.source_url.as_deref()
+    }
+    /// The date and time the question was hidden until a moderator reviews
+    /// it, if it is
+    fn hidden_at(&self) -> Option<NaiveDateTime> {
+        self.hidden_at
+    }
+    /// Previews of the source and the pages the description links to
+    fn links(&self, ctx: &Context) -> FieldResult<Vec<LinkPreview>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::link::get_by_question_id(&mut conn, self.id)?)
+    }
+    /// The question's images, in order
+    fn images(&self, ctx: &Context) -> FieldResult<Vec<Image>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::image::get_by_question_id(&mut conn, self.id)?)
+    }
+    /// The question's comments, most recent first unless sorted
+    /// otherwise. Replies are listed under each comment.
+    fn comments(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        sort: Option<CommentSort>,
+    ) -> FieldResult<Vec<Comment>> {
+        comment_resolver::paginate(
+            ctx,
+            self.id,
+            None,
+            first,
+            after,
+            sort.unwrap_or(CommentSort::New),
+        )
+    }
+    /// The question's tags, by name
+    fn tags(&self, ctx: &Context) -> FieldResult<Vec<Tag>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::tag::get_by_question_id(&mut conn, self.id)?)
+    }
+    /// Whether the logged in user bookmarked the question
+    fn bookmarked(&self, ctx: &Context) -> bool {
+        get_bookmark(ctx, self.id).is_some()
+    }
+    /// Whether the logged in user follows the question
+    fn followed(&self, ctx: &Context) -> bool {
+        get_bookmark(ctx, self.id).map_or(false, |bookmark| bookmark.follow)
+    }
+    /// Who voted what, most recent first, when the voter visibility allows
+    /// it
+    fn voters(&self, ctx: &Context) -> FieldResult<Vec<Voter>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        services::question::check_voters_visible(self, user_id)
+            .map_err(juniper::FieldError::from)?;
+        if self.voter_visibility == VoterVisibility::Public {
+            check_results_visible(&mut conn, user_id, self.id)?;
+        }
+
+        let mut picks =
+            services::vote::get_picks_by_question_id(&mut conn, self.id)?;
+
+        Ok(services::vote::get_all_with_usernames_by_question_id(
+            &mut conn, self.id,
+        )?
+        .into_iter()
+        .map(|(vote, username)| {
+            let picks = picks.remove(&vote.id).unwrap_or_default();
+
+            Voter {
+                user_id: vote.user_id,
+                username,
+                value: vote.value,
+                option_ids: picks.iter().map(|(id, _)| *id).collect(),
+                weights: picks.iter().map(|(_, weight)| *weight).collect(),
+                voted_at: vote.updated_at,
+            }
+        })
+        .collect())
+    }
+}
+
+///An image attached to a question
+#[juniper::graphql_object(Context = Context)]
+impl Image {
+    /// The image's id (UUID)
+    fn id(&self) -> Uuid {
+        self.id
+    }
+    /// Where to download the image from
+    fn url(&self) -> String {
+        storage().url(&self.storage_key)
+    }
+    /// Where to download the image's thumbnail from
+    fn thumbnail_url(&self) -> String {
+        storage().url(&self.thumbnail_key)
+    }
+    /// The image's MIME type
+    fn content_type(&self) -> &str {
+        &self.content_type
+    }
+    /// The image's width, in pixels
+    fn width(&self) -> i32 {
+        self.width
+    }
+    /// The image's height, in pixels
+    fn height(&self) -> i32 {
+        self.height
+    }
+}
+
 pub struct QuestionQuery;
 
 #[juniper::graphql_object(Context = Context)]
@@ -28,6 +265,16 @@ impl QuestionQuery {
         let question = get_by_id(&mut conn, question_id.unwrap());
 
         match question {
+            // Hidden questions wait for a moderator, out of sight.
+            Ok(question)
+                if question.hidden_at.is_some()
+                    && !can_see_hidden(&mut conn, ctx, &question) =>
+            {
+                QuestionResponse::from_error(
+                    "questionId".to_owned(),
+                    "No question found with corresponding Id.".to_owned(),
+                )
+            }
             Ok(question) => QuestionResponse::from_question(question),
             Err(e) => QuestionResponse::from_error(
                 "questionId".to_owned(),
@@ -36,50 +283,179 @@ impl QuestionQuery {
         }
     }
 
-    fn get_paginated(
+    /// The options of a choice question, in order.
+    fn get_options_for_question(
         ctx: &Context,
-        limit: Option<i32>,
-        cursor: Option<String>,
-    ) -> QuestionsResponse {
+        question_id: String,
+    ) -> QuestionOptionsResponse {
         let mut conn = ctx
             .pool
             .get()
             .expect("Failed to get connection to database.");
-        let limit = limit.unwrap_or(20);
+        let question_id = Uuid::parse_str(&question_id);
 
-        let cursor = match cursor {
-            Some(cursor) => {
-                let cursor = Uuid::parse_str(&cursor);
+        if let Err(e) = question_id {
+            return QuestionOptionsResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            );
+        }
 
-                if let Err(e) = cursor {
-                    return QuestionsResponse::from_error(
-                        "cursor".to_owned(),
-                        e.to_string(),
-                    );
-                }
+        let question = get_by_id(&mut conn, question_id.unwrap());
 
-                Some(cursor.unwrap())
+        let question = match question {
+            Ok(question)
+                if question.hidden_at.is_none()
+                    || can_see_hidden(&mut conn, ctx, &question) =>
+            {
+                question
+            }
+            _ => {
+                return QuestionOptionsResponse::from_error(
+                    "questionId".to_owned(),
+                    "No question found with corresponding Id.".to_owned(),
+                )
             }
-            None => None,
         };
 
-        if let Some(cursor) = cursor {
-            let question = get_by_id(&mut conn, cursor);
+        match services::question::get_options(&mut conn, question.id) {
+            Ok(options) => QuestionOptionsResponse::from_options(options),
+            Err(e) => QuestionOptionsResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Questions open to voters, most voted first. With `tags`, only
+    /// questions with all of them.
+    fn get_paginated(
+        ctx: &Context,
+        limit: Option<i32>,
+        cursor: Option<String>,
+        tags: Option<Vec<String>>,
+    ) -> QuestionsResponse {
+        paginate(ctx, limit, cursor, tags.unwrap_or_default())
+    }
 
-            if question.is_err() {
-                return QuestionsResponse::from_error(
-                    "cursor".to_owned(),
-                    "No question found with corresponding Id.".to_owned(),
-                );
+    /// Questions with the tag, most voted first.
+    fn by_tag(
+        ctx: &Context,
+        tag: String,
+        limit: Option<i32>,
+        cursor: Option<String>,
+    ) -> QuestionsResponse {
+        paginate(ctx, limit, cursor, vec![tag])
+    }
+
+    /// The logged in user's home feed: trending questions mixed with those
+    /// by users they follow, best first. Pass the last item's `cursor` as
+    /// `after` for the next page. With `excludeVoted`, questions they voted
+    /// on are left out.
+    fn feed(
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        exclude_voted: Option<bool>,
+    ) -> FieldResult<Vec<FeedItem>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let first = first.unwrap_or(20).clamp(1, 100);
+        // Later pages are scored as of the first one, so their order holds.
+        let (at, after) = match after {
+            Some(after) => {
+                let (at, after) = services::question::parse_feed_cursor(&after)
+                    .ok_or_else(|| {
+                        juniper::FieldError::from("Cursor is invalid.")
+                    })?;
+                get_by_id(&mut conn, after).map_err(|_| {
+                    juniper::FieldError::from(
+                        "No question found with corresponding Id.",
+                    )
+                })?;
+                (at, Some(after))
             }
+            None => {
+                let now = Utc::now().naive_utc();
+                let at = services::question::feed_time(now.timestamp_millis())
+                    .unwrap_or(now);
+                (at, None)
+            }
+        };
+
+        Ok(services::question::get_feed(
+            &mut conn,
+            user_id,
+            exclude_voted.unwrap_or(false),
+            first,
+            at,
+            after,
+        )?
+        .into_iter()
+        .map(|(question, from_followed_user)| FeedItem {
+            cursor: services::question::feed_cursor(at, question.id),
+            question,
+            from_followed_user,
+        })
+        .collect())
+    }
+
+    /// Finds questions matching `query`, in English or French. Quote a
+    /// phrase to match it exactly, use `or` for alternatives and `-` to
+    /// exclude a word. Best matches come first unless sorted otherwise.
+    fn search(
+        ctx: &Context,
+        query: String,
+        first: Option<i32>,
+        after: Option<String>,
+        sort: Option<QuestionSort>,
+    ) -> FieldResult<Vec<QuestionSearchResult>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let query = query.trim();
+
+        if query.is_empty() {
+            return Err(juniper::FieldError::from("Query cannot be empty."));
+        }
+        if query.chars().count() > MAX_SEARCH_LENGTH {
+            return Err(juniper::FieldError::from(format!(
+                "Query cannot be longer than {MAX_SEARCH_LENGTH} characters."
+            )));
         }
 
-        let questions =
-            services::question::get_paginated(&mut conn, limit, cursor);
+        let first = first.unwrap_or(20).clamp(1, 100);
+        let after = match after {
+            Some(after) => {
+                let after = Uuid::parse_str(&after)?;
+                get_by_id(&mut conn, after).map_err(|_| {
+                    juniper::FieldError::from(
+                        "No question found with corresponding Id.",
+                    )
+                })?;
+                Some(after)
+            }
+            None => None,
+        };
 
-        QuestionsResponse::from_questions(
-            questions.expect("Failed to get questions."),
-        )
+        Ok(services::question::search(
+            &mut conn,
+            query,
+            sort.unwrap_or(QuestionSort::Relevance),
+            first,
+            after,
+        )?
+        .into_iter()
+        .map(|(question, rank, snippet)| QuestionSearchResult {
+            question,
+            rank,
+            snippet,
+        })
+        .collect())
     }
 }
 
@@ -87,12 +463,48 @@ pub struct QuestionMutation;
 
 #[juniper::graphql_object(Context = Context)]
 impl QuestionMutation {
-    fn create(ctx: &Context, text: String) -> QuestionResponse {
+    /// Create a question. With `opensAt` in the future it stays a draft until
+    /// then; with `closesAt` it stops accepting votes at that time.
+    ///
+    /// Questions are rated from 0 to 5 unless `questionType` says otherwise.
+    /// Ratings take an optional `scale`, numeric questions optional bounds,
+    /// and choice questions their `options`. Quadratic questions give each
+    /// voter `creditBudget` credits.
+    ///
+    /// Voters stay anonymous unless `voterVisibility` says otherwise.
+    ///
+    /// Questions take up to 5 `tags`, created as needed, an optional markdown
+    /// `description` and a `sourceUrl`. Linked pages are previewed.
+    ///
+    /// Up to 4 images uploaded with `POST /uploads` can be attached, in
+    /// order, by their `imageIds`.
+    ///
+    /// A question close to an existing one is turned down with a
+    /// `POSSIBLE_DUPLICATE` error listing the similar questions, unless
+    /// `force` is set.
+    #[allow(clippy::too_many_arguments)]
+    fn create(
+        ctx: &Context,
+        text: String,
+        opens_at: Option<NaiveDateTime>,
+        closes_at: Option<NaiveDateTime>,
+        results_visibility: Option<ResultsVisibility>,
+        question_type: Option<QuestionType>,
+        scale: Option<ScaleInput>,
+        options: Option<Vec<String>>,
+        credit_budget: Option<i32>,
+        voter_visibility: Option<VoterVisibility>,
+        tags: Option<Vec<String>>,
+        description: Option<String>,
+        source_url: Option<String>,
+        image_ids: Option<Vec<String>>,
+        force: Option<bool>,
+    ) -> QuestionResponse {
         let mut conn = ctx
             .pool
             .get()
             .expect("Failed to get connection to database.");
-        let text = trim_whitespace(&text);
+        let text = validation::normalize(&text);
 
         let user_id = ctx
             .session
@@ -110,35 +522,117 @@ impl QuestionMutation {
                 );
             }
 
-            if text.len() < 4 {
-                return QuestionResponse::from_error(
-                    "question".to_owned(),
-                    "Question must be at least 4 characters long.".to_owned(),
-                );
+            if let Err(e) = validate_text(&mut conn, &text, None) {
+                return QuestionResponse::from_errors(vec![e]);
             }
 
-            if !text.chars().all(|c| {
-                c.is_alphabetic() || c == ' ' || c.is_ascii_punctuation()
-            }) {
-                return QuestionResponse::from_error(
-                    "question".to_owned(),
-                    "Question contains invalid characters.".to_owned(),
-                );
+            let tags = match validate_tags(&tags.unwrap_or_default()) {
+                Ok(tags) => tags,
+                Err(e) => return QuestionResponse::from_errors(vec![e]),
+            };
+
+            let description =
+                match validate_description(description, source_url) {
+                    Ok(description) => description,
+                    Err(e) => return QuestionResponse::from_errors(vec![e]),
+                };
+
+            let image_ids = match validate_images(
+                &mut conn,
+                user_id,
+                &image_ids.unwrap_or_default(),
+                None,
+            ) {
+                Ok(image_ids) => image_ids,
+                Err(e) => return QuestionResponse::from_errors(vec![e]),
+            };
+
+            if !force.unwrap_or(false) {
+                match get_similar(&mut conn, &text, user_id, None) {
+                    Ok(similar) if similar.is_empty() => (),
+                    Ok(similar) => return possible_duplicate(similar),
+                    Err(e) => {
+                        return QuestionResponse::from_error(
+                            "question".to_owned(),
+                            e.to_string(),
+                        )
+                    }
+                }
             }
 
-            let question = services::question::get_by_text(&mut conn, &text);
+            let now = Utc::now().naive_utc();
 
-            if question.is_ok() {
-                return QuestionResponse::from_error(
-                    "question".to_owned(),
-                    "Question already exists.".to_owned(),
-                );
+            if let Err(e) = validate_window(now, opens_at, closes_at) {
+                return QuestionResponse::from_errors(vec![e]);
             }
 
-            let question = services::question::create(
-                &mut conn,
-                QuestionInput { text, user_id },
-            );
+            let question_type = question_type.unwrap_or(QuestionType::Rating);
+            let options: Vec<String> = options
+                .unwrap_or_default()
+                .iter()
+                .map(|option| validation::normalize(option))
+                .collect();
+
+            let scale = match validate_type(
+                question_type,
+                scale.unwrap_or_default(),
+                &options,
+            ) {
+                Ok(scale) => scale,
+                Err(e) => return QuestionResponse::from_errors(vec![e]),
+            };
+
+            let credit_budget =
+                match validate_budget(question_type, credit_budget) {
+                    Ok(credit_budget) => credit_budget,
+                    Err(e) => return QuestionResponse::from_errors(vec![e]),
+                };
+
+            let status = match opens_at {
+                Some(opens_at) if opens_at > now => QuestionStatus::Draft,
+                _ => QuestionStatus::Open,
+            };
+
+            // The question is not created without its links and images.
+            let question: QueryResult<Question> = conn.transaction(|conn| {
+                let question = services::question::create(
+                    conn,
+                    QuestionInput {
+                        text,
+                        user_id,
+                        status,
+                        opens_at,
+                        closes_at,
+                        results_visibility: results_visibility
+                            .unwrap_or(ResultsVisibility::Always),
+                        question_type,
+                        scale_min: scale.min,
+                        scale_max: scale.max,
+                        scale_step: scale.step,
+                        min_label: scale.min_label,
+                        max_label: scale.max_label,
+                        credit_budget,
+                        voter_visibility: voter_visibility
+                            .unwrap_or(VoterVisibility::Anonymous),
+                        description: description.markdown,
+                        description_html: description.html,
+                        source_url: description.source_url,
+                    },
+                    &options,
+                    &tags,
+                )?;
+                services::link::set_for_question(
+                    conn,
+                    question.id,
+                    &description.links,
+                )?;
+                services::image::set_for_question(
+                    conn,
+                    question.id,
+                    &image_ids,
+                )?;
+                Ok(question)
+            });
 
             match question {
                 Ok(question) => QuestionResponse::from_question(question),
@@ -155,6 +649,487 @@ impl QuestionMutation {
         }
     }
 
+    /// Change a question's text, and its tags, description, source and
+    /// images when given (empty to remove them). Only its author or a
+    /// moderator can.
+    ///
+    /// New text close to another question is turned down as in `create`,
+    /// unless `force` is set.
+    #[allow(clippy::too_many_arguments)]
+    fn update(
+        ctx: &Context,
+        question_id: String,
+        text: String,
+        tags: Option<Vec<String>>,
+        description: Option<String>,
+        source_url: Option<String>,
+        image_ids: Option<Vec<String>>,
+        force: Option<bool>,
+    ) -> QuestionResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let text = validation::normalize(&text);
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id);
+
+        if let Err(e) = question_id {
+            return QuestionResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let question = match get_owned_question(
+            &mut conn,
+            user_id,
+            question_id.unwrap(),
+        ) {
+            Ok(question) => question,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+        let description = if description.is_some() || source_url.is_some() {
+            match validate_description(
+                description.or_else(|| question.description.clone()),
+                source_url.or_else(|| question.source_url.clone()),
+            ) {
+                Ok(description) => Some(description),
+                Err(e) => return QuestionResponse::from_errors(vec![e]),
+            }
+        } else {
+            None
+        };
+
+        // Retagging or changing the source does not change what voters
+        // answered.
+        let changes_meaning = text != question.text
+            || description.as_ref().map_or(false, |description| {
+                description.markdown != question.description
+            });
+
+        if changes_meaning && !config().question.edit_with_votes {
+            match services::vote::count_for_question(&mut conn, question.id) {
+                Ok(0) => (),
+                Ok(_) => {
+                    return QuestionResponse::from_error(
+                        "questionId".to_owned(),
+                        "Questions with votes cannot be edited.".to_owned(),
+                    )
+                }
+                Err(e) => {
+                    return QuestionResponse::from_error(
+                        "question".to_owned(),
+                        e.to_string(),
+                    )
+                }
+            }
+        }
+
+        if let Err(e) = validate_text(&mut conn, &text, Some(question.id)) {
+            return QuestionResponse::from_errors(vec![e]);
+        }
+
+        if text != question.text && !force.unwrap_or(false) {
+            match get_similar(
+                &mut conn,
+                &text,
+                question.user_id,
+                Some(question.id),
+            ) {
+                Ok(similar) if similar.is_empty() => (),
+                Ok(similar) => return possible_duplicate(similar),
+                Err(e) => {
+                    return QuestionResponse::from_error(
+                        "question".to_owned(),
+                        e.to_string(),
+                    )
+                }
+            }
+        }
+
+        let tags = match tags.map(|tags| validate_tags(&tags)).transpose() {
+            Ok(tags) => tags,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+        let image_ids = match image_ids
+            .map(|image_ids| {
+                validate_images(
+                    &mut conn,
+                    question.user_id,
+                    &image_ids,
+                    Some(question.id),
+                )
+            })
+            .transpose()
+        {
+            Ok(image_ids) => image_ids,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+        // Either the whole edit is applied or none of it is.
+        let updated = conn.transaction(|conn| {
+            if let Some(tags) = &tags {
+                services::tag::set_for_question(conn, question.id, tags)?;
+            }
+            if let Some(image_ids) = &image_ids {
+                services::image::set_for_question(
+                    conn,
+                    question.id,
+                    image_ids,
+                )?;
+            }
+            if let Some(description) = &description {
+                services::question::update_description(
+                    conn,
+                    question.id,
+                    description.markdown.as_deref(),
+                    description.html.as_deref(),
+                    description.source_url.as_deref(),
+                )?;
+                services::link::set_for_question(
+                    conn,
+                    question.id,
+                    &description.links,
+                )?;
+            }
+
+            services::question::update_text(conn, question.id, &text)
+        });
+
+        match updated {
+            Ok(question) => QuestionResponse::from_question(question),
+            Err(e) => QuestionResponse::from_error(
+                "question".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Change who can see a question's results. Only its author or a
+    /// moderator can, and once there are votes only to show them to fewer
+    /// people.
+    fn set_results_visibility(
+        ctx: &Context,
+        question_id: String,
+        results_visibility: ResultsVisibility,
+    ) -> QuestionResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id);
+
+        if let Err(e) = question_id {
+            return QuestionResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let question = match get_owned_question(
+            &mut conn,
+            user_id,
+            question_id.unwrap(),
+        ) {
+            Ok(question) => question,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+        // Voters were promised the results would stay hidden as they were
+        // when they voted.
+        if !results_visibility.is_within(question.results_visibility) {
+            match services::vote::count_for_question(&mut conn, question.id) {
+                Ok(0) => (),
+                Ok(_) => {
+                    return QuestionResponse::from_error(
+                        "resultsVisibility".to_owned(),
+                        "Results cannot be shown to more people once there \
+                         are votes."
+                            .to_owned(),
+                    )
+                }
+                Err(e) => {
+                    return QuestionResponse::from_error(
+                        "question".to_owned(),
+                        e.to_string(),
+                    )
+                }
+            }
+        }
+
+        match services::question::update_results_visibility(
+            &mut conn,
+            question.id,
+            results_visibility,
+        ) {
+            Ok(question) => QuestionResponse::from_question(question),
+            Err(e) => QuestionResponse::from_error(
+                "question".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Change who can see who voted what. Only its author or a moderator
+    /// can, and once it has votes, only to keep voters more private.
+    fn set_voter_visibility(
+        ctx: &Context,
+        question_id: String,
+        voter_visibility: VoterVisibility,
+    ) -> QuestionResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id);
+
+        if let Err(e) = question_id {
+            return QuestionResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let question = match get_owned_question(
+            &mut conn,
+            user_id,
+            question_id.unwrap(),
+        ) {
+            Ok(question) => question,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+        // Voters were promised the privacy in place when they voted.
+        if voter_visibility > question.voter_visibility {
+            match services::vote::count_for_question(&mut conn, question.id) {
+                Ok(0) => (),
+                Ok(_) => {
+                    return QuestionResponse::from_error(
+                        "voterVisibility".to_owned(),
+                        "Voters cannot be made more visible once there are \
+                         votes."
+                            .to_owned(),
+                    )
+                }
+                Err(e) => {
+                    return QuestionResponse::from_error(
+                        "question".to_owned(),
+                        e.to_string(),
+                    )
+                }
+            }
+        }
+
+        match services::question::update_voter_visibility(
+            &mut conn,
+            question.id,
+            voter_visibility,
+        ) {
+            Ok(question) => QuestionResponse::from_question(question),
+            Err(e) => QuestionResponse::from_error(
+                "question".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Stop accepting votes. Only its author or a moderator can.
+    fn close(ctx: &Context, question_id: String) -> QuestionResponse {
+        change_status(ctx, question_id, QuestionStatus::Closed, None)
+    }
+
+    /// Accept votes again, until `closesAt` if given. Only its author or a
+    /// moderator can.
+    fn reopen(
+        ctx: &Context,
+        question_id: String,
+        closes_at: Option<NaiveDateTime>,
+    ) -> QuestionResponse {
+        change_status(ctx, question_id, QuestionStatus::Open, closes_at)
+    }
+
+    /// Close a question for good. Only its author or a moderator can.
+    fn archive(ctx: &Context, question_id: String) -> QuestionResponse {
+        change_status(ctx, question_id, QuestionStatus::Archived, None)
+    }
+
+    /// Delete a question. Only its author or a moderator can. It can be
+    /// restored during the grace period, then it is purged with its votes.
+    fn delete(ctx: &Context, question_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id)?;
+
+        let question = get_owned_question(&mut conn, user_id, question_id)
+            .map_err(|e| juniper::FieldError::from(e.message))?;
+
+        services::question::delete(&mut conn, question.id)?;
+
+        Ok(true)
+    }
+
+    /// Restore a deleted question during the grace period. Only its author
+    /// or an admin can, unless a moderator removed it.
+    fn restore(ctx: &Context, question_id: String) -> QuestionResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id);
+
+        if let Err(e) = question_id {
+            return QuestionResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let user = match user_id.and_then(|user_id| {
+            services::user::get_by_id(&mut conn, user_id).ok()
+        }) {
+            Some(user) => user,
+            None => {
+                return QuestionResponse::from_error(
+                    "userId".to_owned(),
+                    "User not logged in.".to_owned(),
+                )
+            }
+        };
+
+        let grace = Duration::days(config().deletion.restore_grace_days);
+        let question = services::question::get_deleted_by_id(
+            &mut conn,
+            question_id.unwrap(),
+            Utc::now().naive_utc() - grace,
+        );
+
+        let question = match question {
+            Ok(question)
+                if question.user_id == user.id || user.can_administer() =>
+            {
+                question
+            }
+            _ => {
+                return QuestionResponse::from_error(
+                    "questionId".to_owned(),
+                    "No restorable question found with corresponding Id."
+                        .to_owned(),
+                )
+            }
+        };
+
+        match services::report::is_removed(
+            &mut conn,
+            ReportTargetType::Question,
+            question.id,
+        ) {
+            Ok(false) => (),
+            Ok(true) => {
+                return QuestionResponse::from_error(
+                    "questionId".to_owned(),
+                    "A moderator removed this question, it cannot be \
+                     restored."
+                        .to_owned(),
+                )
+            }
+            Err(e) => {
+                return QuestionResponse::from_error(
+                    "questionId".to_owned(),
+                    e.to_string(),
+                )
+            }
+        }
+
+        if services::question::get_by_text(&mut conn, &question.text).is_ok() {
+            return QuestionResponse::from_error(
+                "question".to_owned(),
+                "Question already exists.".to_owned(),
+            );
+        }
+
+        match services::question::restore(&mut conn, question.id) {
+            Ok(question) => QuestionResponse::from_question(question),
+            Err(e) => QuestionResponse::from_error(
+                "questionId".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Save a question to come back to later. With `follow`, the logged in
+    /// user is also notified when its votes reach a threshold and when it
+    /// closes. Bookmarking again changes whether they follow it.
+    fn bookmark(
+        ctx: &Context,
+        question_id: String,
+        follow: Option<bool>,
+    ) -> FieldResult<BookmarkedQuestion> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let user = user_id
+            .and_then(|user_id| {
+                services::user::get_by_id(&mut conn, user_id).ok()
+            })
+            .ok_or_else(|| juniper::FieldError::from("User not logged in."))?;
+        let question = get_by_id(&mut conn, Uuid::parse_str(&question_id)?)
+            .ok()
+            .filter(|question| {
+                question.status != QuestionStatus::Draft
+                    || question.user_id == user.id
+                    || user.can_moderate()
+            })
+            .ok_or_else(|| {
+                juniper::FieldError::from(
+                    "No question found with corresponding Id.",
+                )
+            })?;
+
+        let bookmark = services::bookmark::upsert(
+            &mut conn,
+            user.id,
+            question.id,
+            follow.unwrap_or(false),
+        )?;
+
+        Ok(BookmarkedQuestion {
+            question,
+            follow: bookmark.follow,
+            bookmarked_at: bookmark.created_at,
+        })
+    }
+
+    /// Remove a question from the logged in user's bookmarks, which also
+    /// stops following it.
+    fn unbookmark(ctx: &Context, question_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+        let question_id = Uuid::parse_str(&question_id)?;
+
+        if !services::bookmark::delete(&mut conn, user_id, question_id)? {
+            return Err(juniper::FieldError::from("No bookmark to remove."));
+        }
+
+        Ok(true)
+    }
+
     fn delete_all_by_user(ctx: &Context) -> FieldResult<bool> {
         let mut conn = ctx
             .pool
@@ -175,8 +1150,11 @@ impl QuestionMutation {
                 ));
             }
 
-            let result =
-                services::question::delete_all_by_user_id(&mut conn, user_id);
+            let result = services::question::delete_all_by_user_id(
+                &mut conn,
+                user_id,
+                Utc::now().naive_utc(),
+            );
 
             if let Err(e) = result {
                 return Err(juniper::FieldError::from(e.to_string()));
@@ -188,13 +1166,557 @@ impl QuestionMutation {
     }
 }
 
-fn trim_whitespace(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    s.split_whitespace().for_each(|w| {
-        if !result.is_empty() {
-            result.push(' ');
+/// Checks a normalized question's text, for both new and edited questions.
+fn validate_text(
+    conn: &mut PgConnection,
+    text: &str,
+    question_id: Option<Uuid>,
+) -> Result<(), FieldError> {
+    validation::check_question(text)
+        .map_err(|e| FieldError::new("question".to_owned(), e))?;
+
+    let question = services::question::get_by_text(conn, text);
+
+    if question.map_or(false, |q| Some(q.id) != question_id) {
+        return Err(FieldError::new(
+            "question".to_owned(),
+            "Question already exists.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The questions other than `question_id` that `text` is close to.
+fn get_similar(
+    conn: &mut PgConnection,
+    text: &str,
+    user_id: Uuid,
+    question_id: Option<Uuid>,
+) -> QueryResult<Vec<Question>> {
+    let similar = services::question::get_similar(
+        conn,
+        text,
+        user_id,
+        config().question.duplicate_similarity,
+        MAX_SIMILAR_QUESTIONS + 1,
+    )?;
+
+    Ok(similar
+        .into_iter()
+        .filter(|similar| Some(similar.id) != question_id)
+        .take(MAX_SIMILAR_QUESTIONS as usize)
+        .collect())
+}
+
+fn possible_duplicate(similar: Vec<Question>) -> QuestionResponse {
+    QuestionResponse {
+        question: None,
+        errors: Some(vec![FieldError::with_code(
+            "question".to_owned(),
+            "POSSIBLE_DUPLICATE",
+            "Similar questions already exist.".to_owned(),
+        )]),
+        similar_questions: Some(similar),
+    }
+}
+
+/// A validated description and source, with what is derived from them.
+struct Description {
+    markdown: Option<String>,
+    html: Option<String>,
+    source_url: Option<String>,
+    /// The pages to preview: the source, then the description's links
+    links: Vec<String>,
+}
+
+fn validate_description(
+    description: Option<String>,
+    source_url: Option<String>,
+) -> Result<Description, FieldError> {
+    let markdown = description
+        .map(|description| validation::normalize_multiline(&description))
+        .filter(|description| !description.is_empty());
+    let source_url = source_url
+        .map(|source_url| source_url.trim().to_owned())
+        .filter(|source_url| !source_url.is_empty());
+
+    if let Some(markdown) = &markdown {
+        validation::check_description(markdown)
+            .map_err(|e| FieldError::new("description".to_owned(), e))?;
+    }
+
+    if let Some(source_url) = &source_url {
+        if source_url.len() > MAX_URL_LENGTH
+            || !services::markdown::is_web_url(source_url)
+        {
+            return Err(FieldError::new(
+                "sourceUrl".to_owned(),
+                format!(
+                    "Source must be an http or https URL of at most {} \
+                     characters.",
+                    MAX_URL_LENGTH
+                ),
+            ));
+        }
+    }
+
+    let mut links: Vec<String> = source_url.iter().cloned().collect();
+
+    for link in markdown
+        .iter()
+        .flat_map(|markdown| services::markdown::links(markdown))
+    {
+        if link.len() <= MAX_URL_LENGTH && !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    links.truncate(MAX_LINK_PREVIEWS);
+
+    Ok(Description {
+        html: markdown.as_deref().map(services::markdown::render),
+        markdown,
+        source_url,
+        links,
+    })
+}
+
+/// Checks the images to attach: each must have been uploaded by the
+/// question's author and not be attached elsewhere, or already be the
+/// question's.
+fn validate_images(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    image_ids: &[String],
+    question_id: Option<Uuid>,
+) -> Result<Vec<Uuid>, FieldError> {
+    if image_ids.len() > MAX_IMAGES {
+        return Err(FieldError::new(
+            "imageIds".to_owned(),
+            format!("Questions cannot have more than {MAX_IMAGES} images."),
+        ));
+    }
+
+    let mut ids = vec![];
+
+    for image_id in image_ids {
+        let image_id = Uuid::parse_str(image_id).map_err(|e| {
+            FieldError::new("imageIds".to_owned(), e.to_string())
+        })?;
+
+        if !ids.contains(&image_id) {
+            ids.push(image_id);
+        }
+    }
+
+    let images = services::image::get_by_ids(conn, &ids)
+        .map_err(|e| FieldError::new("imageIds".to_owned(), e.to_string()))?;
+
+    let usable = |image: &Image| match image.question_id {
+        Some(attached_to) => Some(attached_to) == question_id,
+        None => image.user_id == user_id,
+    };
+
+    if images.len() != ids.len() || !images.iter().all(usable) {
+        return Err(FieldError::new(
+            "imageIds".to_owned(),
+            "No uploaded image found with corresponding Id.".to_owned(),
+        ));
+    }
+
+    Ok(ids)
+}
+
+fn validate_window(
+    now: NaiveDateTime,
+    opens_at: Option<NaiveDateTime>,
+    closes_at: Option<NaiveDateTime>,
+) -> Result<(), FieldError> {
+    match (opens_at, closes_at) {
+        (_, Some(closes_at)) if closes_at <= now => Err(FieldError::new(
+            "closesAt".to_owned(),
+            "Closing time must be in the future.".to_owned(),
+        )),
+        (Some(opens_at), Some(closes_at)) if closes_at <= opens_at => {
+            Err(FieldError::new(
+                "closesAt".to_owned(),
+                "Closing time must be after opening time.".to_owned(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+const MAX_OPTIONS: usize = 20;
+const DEFAULT_CREDIT_BUDGET: i32 = 100;
+const MAX_CREDIT_BUDGET: i32 = 10000;
+const MAX_SCALE_STEPS: i32 = 100;
+const MAX_SCALE_LABEL_LENGTH: usize = 64;
+const MAX_SEARCH_LENGTH: usize = 256;
+const MAX_SIMILAR_QUESTIONS: i64 = 5;
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_LINK_PREVIEWS: usize = 10;
+const MAX_IMAGES: usize = 4;
+
+/// Checks the credit budget, which only quadratic questions have.
+fn validate_budget(
+    question_type: QuestionType,
+    credit_budget: Option<i32>,
+) -> Result<Option<i32>, FieldError> {
+    match (question_type, credit_budget) {
+        (QuestionType::Quadratic, credit_budget) => {
+            let credit_budget = credit_budget.unwrap_or(DEFAULT_CREDIT_BUDGET);
+
+            if !(1..=MAX_CREDIT_BUDGET).contains(&credit_budget) {
+                return Err(FieldError::new(
+                    "creditBudget".to_owned(),
+                    format!(
+                        "The credit budget must be from 1 to {}.",
+                        MAX_CREDIT_BUDGET
+                    ),
+                ));
+            }
+
+            Ok(Some(credit_budget))
+        }
+        (_, Some(_)) => Err(FieldError::new(
+            "creditBudget".to_owned(),
+            "Only quadratic questions have a credit budget.".to_owned(),
+        )),
+        (_, None) => Ok(None),
+    }
+}
+
+/// Checks that the scale and options suit the question type, returning the
+/// scale to store.
+fn validate_type(
+    question_type: QuestionType,
+    scale: ScaleInput,
+    options: &[String],
+) -> Result<ScaleInput, FieldError> {
+    if !question_type.has_options() && !options.is_empty() {
+        return Err(FieldError::new(
+            "options".to_owned(),
+            "Only choice questions have options.".to_owned(),
+        ));
+    }
+
+    match question_type {
+        QuestionType::YesNo => Ok(ScaleInput {
+            min: Some(0),
+            max: Some(1),
+            ..Default::default()
+        }),
+        QuestionType::Rating => {
+            let min = scale.min.unwrap_or(0);
+            let max = scale.max.unwrap_or(5);
+            let step = scale.step.unwrap_or(1);
+
+            if min >= max {
+                return Err(FieldError::new(
+                    "scale".to_owned(),
+                    "The scale's minimum must be below its maximum.".to_owned(),
+                ));
+            }
+            if step <= 0 || (max - min) % step != 0 {
+                return Err(FieldError::new(
+                    "scale".to_owned(),
+                    "The scale's step must evenly divide its range.".to_owned(),
+                ));
+            }
+            if (max - min) / step > MAX_SCALE_STEPS {
+                return Err(FieldError::new(
+                    "scale".to_owned(),
+                    format!(
+                        "The scale cannot have more than {} steps.",
+                        MAX_SCALE_STEPS
+                    ),
+                ));
+            }
+
+            let label = |label: Option<String>| {
+                label
+                    .map(|label| validation::normalize(&label))
+                    .filter(|label| !label.is_empty())
+            };
+            let min_label = label(scale.min_label);
+            let max_label = label(scale.max_label);
+
+            for label in min_label.iter().chain(&max_label) {
+                validation::check(
+                    label,
+                    "Scale labels",
+                    1,
+                    MAX_SCALE_LABEL_LENGTH,
+                    MAX_SCALE_LABEL_LENGTH,
+                )
+                .map_err(|e| FieldError::new("scale".to_owned(), e))?;
+            }
+
+            Ok(ScaleInput {
+                min: Some(min),
+                max: Some(max),
+                step: Some(step),
+                min_label,
+                max_label,
+            })
+        }
+        QuestionType::Numeric => {
+            if let (Some(min), Some(max)) = (scale.min, scale.max) {
+                if min > max {
+                    return Err(FieldError::new(
+                        "scale".to_owned(),
+                        "The minimum cannot be above the maximum.".to_owned(),
+                    ));
+                }
+            }
+
+            Ok(ScaleInput {
+                min: scale.min,
+                max: scale.max,
+                ..Default::default()
+            })
+        }
+        QuestionType::SingleChoice
+        | QuestionType::MultipleChoice
+        | QuestionType::Ranked
+        | QuestionType::Approval
+        | QuestionType::Quadratic => {
+            if options.len() < 2 || options.len() > MAX_OPTIONS {
+                return Err(FieldError::new(
+                    "options".to_owned(),
+                    format!(
+                        "Choice questions need from 2 to {} options.",
+                        MAX_OPTIONS
+                    ),
+                ));
+            }
+            for option in options {
+                validation::check(
+                    option,
+                    "Options",
+                    1,
+                    validation::TEXT_COLUMN_LENGTH,
+                    validation::TEXT_COLUMN_LENGTH,
+                )
+                .map_err(|e| FieldError::new("options".to_owned(), e))?;
+            }
+            if options.iter().enumerate().any(|(i, option)| {
+                options[..i]
+                    .iter()
+                    .any(|other| other.to_lowercase() == option.to_lowercase())
+            }) {
+                return Err(FieldError::new(
+                    "options".to_owned(),
+                    "Options must be different from each other.".to_owned(),
+                ));
+            }
+
+            Ok(ScaleInput::default())
+        }
+    }
+}
+
+/// Gets a page of questions, with all of `tags` if any.
+fn paginate(
+    ctx: &Context,
+    limit: Option<i32>,
+    cursor: Option<String>,
+    tags: Vec<String>,
+) -> QuestionsResponse {
+    let mut conn = ctx
+        .pool
+        .get()
+        .expect("Failed to get connection to database.");
+    let limit = limit.unwrap_or(20);
+
+    let cursor = match cursor {
+        Some(cursor) => {
+            let cursor = Uuid::parse_str(&cursor);
+
+            if let Err(e) = cursor {
+                return QuestionsResponse::from_error(
+                    "cursor".to_owned(),
+                    e.to_string(),
+                );
+            }
+
+            Some(cursor.unwrap())
+        }
+        None => None,
+    };
+
+    if let Some(cursor) = cursor {
+        let question = get_by_id(&mut conn, cursor);
+
+        if question.is_err() {
+            return QuestionsResponse::from_error(
+                "cursor".to_owned(),
+                "No question found with corresponding Id.".to_owned(),
+            );
+        }
+    }
+
+    let tags = match validate_tags(&tags) {
+        Ok(tags) => tags,
+        Err(e) => return QuestionsResponse::from_errors(vec![e]),
+    };
+    let mut tag_ids = Vec::with_capacity(tags.len());
+
+    for tag in &tags {
+        match services::tag::get_by_name(&mut conn, tag) {
+            Ok(tag) => tag_ids.push(tag.id),
+            // No question can have a tag that does not exist.
+            Err(_) => return QuestionsResponse::from_questions(vec![]),
+        }
+    }
+
+    let questions =
+        services::question::get_paginated(&mut conn, limit, cursor, &tag_ids);
+
+    QuestionsResponse::from_questions(
+        questions.expect("Failed to get questions."),
+    )
+}
+
+/// Moves a question the logged in user may change to `status`, emitting the
+/// matching event.
+fn change_status(
+    ctx: &Context,
+    question_id: String,
+    status: QuestionStatus,
+    closes_at: Option<NaiveDateTime>,
+) -> QuestionResponse {
+    let mut conn = ctx
+        .pool
+        .get()
+        .expect("Failed to get connection to database.");
+    let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+    let question_id = Uuid::parse_str(&question_id);
+
+    if let Err(e) = question_id {
+        return QuestionResponse::from_error(
+            "questionId".to_owned(),
+            e.to_string(),
+        );
+    }
+
+    let question =
+        match get_owned_question(&mut conn, user_id, question_id.unwrap()) {
+            Ok(question) => question,
+            Err(e) => return QuestionResponse::from_errors(vec![e]),
+        };
+
+    if question.status == QuestionStatus::Archived {
+        return QuestionResponse::from_error(
+            "questionId".to_owned(),
+            "Archived questions cannot be changed.".to_owned(),
+        );
+    }
+
+    let now = Utc::now().naive_utc();
+
+    if let Err(e) = validate_window(now, None, closes_at) {
+        return QuestionResponse::from_errors(vec![e]);
+    }
+
+    let previous = question.status;
+
+    // Opening now overrides a scheduled opening time and any old deadline.
+    let (opens_at, closes_at) = match status {
+        QuestionStatus::Open => (
+            question.opens_at.filter(|opens_at| *opens_at <= now),
+            closes_at,
+        ),
+        _ => (question.opens_at, question.closes_at),
+    };
+
+    let question = services::question::update_status(
+        &mut conn,
+        question.id,
+        status,
+        opens_at,
+        closes_at,
+    );
+
+    match question {
+        Ok(question) => {
+            if status == QuestionStatus::Open
+                && previous != QuestionStatus::Open
+            {
+                events::emit(
+                    &mut conn,
+                    Event::QuestionOpened(question.clone()),
+                );
+            } else if status != QuestionStatus::Open
+                && previous == QuestionStatus::Open
+            {
+                events::emit(
+                    &mut conn,
+                    Event::QuestionClosed(question.clone()),
+                );
+            }
+            QuestionResponse::from_question(question)
+        }
+        Err(e) => {
+            QuestionResponse::from_error("questionId".to_owned(), e.to_string())
         }
-        result.push_str(w);
-    });
-    result
+    }
+}
+
+/// Whether the logged in user is the question's author or a moderator.
+pub(super) fn can_see_hidden(
+    conn: &mut PgConnection,
+    ctx: &Context,
+    question: &Question,
+) -> bool {
+    ctx.session
+        .get::<Uuid>("userId")
+        .unwrap()
+        .and_then(|user_id| services::user::get_by_id(conn, user_id).ok())
+        .map_or(false, |user| services::question::can_edit(question, &user))
+}
+
+/// The logged in user's bookmark of the question, if any.
+fn get_bookmark(ctx: &Context, question_id: Uuid) -> Option<Bookmark> {
+    let user_id = ctx.session.get::<Uuid>("userId").unwrap()?;
+    let mut conn = ctx
+        .pool
+        .get()
+        .expect("Failed to get connection to database.");
+
+    services::bookmark::get(&mut conn, user_id, question_id).ok()
+}
+
+/// Gets a question the logged in user may change: their own, or any if they
+/// are a moderator.
+fn get_owned_question(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    question_id: Uuid,
+) -> Result<Question, FieldError> {
+    let user = user_id
+        .and_then(|user_id| services::user::get_by_id(conn, user_id).ok())
+        .ok_or_else(|| {
+            FieldError::new(
+                "userId".to_owned(),
+                "User not logged in.".to_owned(),
+            )
+        })?;
+
+    let question = get_by_id(conn, question_id).map_err(|_| {
+        FieldError::new(
+            "questionId".to_owned(),
+            "No question found with corresponding Id.".to_owned(),
+        )
+    })?;
+
+    if !services::question::can_edit(&question, &user) {
+        return Err(FieldError::new(
+            "questionId".to_owned(),
+            "Only the question's author or a moderator can do this.".to_owned(),
+        ));
+    }
+
+    Ok(question)
 }
@@ -0,0 +1,241 @@
+use diesel::PgConnection;
+use juniper::FieldResult;
+use uuid::Uuid;
+
+use crate::{
+    context::Context,
+    models::{
+        tag::{Tag, TagResponse, TagUsage},
+        types::FieldError,
+    },
+    services, validation,
+};
+
+pub struct TagQuery;
+
+#[juniper::graphql_object(Context = Context)]
+impl TagQuery {
+    /// Tags starting with `prefix`, most used first.
+    fn autocomplete(
+        ctx: &Context,
+        prefix: String,
+        limit: Option<i32>,
+    ) -> FieldResult<Vec<Tag>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let prefix = normalize_tag(&prefix);
+
+        if prefix.is_empty() {
+            return Ok(vec![]);
+        }
+        if !is_valid_tag(&prefix) {
+            return Err(juniper::FieldError::from(
+                "Tags only contain letters, digits and dashes.",
+            ));
+        }
+
+        Ok(services::tag::autocomplete(
+            &mut conn,
+            &prefix,
+            limit.unwrap_or(10).clamp(1, 50) as i64,
+        )?)
+    }
+
+    /// The tags used by the most questions open to voters.
+    fn popular(
+        ctx: &Context,
+        limit: Option<i32>,
+    ) -> FieldResult<Vec<TagUsage>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::tag::get_popular(
+            &mut conn,
+            limit.unwrap_or(20).clamp(1, 100) as i64,
+        )?
+        .into_iter()
+        .map(|(tag, questions)| TagUsage {
+            tag,
+            questions: questions as i32,
+        })
+        .collect())
+    }
+}
+
+pub struct TagMutation;
+
+#[juniper::graphql_object(Context = Context)]
+impl TagMutation {
+    /// Rename a tag on every question using it. Only moderators can.
+    fn rename(ctx: &Context, tag_id: String, name: String) -> TagResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        if let Err(e) = check_moderator(&mut conn, user_id) {
+            return TagResponse::from_errors(vec![e]);
+        }
+
+        let tag = match get_tag(&mut conn, "tagId", &tag_id) {
+            Ok(tag) => tag,
+            Err(e) => return TagResponse::from_errors(vec![e]),
+        };
+
+        let name = match validate_tag(&name) {
+            Ok(name) => name,
+            Err(e) => return TagResponse::from_errors(vec![e]),
+        };
+
+        if services::tag::get_by_name(&mut conn, &name)
+            .map_or(false, |other| other.id != tag.id)
+        {
+            return TagResponse::from_error(
+                "name".to_owned(),
+                "Tag already exists. Merge the tags instead.".to_owned(),
+            );
+        }
+
+        match services::tag::rename(&mut conn, tag.id, &name) {
+            Ok(tag) => TagResponse::from_tag(tag),
+            Err(e) => {
+                TagResponse::from_error("tagId".to_owned(), e.to_string())
+            }
+        }
+    }
+
+    /// Move every question tagged `sourceId` over to `targetId`, and delete
+    /// the source tag. Only moderators can.
+    fn merge(
+        ctx: &Context,
+        source_id: String,
+        target_id: String,
+    ) -> TagResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        if let Err(e) = check_moderator(&mut conn, user_id) {
+            return TagResponse::from_errors(vec![e]);
+        }
+
+        let source = match get_tag(&mut conn, "sourceId", &source_id) {
+            Ok(tag) => tag,
+            Err(e) => return TagResponse::from_errors(vec![e]),
+        };
+        let target = match get_tag(&mut conn, "targetId", &target_id) {
+            Ok(tag) => tag,
+            Err(e) => return TagResponse::from_errors(vec![e]),
+        };
+
+        if source.id == target.id {
+            return TagResponse::from_error(
+                "targetId".to_owned(),
+                "A tag cannot be merged into itself.".to_owned(),
+            );
+        }
+
+        match services::tag::merge(&mut conn, source.id, target.id) {
+            Ok(tag) => TagResponse::from_tag(tag),
+            Err(e) => {
+                TagResponse::from_error("targetId".to_owned(), e.to_string())
+            }
+        }
+    }
+}
+
+/// Normalizes tag names, dropping repeated ones.
+pub(super) fn validate_tags(
+    names: &[String],
+) -> Result<Vec<String>, FieldError> {
+    let mut tags: Vec<String> = Vec::with_capacity(names.len());
+
+    for name in names {
+        let name = validate_tag(name)?;
+
+        if !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+
+    if tags.len() > MAX_TAGS {
+        return Err(FieldError::new(
+            "tags".to_owned(),
+            format!("Questions can have at most {} tags.", MAX_TAGS),
+        ));
+    }
+
+    Ok(tags)
+}
+
+fn validate_tag(name: &str) -> Result<String, FieldError> {
+    let name = normalize_tag(name);
+
+    if name.is_empty() || name.chars().count() > MAX_TAG_LENGTH {
+        return Err(FieldError::new(
+            "tags".to_owned(),
+            format!("Tags must be 1 to {} characters long.", MAX_TAG_LENGTH),
+        ));
+    }
+    if !is_valid_tag(&name) {
+        return Err(FieldError::new(
+            "tags".to_owned(),
+            "Tags only contain letters, digits and dashes.".to_owned(),
+        ));
+    }
+
+    Ok(name)
+}
+
+/// Lower cases the name and joins its words with dashes.
+fn normalize_tag(name: &str) -> String {
+    validation::normalize(name).replace(' ', "-").to_lowercase()
+}
+
+fn is_valid_tag(name: &str) -> bool {
+    name.chars().all(|c| c.is_alphanumeric() || c == '-')
+}
+
+fn get_tag(
+    conn: &mut PgConnection,
+    field: &str,
+    tag_id: &str,
+) -> Result<Tag, FieldError> {
+    Uuid::parse_str(tag_id)
+        .ok()
+        .and_then(|tag_id| services::tag::get_by_id(conn, tag_id).ok())
+        .ok_or_else(|| {
+            FieldError::new(
+                field.to_owned(),
+                "No tag found with corresponding Id.".to_owned(),
+            )
+        })
+}
+
+fn check_moderator(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+) -> Result<(), FieldError> {
+    let is_moderator = user_id
+        .and_then(|user_id| services::user::get_by_id(conn, user_id).ok())
+        .map_or(false, |user| user.can_moderate());
+
+    if !is_moderator {
+        return Err(FieldError::new(
+            "userId".to_owned(),
+            "Only moderators can change tags.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+const MAX_TAGS: usize = 5;
+const MAX_TAG_LENGTH: usize = 32;
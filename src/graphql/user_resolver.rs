@@ -1,15 +1,269 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::PgConnection;
+use juniper::FieldResult;
 use regex::Regex;
 use uuid::Uuid;
 
 use crate::{
+    config::config,
     context::Context,
     models::{
+        bookmark::BookmarkedQuestion,
+        follow::Follow,
+        identity::Identity,
+        notification::Notification,
+        totp::{
+            LoginChallenge, RecoveryCodesResponse, TotpSetup, TotpSetupResponse,
+        },
         types::FieldError,
-        user::{RegisterUserInput, UserResponse},
+        user::{LoginResponse, RegisterUserInput, User, UserResponse},
+        vote::VoteEvent,
     },
     services::{self, user::get_by_id},
 };
 
+///A user
+#[juniper::graphql_object(Context = Context)]
+impl User {
+    /// The user's id (UUID)
+    fn id(&self) -> Uuid {
+        self.id
+    }
+    /// The user's username
+    fn username(&self) -> &str {
+        &self.username
+    }
+    /// The user's email. Only the user can see it.
+    fn email(&self, ctx: &Context) -> FieldResult<&str> {
+        check_self(ctx, self)?;
+
+        Ok(&self.email)
+    }
+    /// The date and time the user was created
+    fn created_at(&self) -> NaiveDateTime {
+        self.created_at
+    }
+    /// The date and time the user was last updated
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+    /// The date and time the user last logged in. Only the user can see
+    /// it.
+    fn last_login(&self, ctx: &Context) -> FieldResult<Option<NaiveDateTime>> {
+        check_self(ctx, self)?;
+
+        Ok(self.last_login)
+    }
+    /// The user's role (user, moderator or admin)
+    fn role(&self) -> &str {
+        &self.role
+    }
+    /// Whether the user has two-factor authentication enabled. Only the
+    /// user can see it.
+    fn totp_enabled(&self, ctx: &Context) -> FieldResult<bool> {
+        check_self(ctx, self)?;
+
+        Ok(self.totp_enabled)
+    }
+    /// The date and time the user deleted their account, if they did. Only
+    /// the user can see it.
+    fn deleted_at(&self, ctx: &Context) -> FieldResult<Option<NaiveDateTime>> {
+        check_self(ctx, self)?;
+
+        Ok(self.deleted_at)
+    }
+    /// The users this user follows, most recently followed first. Pass the
+    /// last user's id as `after` for the next page. Only the user can see
+    /// them.
+    fn following(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<Vec<User>> {
+        check_self(ctx, self)?;
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let first = first.unwrap_or(20).clamp(1, 100);
+        let after = match after {
+            Some(after) => {
+                Some(get_follow(&mut conn, self.id, Uuid::parse_str(&after)?)?)
+            }
+            None => None,
+        };
+
+        Ok(services::follow::get_following(
+            &mut conn,
+            self.id,
+            first,
+            after.map(|follow| (follow.created_at, follow.followee_id)),
+        )?)
+    }
+    /// The users following this user, most recent first. Pass the last
+    /// user's id as `after` for the next page. Only the user can see them.
+    fn followers(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<Vec<User>> {
+        check_self(ctx, self)?;
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let first = first.unwrap_or(20).clamp(1, 100);
+        let after = match after {
+            Some(after) => {
+                Some(get_follow(&mut conn, Uuid::parse_str(&after)?, self.id)?)
+            }
+            None => None,
+        };
+
+        Ok(services::follow::get_followers(
+            &mut conn,
+            self.id,
+            first,
+            after.map(|follow| (follow.created_at, follow.follower_id)),
+        )?)
+    }
+    /// The number of users this user follows
+    fn following_count(&self, ctx: &Context) -> FieldResult<i32> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::follow::count_following(&mut conn, self.id)? as i32)
+    }
+    /// The number of users following this user
+    fn followers_count(&self, ctx: &Context) -> FieldResult<i32> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::follow::count_followers(&mut conn, self.id)? as i32)
+    }
+    /// Whether the logged in user follows this user
+    fn followed(&self, ctx: &Context) -> bool {
+        let user_id = match ctx.session.get::<Uuid>("userId").unwrap() {
+            Some(user_id) => user_id,
+            None => return false,
+        };
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        services::follow::get(&mut conn, user_id, self.id).is_ok()
+    }
+    /// The questions the user bookmarked, most recent first. Pass the last
+    /// question's id as `after` for the next page. Only the user can see
+    /// them.
+    fn bookmarks(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<Vec<BookmarkedQuestion>> {
+        check_self(ctx, self)?;
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let first = first.unwrap_or(20).clamp(1, 100);
+
+        let after = match after {
+            Some(after) => {
+                let bookmark = services::bookmark::get(
+                    &mut conn,
+                    self.id,
+                    Uuid::parse_str(&after)?,
+                )
+                .map_err(|_| {
+                    juniper::FieldError::from(
+                        "No bookmark found with corresponding Id.",
+                    )
+                })?;
+                Some((bookmark.created_at, bookmark.question_id))
+            }
+            None => None,
+        };
+
+        Ok(services::bookmark::get_by_user_id(
+            &mut conn, self.id, first, after,
+        )?
+        .into_iter()
+        .map(|(bookmark, question)| BookmarkedQuestion {
+            question,
+            follow: bookmark.follow,
+            bookmarked_at: bookmark.created_at,
+        })
+        .collect())
+    }
+    /// The user's notifications, newest first. Pass the last one's id as
+    /// `after` for the next page. Only the user can see them.
+    fn notifications(
+        &self,
+        ctx: &Context,
+        first: Option<i32>,
+        after: Option<String>,
+        unread_only: Option<bool>,
+    ) -> FieldResult<Vec<Notification>> {
+        check_self(ctx, self)?;
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let first = first.unwrap_or(20).clamp(1, 100);
+
+        let after = match after {
+            Some(after) => {
+                let notification = services::notification::get_by_id(
+                    &mut conn,
+                    Uuid::parse_str(&after)?,
+                )
+                .ok()
+                .filter(|notification| notification.user_id == self.id)
+                .ok_or_else(|| {
+                    juniper::FieldError::from(
+                        "No notification found with corresponding Id.",
+                    )
+                })?;
+                Some((notification.created_at, notification.id))
+            }
+            None => None,
+        };
+
+        Ok(services::notification::get_by_user_id(
+            &mut conn,
+            self.id,
+            unread_only.unwrap_or(false),
+            first,
+            after,
+        )?)
+    }
+    /// The number of notifications the user did not read. Only the user can
+    /// see it.
+    fn unread_notifications(&self, ctx: &Context) -> FieldResult<i32> {
+        check_self(ctx, self)?;
+
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+
+        Ok(services::notification::count_unread(&mut conn, self.id)? as i32)
+    }
+}
+
 pub struct UserQuery;
 
 #[juniper::graphql_object(Context = Context)]
@@ -37,6 +291,63 @@ impl UserQuery {
             )
         }
     }
+
+    /// External accounts linked to the logged in user
+    pub fn identities(ctx: &Context) -> Vec<Identity> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        match user_id {
+            Some(user_id) => {
+                services::identity::get_all_by_user_id(&mut conn, user_id)
+                    .unwrap_or_default()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The logged in user's votes being cast, changed and retracted, newest
+    /// first. Pass the last event's id as `cursor` for the next page.
+    pub fn vote_history(
+        ctx: &Context,
+        limit: Option<i32>,
+        cursor: Option<String>,
+    ) -> FieldResult<Vec<VoteEvent>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+        let limit = limit.unwrap_or(20).clamp(1, 100);
+
+        let cursor = match cursor {
+            Some(cursor) => {
+                let event = services::vote::get_event_by_id(
+                    &mut conn,
+                    Uuid::parse_str(&cursor)?,
+                )
+                .ok()
+                .filter(|event| event.user_id == user_id)
+                .ok_or_else(|| {
+                    juniper::FieldError::from(
+                        "No vote event found with corresponding Id.",
+                    )
+                })?;
+                Some((event.created_at, event.id))
+            }
+            None => None,
+        };
+
+        Ok(services::vote::get_events_by_user_id(
+            &mut conn, user_id, limit, cursor,
+        )?)
+    }
 }
 
 pub struct UserMutation;
@@ -44,7 +355,7 @@ pub struct UserMutation;
 #[juniper::graphql_object(Context = Context)]
 impl UserMutation {
     /// Register a new user
-    fn register(
+    async fn register(
         ctx: &Context,
         mut new_user: RegisterUserInput,
     ) -> UserResponse {
@@ -86,27 +397,34 @@ impl UserMutation {
                 "Email already exists.".to_owned(),
             ));
         }
-        if new_user.password.len() < 8 {
-            errors.push(FieldError::new(
-                "password".to_owned(),
-                "Password must be at least 8 characters.".to_owned(),
-            ));
+        for problem in services::password::validate_blocking(
+            new_user.password.clone(),
+            vec![new_user.username.clone(), new_user.email.clone()],
+        )
+        .await
+        {
+            errors.push(FieldError::new("password".to_owned(), problem));
         }
 
         if !errors.is_empty() {
             return UserResponse::from_errors(errors);
         }
 
+        new_user.password =
+            services::password::hash_blocking(new_user.password).await;
+
         UserResponse::from_user(
             services::user::create_user(&mut conn, new_user).unwrap(),
         )
     }
 
-    fn login(
+    /// Log in. Users with TOTP enabled get a challenge to complete with
+    /// `verifyTotp` instead of a session.
+    async fn login(
         ctx: &Context,
         username_or_email: String,
         password: String,
-    ) -> UserResponse {
+    ) -> LoginResponse {
         let mut conn = ctx
             .pool
             .get()
@@ -121,32 +439,685 @@ impl UserMutation {
 
         match user {
             Ok(user) => {
-                if !argon2::verify_encoded(&user.password, password.as_bytes())
-                    .unwrap()
+                if !services::password::verify_blocking(
+                    user.password.clone(),
+                    password.clone(),
+                )
+                .await
                 {
                     errors.push(FieldError::new(
                         "password".to_owned(),
                         "Password is incorrect.".to_owned(),
                     ));
-                    return UserResponse::from_errors(errors);
+                    return LoginResponse::from_errors(errors);
+                }
+
+                if services::password::needs_rehash(&user.password) {
+                    let hash =
+                        services::password::hash_blocking(password).await;
+                    services::user::update_password(&mut conn, user.id, &hash)
+                        .unwrap();
+                }
+
+                if user.totp_enabled {
+                    let challenge =
+                        services::totp::create_challenge(&mut conn, user.id)
+                            .unwrap();
+
+                    return LoginResponse {
+                        user: None,
+                        errors: None,
+                        challenge: Some(LoginChallenge::from(challenge)),
+                    };
                 }
 
                 services::user::update_last_login(&mut conn, user.id).unwrap();
                 ctx.session.insert("userId", user.id).unwrap();
-                UserResponse::from_user(user)
+                LoginResponse::from_user(user)
             }
             Err(_) => {
                 errors.push(FieldError::new(
                     "usernameOrEmail".to_owned(),
                     "Username or email does not exist.".to_owned(),
                 ));
-                UserResponse::from_errors(errors)
+                LoginResponse::from_errors(errors)
+            }
+        }
+    }
+
+    /// Change the logged in user's password. Users who have only logged in
+    /// through a provider set their first one without `currentPassword`.
+    async fn change_password(
+        ctx: &Context,
+        current_password: Option<String>,
+        new_password: String,
+    ) -> UserResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        if user_id.is_none() {
+            return UserResponse::from_error(
+                "userId".to_owned(),
+                "User not logged in".to_owned(),
+            );
+        }
+
+        let user = get_by_id(&mut conn, user_id.unwrap());
+
+        if let Err(e) = user {
+            return UserResponse::from_error(
+                "userId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let user = user.unwrap();
+
+        if user.password != services::password::UNUSABLE
+            && !services::password::verify_blocking(
+                user.password.clone(),
+                current_password.unwrap_or_default(),
+            )
+            .await
+        {
+            return UserResponse::from_error(
+                "currentPassword".to_owned(),
+                "Password is incorrect.".to_owned(),
+            );
+        }
+
+        let errors: Vec<FieldError> = services::password::validate_blocking(
+            new_password.clone(),
+            vec![user.username.clone(), user.email.clone()],
+        )
+        .await
+        .into_iter()
+        .map(|problem| FieldError::new("newPassword".to_owned(), problem))
+        .collect();
+
+        if !errors.is_empty() {
+            return UserResponse::from_errors(errors);
+        }
+
+        let hash = services::password::hash_blocking(new_password).await;
+
+        match services::user::update_password(&mut conn, user.id, &hash) {
+            Ok(user) => UserResponse::from_user(user),
+            Err(e) => UserResponse::from_error(
+                "newPassword".to_owned(),
+                e.to_string(),
+            ),
+        }
+    }
+
+    /// Complete a login with a TOTP code or a recovery code
+    async fn verify_totp(
+        ctx: &Context,
+        challenge_id: String,
+        code: String,
+    ) -> UserResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let challenge_id = Uuid::parse_str(&challenge_id);
+
+        if let Err(e) = challenge_id {
+            return UserResponse::from_error(
+                "challengeId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let challenge =
+            services::totp::get_challenge(&mut conn, challenge_id.unwrap());
+
+        let challenge = match challenge {
+            Ok(challenge) => challenge,
+            Err(_) => {
+                return UserResponse::from_error(
+                    "challengeId".to_owned(),
+                    "Challenge is invalid or expired. Please login again."
+                        .to_owned(),
+                )
+            }
+        };
+
+        if services::totp::is_exhausted(&challenge) {
+            services::totp::delete_challenge(&mut conn, challenge.id).unwrap();
+            return UserResponse::from_error(
+                "challengeId".to_owned(),
+                "Too many attempts. Please login again.".to_owned(),
+            );
+        }
+
+        let user = get_by_id(&mut conn, challenge.user_id).unwrap();
+        let step = user.totp_secret.as_deref().and_then(|secret| {
+            services::totp::verify_code(
+                secret,
+                code.trim(),
+                user.totp_last_step,
+            )
+        });
+
+        let verified = match step {
+            Some(step) => {
+                services::user::update_totp_last_step(&mut conn, user.id, step)
+                    .unwrap();
+                true
+            }
+            None => {
+                let code = services::totp::normalize_recovery_code(&code);
+                let unused = services::totp::get_unused_recovery_codes(
+                    &mut conn, user.id,
+                )
+                .unwrap();
+
+                let mut matching = None;
+                for recovery_code in unused {
+                    if services::password::verify_blocking(
+                        recovery_code.code_hash,
+                        code.clone(),
+                    )
+                    .await
+                    {
+                        matching = Some(recovery_code.id);
+                        break;
+                    }
+                }
+
+                match matching {
+                    Some(codeid) => {
+                        services::totp::use_recovery_code(&mut conn, codeid)
+                            .unwrap()
+                    }
+                    None => false,
+                }
+            }
+        };
+
+        if !verified {
+            services::totp::increment_challenge_attempts(
+                &mut conn,
+                challenge.id,
+            )
+            .unwrap();
+            return UserResponse::from_error(
+                "code".to_owned(),
+                "Code is incorrect.".to_owned(),
+            );
+        }
+
+        services::totp::delete_challenge(&mut conn, challenge.id).unwrap();
+        let user =
+            services::user::update_last_login(&mut conn, user.id).unwrap();
+        ctx.session.insert("userId", user.id).unwrap();
+        UserResponse::from_user(user)
+    }
+
+    /// Start enabling TOTP. The returned secret must be confirmed with
+    /// `confirmTotp` before it is required at login.
+    fn enable_totp(ctx: &Context) -> TotpSetupResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        if user_id.is_none() {
+            return TotpSetupResponse::from_error(
+                "userId".to_owned(),
+                "User not logged in".to_owned(),
+            );
+        }
+
+        let user = get_by_id(&mut conn, user_id.unwrap());
+
+        if let Err(e) = user {
+            return TotpSetupResponse::from_error(
+                "userId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let user = user.unwrap();
+
+        if user.totp_enabled {
+            return TotpSetupResponse::from_error(
+                "totp".to_owned(),
+                "TOTP is already enabled.".to_owned(),
+            );
+        }
+
+        let secret = services::totp::generate_secret();
+        services::user::set_totp_secret(&mut conn, user.id, &secret).unwrap();
+
+        TotpSetupResponse::from_setup(TotpSetup {
+            provisioning_uri: services::totp::provisioning_uri(
+                &user.username,
+                &secret,
+            ),
+            secret,
+        })
+    }
+
+    /// Turn TOTP on with a code from the authenticator app. Returns the
+    /// recovery codes, which are only shown once.
+    async fn confirm_totp(
+        ctx: &Context,
+        code: String,
+    ) -> RecoveryCodesResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        if user_id.is_none() {
+            return RecoveryCodesResponse::from_error(
+                "userId".to_owned(),
+                "User not logged in".to_owned(),
+            );
+        }
+
+        let user = get_by_id(&mut conn, user_id.unwrap());
+
+        if let Err(e) = user {
+            return RecoveryCodesResponse::from_error(
+                "userId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let user = user.unwrap();
+
+        if user.totp_enabled {
+            return RecoveryCodesResponse::from_error(
+                "totp".to_owned(),
+                "TOTP is already enabled.".to_owned(),
+            );
+        }
+
+        let step = match &user.totp_secret {
+            Some(secret) => {
+                services::totp::verify_code(secret, code.trim(), None)
+            }
+            None => {
+                return RecoveryCodesResponse::from_error(
+                    "totp".to_owned(),
+                    "Call enableTotp first.".to_owned(),
+                )
+            }
+        };
+
+        let step = match step {
+            Some(step) => step,
+            None => {
+                return RecoveryCodesResponse::from_error(
+                    "code".to_owned(),
+                    "Code is incorrect.".to_owned(),
+                )
+            }
+        };
+
+        let codes = services::totp::generate_recovery_codes();
+        let mut hashes = Vec::with_capacity(codes.len());
+        for code in &codes {
+            hashes.push(services::password::hash_blocking(code.clone()).await);
+        }
+
+        services::user::enable_totp(&mut conn, user.id, step).unwrap();
+        services::totp::replace_recovery_codes(&mut conn, user.id, &hashes)
+            .unwrap();
+        RecoveryCodesResponse::from_recovery_codes(codes)
+    }
+
+    /// Delete the logged in user's account and questions. They can be
+    /// restored during the grace period with `restoreAccount`.
+    ///
+    /// Accounts without a password, created through an external login,
+    /// confirm with a `code` from their authenticator app, or by logging in
+    /// with their provider again shortly before.
+    async fn delete_account(
+        ctx: &Context,
+        password: Option<String>,
+        code: Option<String>,
+    ) -> UserResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+        let user = match user_id.map(|user_id| get_by_id(&mut conn, user_id)) {
+            Some(Ok(user)) => user,
+            _ => {
+                return UserResponse::from_error(
+                    "userId".to_owned(),
+                    "User not logged in".to_owned(),
+                )
+            }
+        };
+
+        if user.password == services::password::UNUSABLE {
+            if !confirm_without_password(&mut conn, ctx, &user, code) {
+                return UserResponse::from_error(
+                    "code".to_owned(),
+                    "Give a code from your authenticator app, or log in \
+                     with your provider again."
+                        .to_owned(),
+                );
+            }
+        } else if !services::password::verify_blocking(
+            user.password.clone(),
+            password.unwrap_or_default(),
+        )
+        .await
+        {
+            return UserResponse::from_error(
+                "password".to_owned(),
+                "Password is incorrect.".to_owned(),
+            );
+        }
+
+        match services::user::delete(&mut conn, user.id) {
+            Ok(user) => {
+                ctx.session.remove("userId");
+                UserResponse::from_user(user)
+            }
+            Err(e) => {
+                UserResponse::from_error("userId".to_owned(), e.to_string())
             }
         }
     }
 
+    /// Restore a deleted account during the grace period. Log in afterwards.
+    async fn restore_account(
+        ctx: &Context,
+        username_or_email: String,
+        password: String,
+    ) -> UserResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let grace = Duration::days(config().deletion.restore_grace_days);
+
+        let user = services::user::get_deleted_by_username_or_email(
+            &mut conn,
+            &username_or_email,
+            Utc::now().naive_utc() - grace,
+        );
+
+        let user = match user {
+            Ok(user) => user,
+            Err(_) => {
+                return UserResponse::from_error(
+                    "usernameOrEmail".to_owned(),
+                    "No restorable account found.".to_owned(),
+                )
+            }
+        };
+
+        if !services::password::verify_blocking(user.password.clone(), password)
+            .await
+        {
+            return UserResponse::from_error(
+                "password".to_owned(),
+                "Password is incorrect.".to_owned(),
+            );
+        }
+
+        restore(&mut conn, &user)
+    }
+
+    /// Restore any deleted account during the grace period. Admins only.
+    fn restore_user(ctx: &Context, user_id: String) -> UserResponse {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let admin_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let is_admin = admin_id
+            .and_then(|admin_id| get_by_id(&mut conn, admin_id).ok())
+            .map_or(false, |admin| admin.can_administer());
+
+        if !is_admin {
+            return UserResponse::from_error(
+                "userId".to_owned(),
+                "Only admins can restore other users.".to_owned(),
+            );
+        }
+
+        let user_id = Uuid::parse_str(&user_id);
+
+        if let Err(e) = user_id {
+            return UserResponse::from_error(
+                "userId".to_owned(),
+                e.to_string(),
+            );
+        }
+
+        let grace = Duration::days(config().deletion.restore_grace_days);
+
+        match services::user::get_deleted_by_id(
+            &mut conn,
+            user_id.unwrap(),
+            Utc::now().naive_utc() - grace,
+        ) {
+            Ok(user) => restore(&mut conn, &user),
+            Err(_) => UserResponse::from_error(
+                "userId".to_owned(),
+                "No restorable account found.".to_owned(),
+            ),
+        }
+    }
+
+    /// Unlink an external account from the logged in user. Users without a
+    /// password cannot unlink their last one.
+    fn unlink_identity(
+        ctx: &Context,
+        identity_id: String,
+    ) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+        let identity_id = match Uuid::parse_str(&identity_id) {
+            Ok(identity_id) => identity_id,
+            Err(_) => return Ok(false),
+        };
+        let user = get_by_id(&mut conn, user_id)?;
+        let keep_last = user.password == services::password::UNUSABLE;
+
+        if services::identity::delete(
+            &mut conn,
+            identity_id,
+            user.id,
+            keep_last,
+        )? {
+            return Ok(true);
+        }
+
+        let linked =
+            services::identity::get_all_by_user_id(&mut conn, user.id)?;
+
+        if keep_last && linked.iter().any(|identity| identity.id == identity_id)
+        {
+            return Err(juniper::FieldError::from(
+                "Set a password before unlinking your last external account.",
+            ));
+        }
+
+        Ok(false)
+    }
+
+    /// Follow a user: their questions show up in the logged in user's feed.
+    fn follow_user(ctx: &Context, user_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let follower =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+        let followee = get_by_id(&mut conn, Uuid::parse_str(&user_id)?)
+            .map_err(|_| {
+                juniper::FieldError::from(
+                    "No user found with corresponding Id.",
+                )
+            })?;
+
+        if followee.id == follower {
+            return Err(juniper::FieldError::from(
+                "Users cannot follow themselves.",
+            ));
+        }
+
+        services::follow::follow(&mut conn, follower, followee.id)?;
+
+        Ok(true)
+    }
+
+    /// Stop following a user.
+    fn unfollow_user(ctx: &Context, user_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let follower =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+
+        if !services::follow::unfollow(
+            &mut conn,
+            follower,
+            Uuid::parse_str(&user_id)?,
+        )? {
+            return Err(juniper::FieldError::from("User was not followed."));
+        }
+
+        Ok(true)
+    }
+
+    /// Mark the logged in user's notifications as read, only
+    /// `notificationIds` if given. Returns how many were unread.
+    fn mark_notifications_read(
+        ctx: &Context,
+        notification_ids: Option<Vec<String>>,
+    ) -> FieldResult<i32> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id =
+            ctx.session.get::<Uuid>("userId").unwrap().ok_or_else(|| {
+                juniper::FieldError::from("User not logged in.")
+            })?;
+
+        let notification_ids = match notification_ids {
+            Some(notification_ids) => Some(
+                notification_ids
+                    .iter()
+                    .map(|id| Uuid::parse_str(id))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+
+        Ok(services::notification::mark_read(
+            &mut conn,
+            user_id,
+            notification_ids.as_deref(),
+        )? as i32)
+    }
+
     fn logout(ctx: &Context) -> bool {
         ctx.session.remove("userId");
         true
     }
 }
+
+/// Restores a deleted user, unless their username or email was taken since.
+/// How recent an external login must be to confirm a change to an account
+/// without a password.
+const REAUTHENTICATION_MINUTES: i64 = 5;
+
+/// Confirms a change to an account without a password: a code from its
+/// authenticator app, or a recent external login.
+fn confirm_without_password(
+    conn: &mut PgConnection,
+    ctx: &Context,
+    user: &User,
+    code: Option<String>,
+) -> bool {
+    let step = match (&user.totp_secret, code) {
+        (Some(secret), Some(code)) if user.totp_enabled => {
+            services::totp::verify_code(
+                secret,
+                code.trim(),
+                user.totp_last_step,
+            )
+        }
+        _ => None,
+    };
+
+    if let Some(step) = step {
+        return services::user::update_totp_last_step(conn, user.id, step)
+            .is_ok();
+    }
+
+    ctx.session
+        .get::<i64>("oauthLoginAt")
+        .ok()
+        .flatten()
+        .map_or(false, |at| {
+            Utc::now().timestamp() - at < REAUTHENTICATION_MINUTES * 60
+        })
+}
+
+fn restore(conn: &mut PgConnection, user: &User) -> UserResponse {
+    if services::user::get_by_username(conn, &user.username).is_ok()
+        || services::user::get_by_email(conn, &user.email).is_ok()
+    {
+        return UserResponse::from_error(
+            "usernameOrEmail".to_owned(),
+            "Username or email is now used by another account.".to_owned(),
+        );
+    }
+
+    match services::user::restore(conn, user) {
+        Ok(user) => UserResponse::from_user(user),
+        Err(e) => UserResponse::from_error("userId".to_owned(), e.to_string()),
+    }
+}
+
+fn get_follow(
+    conn: &mut PgConnection,
+    follower: Uuid,
+    followee: Uuid,
+) -> FieldResult<Follow> {
+    services::follow::get(conn, follower, followee).map_err(|_| {
+        juniper::FieldError::from("No follow found with corresponding Id.")
+    })
+}
+
+/// Keeps a user's private details and lists to themselves.
+fn check_self(ctx: &Context, user: &User) -> FieldResult<()> {
+    let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+
+    if user_id != Some(user.id) {
+        return Err(juniper::FieldError::from("Only the user can see this."));
+    }
+
+    Ok(())
+}
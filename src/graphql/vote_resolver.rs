@@ -1,9 +1,22 @@
+use std::{cmp::Reverse, collections::HashMap};
+
 use bigdecimal::BigDecimal;
+use chrono::Utc;
+use diesel::PgConnection;
+use juniper::{FieldError, FieldResult};
 use uuid::Uuid;
 
 use crate::{
     context::Context,
-    models::vote::{Vote, VoteInput, VoteResponse},
+    events::{self, Event},
+    models::{
+        question::{Question, QuestionOption, QuestionType, ResultsVisibility},
+        tally::{TallyMethod, TallyResult},
+        vote::{
+            AllocationInput, Credits, OpinionSnapshot, QuestionStats,
+            ShiftPeriod, StatBucket, Vote, VoteInput, VoteResponse,
+        },
+    },
     services,
 };
 
@@ -11,73 +24,323 @@ pub struct VoteQuery;
 
 #[juniper::graphql_object(Context = Context)]
 impl VoteQuery {
-    fn get_avg_for_question(ctx: &Context, question_id: String) -> String {
+    fn get_avg_for_question(
+        ctx: &Context,
+        question_id: String,
+    ) -> FieldResult<String> {
         let mut conn = ctx
             .pool
             .get()
             .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
         let question_id = Uuid::parse_str(&question_id);
 
         if let Err(_e) = question_id {
-            return "0.00".to_owned();
+            return Ok("0.00".to_owned());
         }
 
-        let avg =
-            services::vote::avg_for_question(&mut conn, question_id.unwrap());
+        let question_id = question_id.unwrap();
+        check_results_visible(&mut conn, user_id, question_id)?;
+
+        let avg = services::vote::avg_for_question(&mut conn, question_id);
 
         match avg {
-            Ok(avg) => {
-                format!("{:.2}", avg.unwrap_or_else(|| BigDecimal::from(0)).round(2))
-            }
-            Err(_e) => "0.00".to_owned(),
+            Ok(avg) => Ok(format!(
+                "{:.2}",
+                avg.unwrap_or_else(|| BigDecimal::from(0)).round(2)
+            )),
+            Err(_e) => Ok("0.00".to_owned()),
         }
     }
-    fn get_all_for_question(ctx: &Context, question_id: String) -> Vec<Vote> {
+    fn get_all_for_question(
+        ctx: &Context,
+        question_id: String,
+    ) -> FieldResult<Vec<Vote>> {
         let mut conn = ctx
             .pool
             .get()
             .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
         let question_id = Uuid::parse_str(&question_id);
 
         if let Err(_e) = question_id {
-            return Vec::new();
+            return Ok(Vec::new());
         }
 
-        let votes = services::vote::get_all_by_question_id(
-            &mut conn,
-            question_id.unwrap(),
-        );
+        let question_id = question_id.unwrap();
+        check_results_visible(&mut conn, user_id, question_id)?;
+
+        let votes =
+            services::vote::get_all_by_question_id(&mut conn, question_id);
 
         match votes {
-            Ok(votes) => votes,
-            Err(_e) => Vec::new(),
+            Ok(votes) => Ok(votes),
+            Err(_e) => Ok(Vec::new()),
         }
     }
-    fn get_stats_for_question(ctx: &Context, question_id: String) -> Vec<i32> {
+    /// How many votes gave each value from 0 to 5.
+    #[graphql(
+        deprecated = "Use getQuestionStats, which handles every question type."
+    )]
+    fn get_stats_for_question(
+        ctx: &Context,
+        question_id: String,
+    ) -> FieldResult<Vec<i32>> {
         let mut conn = ctx
             .pool
             .get()
             .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
         let question_id = Uuid::parse_str(&question_id);
 
         if let Err(_e) = question_id {
-            return vec![0, 0, 0, 0, 0, 0];
+            return Ok(vec![0, 0, 0, 0, 0, 0]);
         }
 
-        let mut result: Vec<i32> = vec![];
+        let question_id = question_id.unwrap();
+        check_results_visible(&mut conn, user_id, question_id)?;
+
+        let counts = services::vote::count_by_value(&mut conn, question_id)?;
+
+        Ok((0..6)
+            .map(|value| {
+                counts
+                    .iter()
+                    .find(|(v, _)| *v == value)
+                    .map_or(0, |(_, count)| *count as i32)
+            })
+            .collect())
+    }
+    /// The question's results, in buckets that depend on its type.
+    fn get_question_stats(
+        ctx: &Context,
+        question_id: String,
+    ) -> FieldResult<QuestionStats> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id)?;
+        let question = check_results_visible(&mut conn, user_id, question_id)?;
+
+        let total =
+            services::vote::count_for_question(&mut conn, question.id)? as i32;
+        let average = services::vote::avg_for_question(&mut conn, question.id)?
+            .map(|avg| format!("{:.2}", avg.round(2)));
 
-        for i in 0..6 {
-            result.push(
-                services::vote::count_for_question_with_value(
-                    &mut conn,
-                    question_id.clone().unwrap(),
-                    i,
+        let (buckets, winner) = match question.question_type {
+            // Ranked questions count first choices; see getRankedResults
+            // for the full tally.
+            QuestionType::Ranked => {
+                let options =
+                    services::question::get_options(&mut conn, question.id)?;
+                let ballots =
+                    services::vote::get_ballots(&mut conn, question.id)?;
+                let mut counts: HashMap<Uuid, i64> = HashMap::new();
+
+                for ballot in &ballots {
+                    if let Some(first) = ballot.first() {
+                        *counts.entry(*first).or_default() += 1;
+                    }
+                }
+
+                let option_ids: Vec<Uuid> =
+                    options.iter().map(|option| option.id).collect();
+                let winner = services::tally::tally(
+                    TallyMethod::InstantRunoff,
+                    &option_ids,
+                    &ballots,
                 )
-                .unwrap() as i32,
-            );
+                .winner;
+
+                (option_buckets(options, counts), winner)
+            }
+            question_type if question_type.has_options() => {
+                let options =
+                    services::question::get_options(&mut conn, question.id)?;
+                let counts: HashMap<Uuid, i64> =
+                    if question_type == QuestionType::Quadratic {
+                        services::vote::sum_weights_by_option(
+                            &mut conn,
+                            question.id,
+                        )?
+                    } else {
+                        services::vote::count_by_option(&mut conn, question.id)?
+                    }
+                    .into_iter()
+                    .collect();
+                let buckets = option_buckets(options, counts);
+                let winner = leading_option(&buckets);
+
+                (buckets, winner)
+            }
+            question_type => {
+                let counts =
+                    services::vote::count_by_value(&mut conn, question.id)?;
+                let count = |value: i32| {
+                    counts
+                        .iter()
+                        .find(|(v, _)| *v == value)
+                        .map_or(0, |(_, count)| *count as i32)
+                };
+                let bucket = |label: String, value: i32| StatBucket {
+                    label,
+                    value: Some(value),
+                    option_id: None,
+                    count: count(value),
+                };
+
+                let buckets = match question_type {
+                    QuestionType::YesNo => vec![
+                        bucket("No".to_owned(), 0),
+                        bucket("Yes".to_owned(), 1),
+                    ],
+                    QuestionType::Rating => {
+                        let min = question.scale_min.unwrap_or(0);
+                        let max = question.scale_max.unwrap_or(5);
+                        let step = question.scale_step.unwrap_or(1).max(1);
+
+                        (min..=max)
+                            .step_by(step as usize)
+                            .map(|value| {
+                                let label = match (value == min, value == max) {
+                                    (true, _) => question.min_label.clone(),
+                                    (_, true) => question.max_label.clone(),
+                                    _ => None,
+                                };
+                                bucket(
+                                    label.unwrap_or_else(|| value.to_string()),
+                                    value,
+                                )
+                            })
+                            .collect()
+                    }
+                    // Free numbers have no fixed buckets: list the values
+                    // given.
+                    _ => counts
+                        .iter()
+                        .map(|(value, _)| bucket(value.to_string(), *value))
+                        .collect(),
+                };
+
+                (buckets, None)
+            }
+        };
+
+        let comments =
+            services::comment::count_for_question(&mut conn, question.id)?
+                as i32;
+
+        Ok(QuestionStats {
+            question_type: question.question_type,
+            total,
+            average,
+            winner,
+            buckets,
+            comments,
+        })
+    }
+    /// The logged in user's credits on a quadratic question.
+    fn get_credits(ctx: &Context, question_id: String) -> FieldResult<Credits> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx
+            .session
+            .get::<Uuid>("userId")
+            .unwrap()
+            .ok_or_else(|| FieldError::from("User not logged in."))?;
+        let question_id = Uuid::parse_str(&question_id)?;
+        let question = services::question::get_by_id(&mut conn, question_id)
+            .map_err(|_| {
+                FieldError::from("No question found with corresponding Id.")
+            })?;
+
+        if question.question_type != QuestionType::Quadratic {
+            return Err(FieldError::from("Question is not quadratic."));
+        }
+
+        let budget = question.credit_budget.unwrap_or(0);
+        let spent = match services::vote::get_by_user_id_and_question_id(
+            &mut conn,
+            user_id,
+            question.id,
+        ) {
+            Ok(vote) => services::tally::quadratic_cost(
+                &services::vote::get_picks(&mut conn, vote.id)?,
+            ) as i32,
+            Err(_e) => 0,
+        };
+
+        Ok(Credits {
+            budget,
+            spent,
+            remaining: budget - spent,
+        })
+    }
+    /// Where the question's votes stood over time, by day unless another
+    /// `period` is given.
+    fn get_opinion_shift(
+        ctx: &Context,
+        question_id: String,
+        period: Option<ShiftPeriod>,
+    ) -> FieldResult<Vec<OpinionSnapshot>> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id)?;
+        let question = check_results_visible(&mut conn, user_id, question_id)?;
+
+        let options: Vec<Uuid> =
+            services::question::get_options(&mut conn, question.id)?
+                .iter()
+                .map(|option| option.id)
+                .collect();
+        let events =
+            services::vote::get_events_by_question_id(&mut conn, question.id)?;
+
+        Ok(services::tally::opinion_shift(
+            question.question_type,
+            &options,
+            &events,
+            period.unwrap_or(ShiftPeriod::Day),
+        ))
+    }
+    /// Count a ranked question's ballots, by instant runoff unless another
+    /// `method` is given.
+    fn get_ranked_results(
+        ctx: &Context,
+        question_id: String,
+        method: Option<TallyMethod>,
+    ) -> FieldResult<TallyResult> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx.session.get::<Uuid>("userId").unwrap();
+        let question_id = Uuid::parse_str(&question_id)?;
+        let question = check_results_visible(&mut conn, user_id, question_id)?;
+
+        if question.question_type != QuestionType::Ranked {
+            return Err(FieldError::from("Question is not ranked."));
         }
 
-        result
+        let options: Vec<Uuid> =
+            services::question::get_options(&mut conn, question.id)?
+                .iter()
+                .map(|option| option.id)
+                .collect();
+        let ballots = services::vote::get_ballots(&mut conn, question.id)?;
+
+        Ok(services::tally::tally(
+            method.unwrap_or(TallyMethod::InstantRunoff),
+            &options,
+            &ballots,
+        ))
     }
 }
 
@@ -85,7 +348,16 @@ pub struct VoteMutation;
 
 #[juniper::graphql_object(Context = Context)]
 impl VoteMutation {
-    fn create(ctx: &Context, question_id: String, value: i32) -> VoteResponse {
+    /// Vote on a question, or change an earlier vote. Choice questions take
+    /// `optionIds` (in order of preference for ranked ones), quadratic ones
+    /// `allocations`, every other type a `value`.
+    fn create(
+        ctx: &Context,
+        question_id: String,
+        value: Option<i32>,
+        option_ids: Option<Vec<String>>,
+        allocations: Option<Vec<AllocationInput>>,
+    ) -> VoteResponse {
         let mut conn = ctx
             .pool
             .get()
@@ -100,13 +372,6 @@ impl VoteMutation {
             );
         }
 
-        if !(0..=5).contains(&value) {
-            return VoteResponse::from_error(
-                "value".to_owned(),
-                "Value must be from 0 to 5".to_owned(),
-            );
-        }
-
         if user_id.is_none() {
             return VoteResponse::from_error(
                 "userId".to_owned(),
@@ -117,40 +382,167 @@ impl VoteMutation {
         let user_id = user_id.unwrap();
         let question_id = question_id.unwrap();
 
-        let vote = services::vote::get_by_user_id_and_question_id(
-            &mut conn,
-            user_id,
-            question_id,
-        );
+        let question = services::question::get_by_id(&mut conn, question_id);
+
+        if let Err(_e) = question {
+            return VoteResponse::from_error(
+                "questionId".to_owned(),
+                "No question found with corresponding Id.".to_owned(),
+            );
+        }
 
-        if let Ok(vote) = vote {
-            let vote = services::vote::update(&mut conn, vote.id, value);
+        let question = question.unwrap();
 
-            match vote {
-                Ok(vote) => return VoteResponse::from_vote(vote),
-                Err(e) => {
-                    return VoteResponse::from_error(
-                        "userId".to_owned(),
-                        e.to_string(),
-                    );
-                }
-            }
+        if !question.accepts_votes(Utc::now().naive_utc()) {
+            return VoteResponse::from_error(
+                "questionId".to_owned(),
+                "Question is not open for votes.".to_owned(),
+            );
         }
 
-        let vote = services::vote::create(
+        let picks = match services::vote::check_answer(
+            &mut conn,
+            &question,
+            value,
+            option_ids.unwrap_or_default(),
+            allocations.unwrap_or_default(),
+        ) {
+            Ok(picks) => picks,
+            Err((field, message)) => {
+                return VoteResponse::from_error(field.to_owned(), message)
+            }
+        };
+
+        let vote = services::vote::upsert(
             &mut conn,
             VoteInput {
                 value,
                 user_id,
                 question_id,
             },
+            &picks,
         );
 
         match vote {
-            Ok(vote) => VoteResponse::from_vote(vote),
+            Ok(vote) => {
+                if let Ok(votes) =
+                    services::vote::count_for_question(&mut conn, question.id)
+                {
+                    events::emit(&mut conn, Event::VoteCast(question, votes));
+                }
+                VoteResponse::from_vote(vote)
+            }
             Err(e) => {
                 VoteResponse::from_error("userId".to_owned(), e.to_string())
             }
         }
     }
+
+    /// Withdraw the logged in user's vote on a question that is still open.
+    fn retract(ctx: &Context, question_id: String) -> FieldResult<bool> {
+        let mut conn = ctx
+            .pool
+            .get()
+            .expect("Failed to get connection to database.");
+        let user_id = ctx
+            .session
+            .get::<Uuid>("userId")
+            .unwrap()
+            .ok_or_else(|| FieldError::from("User not logged in."))?;
+        let question_id = Uuid::parse_str(&question_id)?;
+        let question = services::question::get_by_id(&mut conn, question_id)
+            .map_err(|_| {
+                FieldError::from("No question found with corresponding Id.")
+            })?;
+
+        if !question.accepts_votes(Utc::now().naive_utc()) {
+            return Err(FieldError::from("Question is not open for votes."));
+        }
+
+        if !services::vote::retract(&mut conn, user_id, question.id)? {
+            return Err(FieldError::from("No vote to retract."));
+        }
+
+        Ok(true)
+    }
+}
+
+fn option_buckets(
+    options: Vec<QuestionOption>,
+    counts: HashMap<Uuid, i64>,
+) -> Vec<StatBucket> {
+    options
+        .into_iter()
+        .map(|option| StatBucket {
+            count: *counts.get(&option.id).unwrap_or(&0) as i32,
+            label: option.label,
+            value: None,
+            option_id: Some(option.id),
+        })
+        .collect()
+}
+
+/// The option with the most votes, the first listed if several tie.
+fn leading_option(buckets: &[StatBucket]) -> Option<Uuid> {
+    buckets
+        .iter()
+        .enumerate()
+        .filter(|(_, bucket)| bucket.count > 0)
+        .max_by_key(|(i, bucket)| (bucket.count, Reverse(*i)))
+        .and_then(|(_, bucket)| bucket.option_id)
+}
+
+/// Applies the question's results visibility to `user_id`, returning the
+/// question. Its author and moderators can always see the results, and
+/// nobody else can while the question is hidden.
+pub(super) fn check_results_visible(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    question_id: Uuid,
+) -> FieldResult<Question> {
+    let question =
+        services::question::get_by_id(conn, question_id).map_err(|_| {
+            FieldError::from("No question found with corresponding Id.")
+        })?;
+    let user = user_id.and_then(|id| services::user::get_by_id(conn, id).ok());
+
+    if let Some(user) = &user {
+        if user.id == question.user_id || user.can_moderate() {
+            return Ok(question);
+        }
+    }
+
+    if question.hidden_at.is_some() {
+        return Err(FieldError::from(
+            "No question found with corresponding Id.",
+        ));
+    }
+
+    let visible = match question.results_visibility {
+        ResultsVisibility::Always => true,
+        ResultsVisibility::AfterVote => user.map_or(false, |user| {
+            services::vote::get_by_user_id_and_question_id(
+                conn,
+                user.id,
+                question_id,
+            )
+            .is_ok()
+        }),
+        ResultsVisibility::AfterClose => {
+            question.is_closed(Utc::now().naive_utc())
+        }
+        ResultsVisibility::AuthorOnly => false,
+    };
+
+    if visible {
+        Ok(question)
+    } else {
+        Err(FieldError::from(match question.results_visibility {
+            ResultsVisibility::AfterVote => "Vote to see the results.",
+            ResultsVisibility::AfterClose => {
+                "Results are shown once the question closes."
+            }
+            _ => "Results are only visible to the author.",
+        }))
+    }
 }
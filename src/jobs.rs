@@ -0,0 +1,193 @@
+use std::time::Duration as StdDuration;
+
+use actix_web::{rt, web};
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+
+use crate::{
+    config::config,
+    database::{get_pool, PostgresPool},
+    events::{self, Event},
+    link_preview, services,
+    storage::storage,
+};
+
+const PURGE_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+const SCHEDULE_INTERVAL: StdDuration = StdDuration::from_secs(60);
+const PREVIEW_INTERVAL: StdDuration = StdDuration::from_secs(30);
+const PREVIEW_BATCH: i64 = 20;
+
+/// Starts the background tasks. Must be called from within the actix runtime.
+pub fn spawn() {
+    let pool = get_pool();
+
+    every(
+        PURGE_INTERVAL,
+        pool.clone(),
+        "purge deleted rows",
+        purge_deleted,
+    );
+    every(
+        PURGE_INTERVAL,
+        pool.clone(),
+        "purge expired login challenges",
+        purge_expired_challenges,
+    );
+    every(
+        PURGE_INTERVAL,
+        pool.clone(),
+        "purge unused uploads",
+        purge_unused_uploads,
+    );
+    every(
+        SCHEDULE_INTERVAL,
+        pool.clone(),
+        "update question statuses",
+        update_question_statuses,
+    );
+
+    if config().question.link_previews {
+        fetch_link_previews(pool);
+    }
+}
+
+/// Runs `job` on the blocking thread pool every `period`, logging failures.
+fn every(
+    period: StdDuration,
+    pool: PostgresPool,
+    name: &'static str,
+    job: fn(&mut PgConnection) -> diesel::QueryResult<()>,
+) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(period);
+
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+
+            let result = web::block(move || {
+                let mut conn =
+                    pool.get().expect("Failed to get connection to database.");
+                job(&mut conn)
+            })
+            .await;
+
+            if let Ok(Err(e)) = result {
+                log::error!("Failed to {}: {}", name, e);
+            }
+        }
+    });
+}
+
+/// Fetches the previews of newly linked pages every `PREVIEW_INTERVAL`. Pages
+/// are fetched one at a time, outside of the blocking thread pool.
+fn fetch_link_previews(pool: PostgresPool) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(PREVIEW_INTERVAL);
+        let client = link_preview::client();
+
+        loop {
+            interval.tick().await;
+            let conn_pool = pool.clone();
+
+            let pending = web::block(move || {
+                let mut conn = conn_pool
+                    .get()
+                    .expect("Failed to get connection to database.");
+                services::link::get_pending(&mut conn, PREVIEW_BATCH)
+            })
+            .await;
+
+            let pending = match pending {
+                Ok(Ok(pending)) => pending,
+                Ok(Err(e)) => {
+                    log::error!("Failed to get pending link previews: {}", e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+
+            for url in pending {
+                let preview = link_preview::fetch(&client, &url).await;
+                let conn_pool = pool.clone();
+
+                let result = web::block(move || {
+                    let mut conn = conn_pool
+                        .get()
+                        .expect("Failed to get connection to database.");
+                    services::link::set_preview(&mut conn, &url, preview)
+                })
+                .await;
+
+                if let Ok(Err(e)) = result {
+                    log::error!("Failed to store a link preview: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Hard deletes questions, users and comments deleted longer ago than the
+/// retention window, and previews no question links to.
+fn purge_deleted(conn: &mut PgConnection) -> diesel::QueryResult<()> {
+    let before = Utc::now().naive_utc()
+        - Duration::days(config().deletion.retention_days);
+
+    let questions = services::question::purge_deleted(conn, before)?;
+    let users = services::user::purge_deleted(conn, before)?;
+    services::comment::purge_deleted(conn, before)?;
+    services::link::delete_unused(conn)?;
+
+    if questions + users > 0 {
+        log::info!(
+            "Purged {} deleted questions and {} deleted users",
+            questions,
+            users
+        );
+    }
+
+    Ok(())
+}
+
+/// Deletes the login challenges of two-factor logins that were never
+/// completed.
+fn purge_expired_challenges(
+    conn: &mut PgConnection,
+) -> diesel::QueryResult<()> {
+    services::totp::delete_expired_challenges(conn, Utc::now().naive_utc())?;
+
+    Ok(())
+}
+
+/// Deletes images that were uploaded but never attached to a question, or
+/// whose question is gone.
+fn purge_unused_uploads(conn: &mut PgConnection) -> diesel::QueryResult<()> {
+    let before =
+        Utc::now().naive_utc() - Duration::hours(config().upload.unused_hours);
+
+    for image in services::image::delete_unused(conn, before)? {
+        for key in [&image.storage_key, &image.thumbnail_key] {
+            if let Err(e) = storage().delete(key) {
+                log::error!("Failed to delete upload {}: {}", key, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens scheduled questions and closes those past their deadline.
+fn update_question_statuses(
+    conn: &mut PgConnection,
+) -> diesel::QueryResult<()> {
+    let now = Utc::now().naive_utc();
+
+    for question in services::question::open_scheduled(conn, now)? {
+        events::emit(conn, Event::QuestionOpened(question));
+    }
+    for question in services::question::close_expired(conn, now)? {
+        events::emit(conn, Event::QuestionClosed(question));
+    }
+
+    Ok(())
+}
@@ -5,13 +5,22 @@ use graphql::{MutationRoot, QueryRoot};
 use juniper::{EmptySubscription, RootNode};
 use juniper_actix::graphql_handler;
 
+mod config;
 mod context;
 mod database;
+mod events;
 mod graphql;
+pub mod jobs;
+mod link_preview;
 mod models;
+pub mod oauth;
+pub mod rate_limit;
 mod schema;
 mod services;
 mod shared;
+mod storage;
+pub mod upload;
+mod validation;
 
 pub type Schema =
     RootNode<'static, QueryRoot, MutationRoot, EmptySubscription<Context>>;
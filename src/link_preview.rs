@@ -0,0 +1,366 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use actix_web::{http::header, web};
+use awc::{http::Uri, Client};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::services::markdown::is_web_url;
+
+/// What a page says about itself, from its `<title>` and Open Graph tags.
+#[derive(Default)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+}
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: usize = 3;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+const MAX_URL_LENGTH: usize = 2048;
+
+static META_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+});
+static TITLE_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+pub fn client() -> Client {
+    Client::builder()
+        .timeout(TIMEOUT)
+        // Redirects are followed by hand, to check every address.
+        .disable_redirects()
+        .finish()
+}
+
+/// Fetches the page at `url` and reads its metadata. Only public addresses
+/// are fetched, so questions cannot be used to probe the server's network.
+pub async fn fetch(client: &Client, url: &str) -> Result<PageMetadata, String> {
+    let mut url = url.to_owned();
+
+    for _ in 0..=MAX_REDIRECTS {
+        let uri: Uri = url.parse().map_err(|_| "Invalid URL.".to_owned())?;
+        let address = check_public(&uri).await?;
+
+        // The vetted address is connected to rather than the host name,
+        // which could resolve somewhere else the second time around.
+        let mut response = client
+            .get(uri.clone())
+            .address(address)
+            .insert_header((header::USER_AGENT, "votodroid-server"))
+            .insert_header((header::ACCEPT, "text/html"))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| "Redirect without a location.".to_owned())?;
+            url = resolve(&uri, location)?;
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(format!("Page answered {}.", response.status()));
+        }
+
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .map_or(false, |content_type| content_type.contains("text/html"));
+
+        if !is_html {
+            return Err("Not an HTML page.".to_owned());
+        }
+
+        let body = response
+            .body()
+            .limit(MAX_BODY_BYTES)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        return Ok(parse(&String::from_utf8_lossy(&body)));
+    }
+
+    Err("Too many redirects.".to_owned())
+}
+
+/// Reads the Open Graph title, description and image of a page, falling
+/// back to its `<title>` and meta description.
+fn parse(page: &str) -> PageMetadata {
+    let mut meta: HashMap<String, String> = HashMap::new();
+
+    for tag in META_TAG.find_iter(page) {
+        let attributes: HashMap<String, String> = ATTRIBUTE
+            .captures_iter(tag.as_str())
+            .map(|attribute| {
+                let value = attribute.get(2).or_else(|| attribute.get(3));
+                (
+                    attribute[1].to_lowercase(),
+                    value.map_or("", |value| value.as_str()).to_owned(),
+                )
+            })
+            .collect();
+        let key = attributes
+            .get("property")
+            .or_else(|| attributes.get("name"));
+
+        if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+            meta.entry(key.to_lowercase())
+                .or_insert_with(|| content.clone());
+        }
+    }
+
+    let text = |value: Option<&String>, max: usize| {
+        value
+            .map(|value| {
+                decode_entities(value)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .chars()
+                    .take(max)
+                    .collect::<String>()
+            })
+            .filter(|value| !value.is_empty())
+    };
+    let title = TITLE_TAG.captures(page).map(|title| title[1].to_owned());
+
+    PageMetadata {
+        title: text(meta.get("og:title").or(title.as_ref()), MAX_TITLE_LENGTH),
+        description: text(
+            meta.get("og:description")
+                .or_else(|| meta.get("description")),
+            MAX_DESCRIPTION_LENGTH,
+        ),
+        image_url: meta
+            .get("og:image")
+            .map(|image| decode_entities(image))
+            .filter(|image| image.len() <= MAX_URL_LENGTH && is_web_url(image)),
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Resolves a redirect's `location` against the URL that sent it.
+fn resolve(from: &Uri, location: &str) -> Result<String, String> {
+    if is_web_url(location) {
+        return Ok(location.to_owned());
+    }
+
+    match (
+        from.scheme_str(),
+        from.authority(),
+        location.starts_with('/'),
+    ) {
+        (Some(scheme), Some(authority), true)
+            if !location.starts_with("//") =>
+        {
+            Ok(format!("{}://{}{}", scheme, authority, location))
+        }
+        _ => Err("Unsupported redirect.".to_owned()),
+    }
+}
+
+/// Fails unless every address the URL's host resolves to is public, else
+/// returns the one to connect to.
+async fn check_public(uri: &Uri) -> Result<SocketAddr, String> {
+    if !is_web_url(&uri.to_string()) {
+        return Err("Only web pages can be previewed.".to_owned());
+    }
+
+    let host = uri.host().unwrap_or_default().to_owned();
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("https") => 443,
+        _ => 80,
+    });
+    let addresses = web::block(move || {
+        (host.trim_start_matches('[').trim_end_matches(']'), port)
+            .to_socket_addrs()
+            .map(|addresses| addresses.collect::<Vec<_>>())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+
+    if addresses.iter().any(|address| !is_public(address.ip())) {
+        return Err("Only public addresses can be previewed.".to_owned());
+    }
+
+    addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Only public addresses can be previewed.".to_owned())
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped().or_else(|| nat64(&ip)) {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local and link-local addresses
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The IPv4 address a NAT64 address (`64:ff9b::/96`) is translated to.
+fn nat64(ip: &Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d, e, f, g, h] = ip.segments();
+
+    ([a, b, c, d, e, f] == [0x64, 0xff9b, 0, 0, 0, 0]).then(|| {
+        Ipv4Addr::new((g >> 8) as u8, g as u8, (h >> 8) as u8, h as u8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn allows_public_addresses() {
+        assert!(public("93.184.216.34"));
+        assert!(public("8.8.8.8"));
+        assert!(public("2606:2800:220:1:248:1893:25c8:1946"));
+        assert!(public("::ffff:93.184.216.34"));
+        assert!(public("64:ff9b::5db8:d822"));
+    }
+
+    #[test]
+    fn refuses_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "0.1.2.3",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+            "192.0.2.1",
+            "::",
+            "::1",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!public(ip), "{} should not be public", ip);
+        }
+    }
+
+    #[test]
+    fn reads_open_graph_metadata() {
+        let metadata = parse(
+            r#"<html><head>
+            <title>Fallback title</title>
+            <meta property="og:title" content="The &quot;real&quot; title">
+            <meta property="og:title" content="A later title">
+            <META NAME='description' CONTENT='A   short
+              description'>
+            <meta content="https://example.com/a.png?x=1&amp;y=2"
+                  property="og:image">
+            </head></html>"#,
+        );
+
+        assert_eq!(metadata.title.as_deref(), Some("The \"real\" title"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("A short description")
+        );
+        assert_eq!(
+            metadata.image_url.as_deref(),
+            Some("https://example.com/a.png?x=1&y=2")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_title_tag() {
+        let metadata = parse(
+            "<title>\n  Cats &amp; dogs\n</title>\
+             <meta property=\"og:image\" content=\"javascript:alert(1)\">",
+        );
+
+        assert_eq!(metadata.title.as_deref(), Some("Cats & dogs"));
+        assert_eq!(metadata.description, None);
+        assert_eq!(metadata.image_url, None);
+    }
+
+    #[test]
+    fn truncates_and_drops_empty_values() {
+        let metadata = parse(&format!(
+            "<title>{}</title><meta name=\"description\" content=\"  \">",
+            "a".repeat(MAX_TITLE_LENGTH + 10)
+        ));
+
+        assert_eq!(metadata.title.unwrap().len(), MAX_TITLE_LENGTH);
+        assert_eq!(metadata.description, None);
+        assert!(parse("").title.is_none());
+    }
+
+    #[test]
+    fn resolves_redirects() {
+        let from: Uri = "https://a.example/x?y".parse().unwrap();
+
+        assert_eq!(resolve(&from, "/z"), Ok("https://a.example/z".to_owned()));
+        assert_eq!(
+            resolve(&from, "http://b.example/"),
+            Ok("http://b.example/".to_owned())
+        );
+        assert!(resolve(&from, "//b.example/").is_err());
+        assert!(resolve(&from, "z").is_err());
+        assert!(resolve(&from, "file:///etc/passwd").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use actix_cors::Cors;
 use actix_session::{storage::RedisActorSessionStore, SessionMiddleware};
@@ -10,7 +10,13 @@ use actix_web::{
 };
 #[cfg(debug_assertions)]
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
-use votodroid_server::{graphql_route, schema};
+use votodroid_server::{
+    graphql_route, jobs,
+    oauth::{oauth_callback_route, oauth_login_route},
+    rate_limit::{RateLimitMiddleware, RateLimiter},
+    schema,
+    upload::{payload_config, upload_file_route, upload_route},
+};
 
 #[cfg(not(debug_assertions))]
 #[actix_web::main]
@@ -20,6 +26,8 @@ async fn main() -> std::io::Result<()> {
 
     let secret_key = Key::generate();
     let redis_url = "127.0.0.1:6379";
+    let rate_limiter = Arc::new(RateLimiter::from_config());
+    jobs::spawn();
 
     let server = HttpServer::new(move || {
         App::new()
@@ -33,9 +41,31 @@ async fn main() -> std::io::Result<()> {
             .wrap(middleware::Logger::default())
             .service(
                 web::resource("/graphql")
+                    .wrap(RateLimitMiddleware::new(rate_limiter.clone()))
                     .route(web::post().to(graphql_route))
                     .route(web::get().to(graphql_route)),
             )
+            .service(
+                web::resource("/auth/{provider}")
+                    .route(web::get().to(oauth_login_route)),
+            )
+            .service(
+                web::resource("/auth/{provider}/callback")
+                    .route(web::get().to(oauth_callback_route)),
+            )
+            .service(
+                web::resource("/uploads")
+                    .wrap(RateLimitMiddleware::for_operation(
+                        rate_limiter.clone(),
+                        "uploads.create",
+                    ))
+                    .app_data(payload_config())
+                    .route(web::post().to(upload_route)),
+            )
+            .service(
+                web::resource("/uploads/{key}")
+                    .route(web::get().to(upload_file_route)),
+            )
     });
 
     server.bind("127.0.0.1:8080").unwrap().run().await
@@ -49,8 +79,11 @@ async fn main() -> std::io::Result<()> {
 
     let secret_key = Key::generate();
     let redis_url = "127.0.0.1:6379";
+    let rate_limiter = Arc::new(RateLimiter::from_config());
+    jobs::spawn();
 
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    let mut builder =
+        SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
     builder
         .set_private_key_file("nopass.pem", SslFiletype::PEM)
         .unwrap();
@@ -68,9 +101,31 @@ async fn main() -> std::io::Result<()> {
             .wrap(middleware::Logger::default())
             .service(
                 web::resource("/graphql")
+                    .wrap(RateLimitMiddleware::new(rate_limiter.clone()))
                     .route(web::post().to(graphql_route))
                     .route(web::get().to(graphql_route)),
             )
+            .service(
+                web::resource("/auth/{provider}")
+                    .route(web::get().to(oauth_login_route)),
+            )
+            .service(
+                web::resource("/auth/{provider}/callback")
+                    .route(web::get().to(oauth_callback_route)),
+            )
+            .service(
+                web::resource("/uploads")
+                    .wrap(RateLimitMiddleware::for_operation(
+                        rate_limiter.clone(),
+                        "uploads.create",
+                    ))
+                    .app_data(payload_config())
+                    .route(web::post().to(upload_route)),
+            )
+            .service(
+                web::resource("/uploads/{key}")
+                    .route(web::get().to(upload_file_route)),
+            )
     });
     server
         .bind_openssl("127.0.0.1:8080", builder)
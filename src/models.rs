@@ -1,4 +1,14 @@
+pub(crate) mod bookmark;
+pub(crate) mod comment;
+pub(crate) mod follow;
+pub(crate) mod identity;
+pub(crate) mod image;
+pub(crate) mod notification;
 pub(crate) mod question;
+pub(crate) mod report;
+pub(crate) mod tag;
+pub(crate) mod tally;
+pub(crate) mod totp;
 pub(crate) mod types;
 pub(crate) mod user;
 pub(crate) mod vote;
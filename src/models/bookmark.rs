@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use juniper::GraphQLObject;
+use uuid::Uuid;
+
+use crate::context::Context;
+
+use super::question::Question;
+
+/// A question a user saved to come back to, and maybe follows.
+#[derive(Clone, Queryable)]
+pub struct Bookmark {
+    pub user_id: Uuid,
+    pub question_id: Uuid,
+    /// Whether the user is notified about the question
+    pub follow: bool,
+    /// The highest vote threshold the user was notified of
+    pub notified_votes: i32,
+    pub created_at: NaiveDateTime,
+}
+
+/// A bookmarked question.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct BookmarkedQuestion {
+    pub question: Question,
+    /// Whether the user is notified when the question's votes reach a
+    /// threshold or when it closes
+    pub follow: bool,
+    /// The date and time the question was bookmarked
+    pub bookmarked_at: NaiveDateTime,
+}
@@ -0,0 +1,100 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sql_types::Text, AsExpression, FromSqlRow};
+use juniper::{GraphQLEnum, GraphQLObject};
+use uuid::Uuid;
+use votodroid_server_derive::{VotodroidDbEnum, VotodroidResponseObject};
+
+use crate::{context::Context, schema};
+
+use super::types::FieldError;
+
+/// A comment on a question, or a reply to another comment. Its GraphQL
+/// fields are resolved in `graphql::comment_resolver`.
+#[derive(Clone, Queryable)]
+pub struct Comment {
+    /// The comment's id (UUID)
+    pub id: Uuid,
+    /// The question the comment is about
+    pub question_id: Uuid,
+    /// The user who wrote the comment
+    pub user_id: Uuid,
+    /// The comment replied to, if any
+    pub parent_id: Option<Uuid>,
+    /// The comment's text, in markdown
+    pub body: String,
+    /// The date and time the comment was written
+    pub created_at: NaiveDateTime,
+    /// The date and time the comment was last edited
+    pub updated_at: NaiveDateTime,
+    /// The date and time the comment was deleted, if it was
+    pub deleted_at: Option<NaiveDateTime>,
+    /// The date and time the comment was hidden until a moderator reviews
+    /// it, if it is
+    pub hidden_at: Option<NaiveDateTime>,
+}
+
+impl Comment {
+    /// Whether the comment's text and author can be shown: it was neither
+    /// deleted nor hidden for review.
+    pub fn is_visible(&self) -> bool {
+        self.deleted_at.is_none() && self.hidden_at.is_none()
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::comments)]
+pub struct CommentInput {
+    pub question_id: Uuid,
+    pub user_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ReactionKind {
+    /// Agrees or appreciates
+    Like,
+    /// Learned something
+    Insightful,
+    /// Found it funny
+    Funny,
+    /// Disagrees
+    Disagree,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+pub enum CommentSort {
+    /// Most recent first
+    New,
+    /// Oldest first, to read a thread in order
+    Old,
+    /// Most reactions first
+    Top,
+}
+
+/// The reactions of one kind to a comment.
+#[derive(GraphQLObject)]
+pub struct ReactionCount {
+    pub kind: ReactionKind,
+    pub count: i32,
+    /// Whether the logged in user reacted so
+    pub reacted: bool,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
+pub struct CommentResponse {
+    pub comment: Option<Comment>,
+    pub errors: Option<Vec<FieldError>>,
+}
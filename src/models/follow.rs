@@ -0,0 +1,11 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// A user following another.
+#[derive(Clone, Queryable)]
+pub struct Follow {
+    pub follower_id: Uuid,
+    pub followee_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use juniper::GraphQLObject;
+use uuid::Uuid;
+
+use crate::schema;
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///An external account (Google, GitHub, ...) linked to a user
+pub struct Identity {
+    /// The identity's id (UUID)
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub user_id: Uuid,
+    /// The provider's name
+    pub provider: String,
+    /// The account's id at the provider
+    pub subject: String,
+    /// The email the provider returned
+    pub email: Option<String>,
+    /// The date and time the identity was linked
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::identities)]
+pub struct IdentityInput {
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
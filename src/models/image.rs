@@ -0,0 +1,46 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema;
+
+/// An uploaded image. Its GraphQL fields are resolved in
+/// `graphql::question_resolver`.
+#[derive(Clone, Queryable)]
+pub struct Image {
+    /// The image's id (UUID)
+    pub id: Uuid,
+    /// The user who uploaded the image
+    pub user_id: Uuid,
+    /// The question the image is attached to, if any yet
+    pub question_id: Option<Uuid>,
+    /// Where the image is shown among the question's, from 0
+    pub position: i32,
+    /// The image's MIME type
+    pub content_type: String,
+    /// The image's width, in pixels
+    pub width: i32,
+    /// The image's height, in pixels
+    pub height: i32,
+    /// The stored image's size, in bytes
+    pub size_bytes: i32,
+    /// Where the image is stored
+    pub storage_key: String,
+    /// Where the image's thumbnail is stored
+    pub thumbnail_key: String,
+    /// The date and time the image was uploaded
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::images)]
+pub struct ImageInput {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub size_bytes: i32,
+    pub storage_key: String,
+    pub thumbnail_key: String,
+}
@@ -0,0 +1,45 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sql_types::Text, AsExpression, FromSqlRow};
+use juniper::{GraphQLEnum, GraphQLObject};
+use uuid::Uuid;
+use votodroid_server_derive::VotodroidDbEnum;
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///Something that happened to what a user follows
+pub struct Notification {
+    /// The notification's id (UUID)
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub user_id: Uuid,
+    /// The question the notification is about, if any
+    pub question_id: Option<Uuid>,
+    /// What happened
+    pub kind: NotificationKind,
+    /// What happened, for people
+    pub message: String,
+    /// The date and time the notification was sent
+    pub created_at: NaiveDateTime,
+    /// The date and time the user read the notification, if they did
+    pub read_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum NotificationKind {
+    /// A followed question's votes reached a threshold
+    VotesReached,
+    /// A followed question closed
+    QuestionClosed,
+    /// A moderator resolved the user's report
+    ReportResolved,
+}
@@ -1,15 +1,16 @@
 use chrono::NaiveDateTime;
-use diesel::prelude::*;
-use juniper::{GraphQLInputObject, GraphQLObject};
+use diesel::{prelude::*, sql_types::Text, AsExpression, FromSqlRow};
+use juniper::{GraphQLEnum, GraphQLInputObject, GraphQLObject};
 use uuid::Uuid;
-use votodroid_server_derive::VotodroidResponseObject;
+use votodroid_server_derive::{VotodroidDbEnum, VotodroidResponseObject};
 
-use crate::schema;
+use crate::{context::Context, schema};
 
 use super::types::FieldError;
 
-#[derive(Clone, Queryable, GraphQLObject)]
-///A question
+/// A question. Its GraphQL fields are resolved in
+/// `graphql::question_resolver`.
+#[derive(Clone, Queryable)]
 pub struct Question {
     /// The question's id (UUID)
     pub id: Uuid,
@@ -21,6 +22,233 @@ pub struct Question {
     pub updated_at: NaiveDateTime,
     /// The user who created the question
     pub user_id: Uuid,
+    /// The date and time the question was deleted, if it was
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Where the question is in its lifecycle
+    pub status: QuestionStatus,
+    /// The date and time the question opens for votes, if scheduled
+    pub opens_at: Option<NaiveDateTime>,
+    /// The date and time the question stops accepting votes, if any
+    pub closes_at: Option<NaiveDateTime>,
+    /// Who can see the question's results, and when
+    pub results_visibility: ResultsVisibility,
+    /// How the question is answered
+    pub question_type: QuestionType,
+    /// The lowest accepted value, for ratings and numbers
+    pub scale_min: Option<i32>,
+    /// The highest accepted value, for ratings and numbers
+    pub scale_max: Option<i32>,
+    /// The interval between accepted ratings
+    pub scale_step: Option<i32>,
+    /// The label of the lowest rating
+    pub min_label: Option<String>,
+    /// The label of the highest rating
+    pub max_label: Option<String>,
+    /// The credits each voter can spend, for quadratic questions
+    pub credit_budget: Option<i32>,
+    /// Who can see who voted what
+    pub voter_visibility: VoterVisibility,
+    /// The question's description, in markdown
+    pub description: Option<String>,
+    /// The question's description, rendered to sanitized HTML
+    pub description_html: Option<String>,
+    /// Where the question comes from
+    pub source_url: Option<String>,
+    /// The date and time the question was hidden until a moderator reviews
+    /// it, if it is
+    pub hidden_at: Option<NaiveDateTime>,
+}
+
+impl Question {
+    /// Whether votes can be cast or retracted. Hidden questions take none
+    /// until a moderator restores them.
+    pub fn accepts_votes(&self, now: NaiveDateTime) -> bool {
+        self.status == QuestionStatus::Open
+            && self.hidden_at.is_none()
+            && self.opens_at.map_or(true, |opens_at| opens_at <= now)
+            && self.closes_at.map_or(true, |closes_at| closes_at > now)
+    }
+
+    pub fn is_closed(&self, now: NaiveDateTime) -> bool {
+        matches!(
+            self.status,
+            QuestionStatus::Closed | QuestionStatus::Archived
+        ) || self.closes_at.map_or(false, |closes_at| closes_at <= now)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum QuestionStatus {
+    /// Not open for votes yet
+    Draft,
+    /// Accepting votes
+    Open,
+    /// No longer accepting votes
+    Closed,
+    /// Closed for good
+    Archived,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ResultsVisibility {
+    /// Anyone, at any time
+    Always,
+    /// Users who voted on the question
+    AfterVote,
+    /// Anyone, once the question is closed
+    AfterClose,
+    /// Only the question's author
+    AuthorOnly,
+}
+
+impl ResultsVisibility {
+    /// Whether everyone who can see results under `self` already could under
+    /// `other`.
+    pub fn is_within(self, other: ResultsVisibility) -> bool {
+        self == other
+            || self == ResultsVisibility::AuthorOnly
+            || other == ResultsVisibility::Always
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+/// From the most to the least private
+pub enum VoterVisibility {
+    /// No one
+    Anonymous,
+    /// Only the question's author
+    Author,
+    /// Anyone who can see the results
+    Public,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum QuestionType {
+    /// Answered with 1 (yes) or 0 (no)
+    YesNo,
+    /// Answered with a value on a scale
+    Rating,
+    /// Answered by picking one option
+    SingleChoice,
+    /// Answered by picking one or more options
+    MultipleChoice,
+    /// Answered by ranking options in order of preference
+    Ranked,
+    /// Answered by approving any number of options
+    Approval,
+    /// Answered by spreading votes over options, `n` votes on an option
+    /// costing `n²` credits
+    Quadratic,
+    /// Answered with any number, optionally bounded
+    Numeric,
+}
+
+impl QuestionType {
+    /// Whether the question is answered by picking options.
+    pub fn has_options(&self) -> bool {
+        matches!(
+            self,
+            QuestionType::SingleChoice
+                | QuestionType::MultipleChoice
+                | QuestionType::Ranked
+                | QuestionType::Approval
+                | QuestionType::Quadratic
+        )
+    }
+}
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///A possible answer to a choice question
+pub struct QuestionOption {
+    /// The option's id (UUID)
+    pub id: Uuid,
+    /// The question the option belongs to
+    pub question_id: Uuid,
+    /// Where the option is listed, from 0
+    pub position: i32,
+    /// The option's text
+    pub label: String,
+}
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///A preview of a linked page
+pub struct LinkPreview {
+    /// The page's URL
+    pub url: String,
+    /// The page's title
+    pub title: Option<String>,
+    /// The page's summary
+    pub description: Option<String>,
+    /// The page's preview image
+    pub image_url: Option<String>,
+    /// Why the page could not be previewed, if it could not
+    #[graphql(skip)]
+    pub error: Option<String>,
+    #[graphql(skip)]
+    pub created_at: NaiveDateTime,
+    /// The date and time the preview was fetched, none while pending
+    pub fetched_at: Option<NaiveDateTime>,
+}
+
+#[derive(GraphQLInputObject, Default)]
+/// The scale of a rating question, or the bounds of a numeric one
+pub struct ScaleInput {
+    /// The lowest accepted value (0 by default for ratings)
+    pub min: Option<i32>,
+    /// The highest accepted value (5 by default for ratings)
+    pub max: Option<i32>,
+    /// The interval between accepted ratings (1 by default)
+    pub step: Option<i32>,
+    /// The label of the lowest rating
+    pub min_label: Option<String>,
+    /// The label of the highest rating
+    pub max_label: Option<String>,
 }
 
 #[derive(GraphQLInputObject, Insertable)]
@@ -30,15 +258,88 @@ pub struct QuestionInput {
     pub text: String,
     // The user who created the question
     pub user_id: Uuid,
+    /// Where the question starts in its lifecycle
+    pub status: QuestionStatus,
+    /// The date and time the question opens for votes, if scheduled
+    pub opens_at: Option<NaiveDateTime>,
+    /// The date and time the question stops accepting votes, if any
+    pub closes_at: Option<NaiveDateTime>,
+    /// Who can see the question's results, and when
+    pub results_visibility: ResultsVisibility,
+    /// How the question is answered
+    pub question_type: QuestionType,
+    /// The lowest accepted value, for ratings and numbers
+    pub scale_min: Option<i32>,
+    /// The highest accepted value, for ratings and numbers
+    pub scale_max: Option<i32>,
+    /// The interval between accepted ratings
+    pub scale_step: Option<i32>,
+    /// The label of the lowest rating
+    pub min_label: Option<String>,
+    /// The label of the highest rating
+    pub max_label: Option<String>,
+    /// The credits each voter can spend, for quadratic questions
+    pub credit_budget: Option<i32>,
+    /// Who can see who voted what
+    pub voter_visibility: VoterVisibility,
+    /// The question's description, in markdown
+    pub description: Option<String>,
+    /// The question's description, rendered to sanitized HTML
+    pub description_html: Option<String>,
+    /// Where the question comes from
+    pub source_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+pub enum QuestionSort {
+    /// Best matches first
+    Relevance,
+    /// Most voted first
+    Popular,
+}
+
+/// A question matching a search.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct QuestionSearchResult {
+    pub question: Question,
+    /// How well the question matches, higher is better
+    pub rank: f64,
+    /// The question's text, HTML escaped, with the matching words wrapped
+    /// in `<mark>` tags
+    pub snippet: String,
+}
+
+/// A question in the home feed.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct FeedItem {
+    pub question: Question,
+    /// Whether the question is there because the user follows its author,
+    /// rather than for trending
+    pub from_followed_user: bool,
+    /// Pass as `after` for the items after this one
+    pub cursor: String,
 }
 
 #[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
 pub struct QuestionResponse {
     pub question: Option<Question>,
     pub errors: Option<Vec<FieldError>>,
+    /// Existing questions like the one being created, when it was turned
+    /// down as a possible duplicate
+    pub similar_questions: Option<Vec<Question>>,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+pub struct QuestionOptionsResponse {
+    pub options: Option<Vec<QuestionOption>>,
+    pub errors: Option<Vec<FieldError>>,
 }
 
 #[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
 pub struct QuestionsResponse {
     pub questions: Option<Vec<Question>>,
     pub errors: Option<Vec<FieldError>>,
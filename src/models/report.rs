@@ -0,0 +1,184 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sql_types::Text, AsExpression, FromSqlRow};
+use juniper::{GraphQLEnum, GraphQLObject};
+use uuid::Uuid;
+use votodroid_server_derive::{VotodroidDbEnum, VotodroidResponseObject};
+
+use crate::{context::Context, schema};
+
+use super::types::FieldError;
+
+/// A user's report of abusive content. Its GraphQL fields are resolved in
+/// `graphql::moderation_resolver`.
+#[derive(Clone, Queryable)]
+pub struct Report {
+    /// The report's id (UUID)
+    pub id: Uuid,
+    /// The user who filed the report
+    pub reporter_id: Uuid,
+    /// What kind of content is reported
+    pub target_type: ReportTargetType,
+    /// The reported question or comment
+    pub target_id: Uuid,
+    /// Why the content is reported
+    pub reason: ReportReason,
+    /// The reporter's explanation
+    pub note: Option<String>,
+    /// Where the report is in review
+    pub status: ReportStatus,
+    /// The date and time the report was filed
+    pub created_at: NaiveDateTime,
+    /// The date and time a moderator resolved the report, if one did
+    pub resolved_at: Option<NaiveDateTime>,
+    /// The moderator who resolved the report
+    pub resolved_by: Option<Uuid>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::reports)]
+pub struct ReportInput {
+    pub reporter_id: Uuid,
+    pub target_type: ReportTargetType,
+    pub target_id: Uuid,
+    pub reason: ReportReason,
+    pub note: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ReportTargetType {
+    Question,
+    Comment,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ReportReason {
+    /// Advertising or repeated posts
+    Spam,
+    /// Attacks on a person
+    Harassment,
+    /// Attacks on a group
+    HateSpeech,
+    /// Misleading on purpose
+    Misinformation,
+    /// Explained in the note
+    Other,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ReportStatus {
+    /// Waiting for a moderator
+    Pending,
+    /// The content was reviewed and kept
+    Approved,
+    /// The content was removed
+    Removed,
+    /// The report was found unfounded
+    Dismissed,
+}
+
+/// What a moderator decides about a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+pub enum ModerationDecision {
+    /// Keep the content, shown again if it was hidden, closing every report
+    /// on it
+    Approve,
+    /// Delete the content, closing every report on it
+    Remove,
+    /// Close this report only, as unfounded
+    Dismiss,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum ModerationActionKind {
+    /// Content hidden after enough reports
+    Hide,
+    /// Content reviewed and kept
+    Approve,
+    /// Content removed
+    Remove,
+    /// Report dismissed
+    Dismiss,
+}
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///An entry of the moderation audit trail
+pub struct ModerationAction {
+    /// The action's id (UUID)
+    pub id: Uuid,
+    /// The moderator who acted, none for automatic actions
+    pub moderator_id: Option<Uuid>,
+    /// What was done
+    pub action: ModerationActionKind,
+    /// What kind of content it was done to
+    pub target_type: ReportTargetType,
+    /// The question or comment it was done to
+    pub target_id: Uuid,
+    /// The report that led to it, if any
+    pub report_id: Option<Uuid>,
+    /// Why it was done
+    pub note: Option<String>,
+    /// The date and time it was done
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::moderation_actions)]
+pub struct ModerationActionInput {
+    pub moderator_id: Option<Uuid>,
+    pub action: ModerationActionKind,
+    pub target_type: ReportTargetType,
+    pub target_id: Uuid,
+    pub report_id: Option<Uuid>,
+    pub note: Option<String>,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
+pub struct ReportResponse {
+    pub report: Option<Report>,
+    pub errors: Option<Vec<FieldError>>,
+}
@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use juniper::GraphQLObject;
+use uuid::Uuid;
+use votodroid_server_derive::VotodroidResponseObject;
+
+use super::types::FieldError;
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///A tag grouping questions
+pub struct Tag {
+    /// The tag's id (UUID)
+    pub id: Uuid,
+    /// The tag's name, lower case words joined by dashes
+    pub name: String,
+    /// The date and time the tag was first used
+    pub created_at: NaiveDateTime,
+}
+
+/// A tag with the number of questions using it.
+#[derive(GraphQLObject)]
+pub struct TagUsage {
+    pub tag: Tag,
+    /// Questions open to voters that use the tag
+    pub questions: i32,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+pub struct TagResponse {
+    pub tag: Option<Tag>,
+    pub errors: Option<Vec<FieldError>>,
+}
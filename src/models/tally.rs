@@ -0,0 +1,44 @@
+use juniper::{GraphQLEnum, GraphQLObject};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+/// How ranked ballots are counted
+pub enum TallyMethod {
+    /// Eliminate the option with the fewest first choices until one has a
+    /// majority
+    InstantRunoff,
+    /// The Condorcet method using strongest paths between options
+    Schulze,
+    /// Score each option by how many options are ranked below it
+    Borda,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, GraphQLObject)]
+/// The outcome of counting a ranked question's ballots
+pub struct TallyResult {
+    pub method: TallyMethod,
+    /// The number of ballots counted
+    pub ballots: i32,
+    /// The winning option, unless there were no ballots
+    pub winner: Option<Uuid>,
+    /// The counting rounds, in order. Methods without rounds have one.
+    pub rounds: Vec<TallyRound>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, GraphQLObject)]
+pub struct TallyRound {
+    /// The round's number, from 1
+    pub round: i32,
+    /// The score of every option still counted
+    pub scores: Vec<OptionScore>,
+    /// The option eliminated at the end of the round, if any
+    pub eliminated: Option<Uuid>,
+    /// Ballots that rank none of the options still counted
+    pub exhausted: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, GraphQLObject)]
+pub struct OptionScore {
+    pub option_id: Uuid,
+    pub score: i32,
+}
@@ -0,0 +1,73 @@
+use chrono::NaiveDateTime;
+use diesel::Queryable;
+use juniper::{GraphQLEnum, GraphQLObject};
+use uuid::Uuid;
+use votodroid_server_derive::VotodroidResponseObject;
+
+use super::types::FieldError;
+
+#[derive(Clone, Copy, GraphQLEnum)]
+pub enum ChallengeKind {
+    TotpRequired,
+}
+
+#[derive(Clone, Queryable)]
+pub struct LoginChallengeRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub attempts: i32,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Clone, GraphQLObject)]
+///A second step required to complete a login
+pub struct LoginChallenge {
+    /// The challenge's id, to pass to `verifyTotp`
+    pub id: Uuid,
+    /// What the client must provide
+    pub kind: ChallengeKind,
+    /// The date and time after which the challenge can no longer be used
+    pub expires_at: NaiveDateTime,
+}
+
+impl From<LoginChallengeRow> for LoginChallenge {
+    fn from(row: LoginChallengeRow) -> Self {
+        Self {
+            id: row.id,
+            kind: ChallengeKind::TotpRequired,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Clone, Queryable)]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, GraphQLObject)]
+///What an authenticator app needs to generate codes
+pub struct TotpSetup {
+    /// The shared secret, base32 encoded
+    pub secret: String,
+    /// The `otpauth://` URI to encode in a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+pub struct TotpSetupResponse {
+    pub setup: Option<TotpSetup>,
+    pub errors: Option<Vec<FieldError>>,
+}
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+pub struct RecoveryCodesResponse {
+    /// One-time codes, only shown once
+    pub recovery_codes: Option<Vec<String>>,
+    pub errors: Option<Vec<FieldError>>,
+}
@@ -4,10 +4,24 @@ use juniper::GraphQLObject;
 pub struct FieldError {
     pub field: String,
     pub message: String,
+    /// A stable code for errors clients handle specially
+    pub code: Option<String>,
 }
 
 impl FieldError {
     pub fn new(field: String, message: String) -> Self {
-        Self { field, message }
+        Self {
+            field,
+            message,
+            code: None,
+        }
+    }
+
+    pub fn with_code(field: String, code: &str, message: String) -> Self {
+        Self {
+            field,
+            message,
+            code: Some(code.to_owned()),
+        }
     }
 }
@@ -4,12 +4,12 @@ use juniper::{GraphQLInputObject, GraphQLObject};
 use uuid::Uuid;
 use votodroid_server_derive::VotodroidResponseObject;
 
-use crate::schema;
+use crate::{context::Context, schema};
 
-use super::types::FieldError;
+use super::{totp::LoginChallenge, types::FieldError};
 
-#[derive(Clone, Queryable, GraphQLObject)]
-///A user
+/// A user. Its GraphQL fields are resolved in `graphql::user_resolver`.
+#[derive(Clone, Queryable)]
 pub struct User {
     /// The user's id (UUID)
     pub id: Uuid,
@@ -17,7 +17,6 @@ pub struct User {
     pub username: String,
     /// The user's email
     pub email: String,
-    #[graphql(skip)]
     pub password: String,
     /// The date and time the user was created
     pub created_at: NaiveDateTime,
@@ -25,6 +24,29 @@ pub struct User {
     pub updated_at: NaiveDateTime,
     /// The date and time the user last logged in
     pub last_login: Option<NaiveDateTime>,
+    /// The user's role (user, moderator or admin)
+    pub role: String,
+    pub totp_secret: Option<String>,
+    /// Whether the user has two-factor authentication enabled
+    pub totp_enabled: bool,
+    pub totp_last_step: Option<i64>,
+    /// The date and time the user deleted their account, if they did
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl User {
+    pub fn is_privileged(&self) -> bool {
+        self.role == "moderator" || self.role == "admin"
+    }
+
+    /// Moderators and admins must enable TOTP before using their privileges.
+    pub fn can_moderate(&self) -> bool {
+        self.is_privileged() && self.totp_enabled
+    }
+
+    pub fn can_administer(&self) -> bool {
+        self.role == "admin" && self.totp_enabled
+    }
 }
 
 #[derive(GraphQLInputObject, Insertable)]
@@ -39,7 +61,17 @@ pub struct RegisterUserInput {
 }
 
 #[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
 pub struct UserResponse {
     pub user: Option<User>,
     pub errors: Option<Vec<FieldError>>,
 }
+
+#[derive(GraphQLObject, VotodroidResponseObject)]
+#[graphql(context = Context)]
+pub struct LoginResponse {
+    pub user: Option<User>,
+    pub errors: Option<Vec<FieldError>>,
+    /// Set when a second factor is needed to complete the login
+    pub challenge: Option<LoginChallenge>,
+}
@@ -1,20 +1,20 @@
 use chrono::NaiveDateTime;
-use diesel::prelude::*;
-use juniper::GraphQLObject;
+use diesel::{prelude::*, sql_types::Text, AsExpression, FromSqlRow};
+use juniper::{GraphQLEnum, GraphQLInputObject, GraphQLObject};
 use uuid::Uuid;
-use votodroid_server_derive::VotodroidResponseObject;
+use votodroid_server_derive::{VotodroidDbEnum, VotodroidResponseObject};
 
 use crate::schema;
 
-use super::types::FieldError;
+use super::{question::QuestionType, tally::OptionScore, types::FieldError};
 
 #[derive(Clone, Queryable, GraphQLObject)]
 ///A vote
 pub struct Vote {
     /// The vote's id (UUID)
     pub id: Uuid,
-    /// The vote's value
-    pub value: i32,
+    /// The vote's value, unless it picks options
+    pub value: Option<i32>,
     /// The date and time the vote was created
     pub created_at: NaiveDateTime,
     /// The date and time the vote was last updated
@@ -29,8 +29,8 @@ pub struct Vote {
 #[derive(Insertable)]
 #[diesel(table_name = schema::votes)]
 pub struct VoteInput {
-    /// The vote's value
-    pub value: i32,
+    /// The vote's value, unless it picks options
+    pub value: Option<i32>,
     // The user who created the vote
     pub user_id: Uuid,
     /// The question for which the vote was created
@@ -41,4 +41,131 @@ pub struct VoteInput {
 pub struct VoteResponse {
     pub vote: Option<Vote>,
     pub errors: Option<Vec<FieldError>>,
-}
\ No newline at end of file
+}
+
+#[derive(GraphQLObject)]
+/// A question's results, shaped by its type
+pub struct QuestionStats {
+    pub question_type: QuestionType,
+    /// The number of votes
+    pub total: i32,
+    /// The average value, for yes/no, rating and numeric questions
+    pub average: Option<String>,
+    /// The leading option, for choice questions. Ranked questions use an
+    /// instant runoff.
+    pub winner: Option<Uuid>,
+    /// The number of votes for each value or option
+    pub buckets: Vec<StatBucket>,
+    /// The number of comments, replies included
+    pub comments: i32,
+}
+
+#[derive(GraphQLObject)]
+pub struct StatBucket {
+    pub label: String,
+    /// The value counted, for yes/no, rating and numeric questions
+    pub value: Option<i32>,
+    /// The option counted, for choice questions
+    pub option_id: Option<Uuid>,
+    /// The number of votes, or of first choices for ranked questions
+    pub count: i32,
+}
+
+#[derive(GraphQLInputObject)]
+/// Votes cast on an option of a quadratic question
+pub struct AllocationInput {
+    pub option_id: String,
+    pub votes: i32,
+}
+
+#[derive(GraphQLObject)]
+/// A voter's credits on a quadratic question
+pub struct Credits {
+    pub budget: i32,
+    pub spent: i32,
+    pub remaining: i32,
+}
+
+#[derive(Clone, Queryable, GraphQLObject)]
+///A vote being cast, changed or retracted
+pub struct VoteEvent {
+    /// The event's id (UUID)
+    pub id: Uuid,
+    /// The user who voted
+    #[graphql(skip)]
+    pub user_id: Uuid,
+    /// The question voted on
+    pub question_id: Uuid,
+    /// What happened to the vote
+    pub kind: VoteEventKind,
+    /// The vote's value afterwards, unless it picks options
+    pub value: Option<i32>,
+    /// The options the vote picks afterwards, in order
+    pub option_ids: Vec<Uuid>,
+    /// The votes cast on each of those options
+    pub weights: Vec<i32>,
+    /// The date and time it happened
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    GraphQLEnum,
+    AsExpression,
+    FromSqlRow,
+    VotodroidDbEnum,
+)]
+#[diesel(sql_type = Text)]
+pub enum VoteEventKind {
+    /// The vote was cast
+    Created,
+    /// The vote's answer was changed
+    Changed,
+    /// The vote was withdrawn
+    Retracted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+pub enum ShiftPeriod {
+    Hour,
+    Day,
+    Week,
+}
+
+#[derive(GraphQLObject)]
+/// Where a question's votes stood at the end of a period
+pub struct OpinionSnapshot {
+    /// When the period started
+    pub period_start: NaiveDateTime,
+    /// The number of votes standing
+    pub total: i32,
+    /// The average value of the votes standing
+    pub average: Option<String>,
+    /// The votes standing for each option, for choice questions
+    pub options: Vec<OptionScore>,
+    /// Votes cast during the period
+    pub created: i32,
+    /// Votes changed during the period
+    pub changed: i32,
+    /// Votes retracted during the period
+    pub retracted: i32,
+}
+
+#[derive(GraphQLObject)]
+/// Who voted what, on questions whose voters are visible
+pub struct Voter {
+    pub user_id: Uuid,
+    pub username: String,
+    /// The vote's value, unless it picks options
+    pub value: Option<i32>,
+    /// The options the vote picks, in order
+    pub option_ids: Vec<Uuid>,
+    /// The votes cast on each of those options
+    pub weights: Vec<i32>,
+    /// The date and time the vote was last changed
+    pub voted_at: NaiveDateTime,
+}
@@ -0,0 +1,647 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use actix_session::Session;
+use actix_web::{
+    error::{ErrorBadGateway, ErrorBadRequest, ErrorNotFound},
+    http::header,
+    web, Error, HttpResponse,
+};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    config::{config, OAuthProvider},
+    database::get_pool,
+    models::{identity::IdentityInput, user::RegisterUserInput, user::User},
+    services,
+};
+
+static DISCOVERY: Lazy<Mutex<HashMap<String, Endpoints>>> =
+    Lazy::new(Default::default);
+
+#[derive(Clone)]
+struct Endpoints {
+    authorization: String,
+    token: String,
+    userinfo: String,
+}
+
+/// What must survive the round trip to the provider, kept in the session.
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    provider: String,
+    state: String,
+    verifier: String,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// The account the provider vouched for.
+struct ExternalUser {
+    subject: String,
+    email: Option<String>,
+    email_verified: bool,
+    username: Option<String>,
+}
+
+/// `GET /auth/{provider}`: redirects to the provider's login page using the
+/// authorization code flow with PKCE.
+pub async fn oauth_login_route(
+    provider_name: web::Path<String>,
+    session: Session,
+) -> Result<HttpResponse, Error> {
+    let provider_name = provider_name.into_inner();
+    let provider = get_provider(&provider_name)?;
+    let endpoints = get_endpoints(provider).await?;
+
+    let pending = PendingLogin {
+        provider: provider_name,
+        state: random_string(32),
+        verifier: random_string(64),
+    };
+    let challenge = base64::encode_config(
+        Sha256::digest(pending.verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    );
+
+    let url = format!(
+        "{}?{}",
+        endpoints.authorization,
+        serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", provider.redirect_url.as_str()),
+            ("scope", provider.scopes.as_str()),
+            ("state", pending.state.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(ErrorBadRequest)?
+    );
+
+    session.insert("oauthPending", pending)?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .finish())
+}
+
+/// `GET /auth/{provider}/callback`: exchanges the code, then logs in the
+/// linked user, links the provider to the logged in or same-email user, or
+/// registers a new user.
+pub async fn oauth_callback_route(
+    provider_name: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    session: Session,
+) -> Result<HttpResponse, Error> {
+    let provider_name = provider_name.into_inner();
+    let provider = get_provider(&provider_name)?;
+    let pending = session.remove_as::<PendingLogin>("oauthPending");
+
+    if let Some(error) = &query.error {
+        return Err(ErrorBadRequest(format!("Provider error: {}", error)));
+    }
+
+    let pending = match pending {
+        Some(Ok(pending))
+            if pending.provider == provider_name
+                && Some(&pending.state) == query.state.as_ref() =>
+        {
+            pending
+        }
+        _ => return Err(ErrorBadRequest("Invalid or expired login state.")),
+    };
+    let code = query
+        .code
+        .as_ref()
+        .ok_or_else(|| ErrorBadRequest("Missing authorization code."))?;
+
+    let endpoints = get_endpoints(provider).await?;
+    let external =
+        fetch_external_user(provider, &endpoints, code, &pending.verifier)
+            .await?;
+
+    let mut conn = get_pool()
+        .get()
+        .expect("Failed to get connection to database.");
+    let current_user = session.get::<Uuid>("userId")?;
+    let user = resolve_user(
+        &mut conn,
+        &provider_name,
+        provider,
+        external,
+        current_user,
+    )
+    .map_err(ErrorBadRequest)?;
+
+    let redirect = &config().oauth.redirect;
+    let separator = if redirect.contains('?') { '&' } else { '?' };
+
+    if user.totp_enabled && current_user != Some(user.id) {
+        let challenge = services::totp::create_challenge(&mut conn, user.id)
+            .map_err(ErrorBadGateway)?;
+
+        return Ok(HttpResponse::Found()
+            .insert_header((
+                header::LOCATION,
+                format!(
+                    "{}{}challengeId={}",
+                    redirect, separator, challenge.id
+                ),
+            ))
+            .finish());
+    }
+
+    services::user::update_last_login(&mut conn, user.id)
+        .map_err(ErrorBadGateway)?;
+    session.insert("userId", user.id)?;
+    // Stands in for a password check for accounts without a password.
+    session.insert("oauthLoginAt", Utc::now().timestamp())?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, redirect.as_str()))
+        .finish())
+}
+
+fn resolve_user(
+    conn: &mut diesel::PgConnection,
+    provider_name: &str,
+    provider: &OAuthProvider,
+    external: ExternalUser,
+    current_user: Option<Uuid>,
+) -> Result<User, String> {
+    if let Ok(identity) = services::identity::get_by_provider_and_subject(
+        conn,
+        provider_name,
+        &external.subject,
+    ) {
+        if current_user.map_or(false, |id| id != identity.user_id) {
+            return Err(
+                "This account is already linked to another user.".to_owned()
+            );
+        }
+        return services::user::get_by_id(conn, identity.user_id)
+            .map_err(|e| e.to_string());
+    }
+
+    let trusted_email = external
+        .email
+        .as_ref()
+        .filter(|_| external.email_verified || provider.trust_email);
+
+    let user = match (current_user, trusted_email) {
+        (Some(user_id), _) => services::user::get_by_id(conn, user_id),
+        (None, Some(email)) => services::user::get_by_email(conn, email)
+            .or_else(|_| register(conn, &external, email)),
+        (None, None) => {
+            return Err(
+                "The provider did not return a verified email.".to_owned()
+            )
+        }
+    }
+    .map_err(|e| e.to_string())?;
+
+    services::identity::create(
+        conn,
+        IdentityInput {
+            user_id: user.id,
+            provider: provider_name.to_owned(),
+            subject: external.subject,
+            email: external.email,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(user)
+}
+
+fn register(
+    conn: &mut diesel::PgConnection,
+    external: &ExternalUser,
+    email: &str,
+) -> diesel::QueryResult<User> {
+    let base: String = external
+        .username
+        .as_deref()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default())
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .take(14)
+        .collect();
+    let base = if base.chars().count() < 3 {
+        format!("user{}", base)
+    } else {
+        base
+    };
+
+    let mut username = base.clone();
+    while services::user::get_by_username(conn, &username).is_ok() {
+        username =
+            format!("{}{}", base, rand::thread_rng().gen_range(0..1_000_000));
+    }
+
+    services::user::create_user(
+        conn,
+        RegisterUserInput {
+            username,
+            email: email.to_owned(),
+            // The account logs in through the provider until the user sets
+            // a password, and keeps at least one provider linked until then.
+            password: services::password::UNUSABLE.to_owned(),
+        },
+    )
+}
+
+async fn fetch_external_user(
+    provider: &OAuthProvider,
+    endpoints: &Endpoints,
+    code: &str,
+    verifier: &str,
+) -> Result<ExternalUser, Error> {
+    let client = awc::Client::default();
+
+    let token = client
+        .post(&endpoints.token)
+        .insert_header((header::ACCEPT, "application/json"))
+        .send_form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_url.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("client_secret", provider.client_secret.as_str()),
+            ("code_verifier", verifier),
+        ])
+        .await
+        .map_err(ErrorBadGateway)?
+        .json::<TokenResponse>()
+        .await
+        .map_err(ErrorBadGateway)?;
+
+    let info = client
+        .get(&endpoints.userinfo)
+        .insert_header((header::ACCEPT, "application/json"))
+        .insert_header((header::USER_AGENT, "votodroid-server"))
+        .bearer_auth(&token.access_token)
+        .send()
+        .await
+        .map_err(ErrorBadGateway)?
+        .json::<Value>()
+        .await
+        .map_err(ErrorBadGateway)?;
+
+    // OpenID Connect returns `sub`; plain OAuth2 providers like GitHub
+    // return a numeric `id` instead.
+    let subject = match (&info["sub"], &info["id"]) {
+        (Value::String(sub), _) => sub.clone(),
+        (_, Value::Number(id)) => id.to_string(),
+        (_, Value::String(id)) => id.clone(),
+        _ => return Err(ErrorBadGateway("Provider did not return a subject.")),
+    };
+    let text = |key: &str| info[key].as_str().map(str::to_owned);
+
+    Ok(ExternalUser {
+        subject,
+        email: text("email").map(|e| e.to_lowercase()),
+        email_verified: info["email_verified"].as_bool().unwrap_or(false),
+        username: text("preferred_username")
+            .or_else(|| text("login"))
+            .or_else(|| text("name")),
+    })
+}
+
+fn get_provider(name: &str) -> Result<&'static OAuthProvider, Error> {
+    config()
+        .oauth
+        .providers
+        .get(name)
+        .ok_or_else(|| ErrorNotFound("Unknown login provider."))
+}
+
+async fn get_endpoints(provider: &OAuthProvider) -> Result<Endpoints, Error> {
+    if let (Some(authorization), Some(token), Some(userinfo)) = (
+        &provider.authorization_url,
+        &provider.token_url,
+        &provider.userinfo_url,
+    ) {
+        return Ok(Endpoints {
+            authorization: authorization.clone(),
+            token: token.clone(),
+            userinfo: userinfo.clone(),
+        });
+    }
+
+    let issuer = provider.issuer.as_ref().ok_or_else(|| {
+        ErrorBadRequest("Provider needs an issuer or explicit endpoints.")
+    })?;
+
+    if let Some(endpoints) = DISCOVERY.lock().unwrap().get(issuer) {
+        return Ok(endpoints.clone());
+    }
+
+    let document = awc::Client::default()
+        .get(format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        ))
+        .send()
+        .await
+        .map_err(ErrorBadGateway)?
+        .json::<Value>()
+        .await
+        .map_err(ErrorBadGateway)?;
+    let endpoint = |configured: &Option<String>, key: &str| {
+        configured
+            .clone()
+            .or_else(|| document[key].as_str().map(str::to_owned))
+            .ok_or_else(|| ErrorBadGateway(format!("Provider has no {}.", key)))
+    };
+
+    let endpoints = Endpoints {
+        authorization: endpoint(
+            &provider.authorization_url,
+            "authorization_endpoint",
+        )?,
+        token: endpoint(&provider.token_url, "token_endpoint")?,
+        userinfo: endpoint(&provider.userinfo_url, "userinfo_endpoint")?,
+    };
+    DISCOVERY
+        .lock()
+        .unwrap()
+        .insert(issuer.clone(), endpoints.clone());
+
+    Ok(endpoints)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{dev::ServerHandle, App, HttpRequest, HttpServer};
+    use serde_json::json;
+
+    use super::*;
+    use crate::database::{create_test_user, with_test_transaction};
+
+    /// Starts a local OpenID Connect provider and returns its issuer. It
+    /// trades code `good` for an OpenID user and `github` for a GitHub-like
+    /// one, and refuses any other.
+    fn mock_provider() -> (String, ServerHandle) {
+        let server = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    web::get().to(discovery),
+                )
+                .route("/token", web::post().to(token))
+                .route("/userinfo", web::get().to(userinfo))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let issuer = format!("http://{}", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+
+        actix_web::rt::spawn(server);
+        (issuer, handle)
+    }
+
+    async fn discovery(req: HttpRequest) -> HttpResponse {
+        let issuer = format!("http://{}", req.connection_info().host());
+
+        HttpResponse::Ok().json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "userinfo_endpoint": format!("{}/userinfo", issuer),
+        }))
+    }
+
+    async fn token(form: web::Form<HashMap<String, String>>) -> HttpResponse {
+        let field = |key: &str| form.get(key).map(String::as_str);
+
+        if field("grant_type") != Some("authorization_code")
+            || field("client_secret") != Some("secret")
+            || field("code_verifier").is_none()
+        {
+            return HttpResponse::BadRequest()
+                .json(json!({ "error": "invalid_request" }));
+        }
+
+        match field("code") {
+            Some(code @ ("good" | "github")) => {
+                HttpResponse::Ok().json(json!({
+                    "access_token": format!("token-{}", code),
+                    "token_type": "Bearer",
+                }))
+            }
+            _ => HttpResponse::BadRequest()
+                .json(json!({ "error": "invalid_grant" })),
+        }
+    }
+
+    async fn userinfo(req: HttpRequest) -> HttpResponse {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+
+        match authorization {
+            Some("Bearer token-good") => HttpResponse::Ok().json(json!({
+                "sub": "mock-1",
+                "email": "Ada@Example.com",
+                "email_verified": true,
+                "preferred_username": "ada",
+            })),
+            Some("Bearer token-github") => HttpResponse::Ok().json(json!({
+                "id": 42,
+                "login": "octocat",
+                "email": null,
+            })),
+            _ => HttpResponse::Unauthorized()
+                .json(json!({ "error": "invalid_token" })),
+        }
+    }
+
+    fn provider(issuer: &str) -> OAuthProvider {
+        OAuthProvider {
+            client_id: "votodroid".to_owned(),
+            client_secret: "secret".to_owned(),
+            redirect_url: "http://localhost/auth/mock/callback".to_owned(),
+            issuer: Some(issuer.to_owned()),
+            authorization_url: None,
+            token_url: None,
+            userinfo_url: None,
+            scopes: "openid email profile".to_owned(),
+            trust_email: false,
+        }
+    }
+
+    fn external(
+        subject: &str,
+        email: &str,
+        email_verified: bool,
+    ) -> ExternalUser {
+        ExternalUser {
+            subject: subject.to_owned(),
+            email: Some(email.to_owned()),
+            email_verified,
+            username: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn discovers_endpoints() {
+        let (issuer, server) = mock_provider();
+
+        let endpoints = get_endpoints(&provider(&issuer)).await.unwrap();
+
+        assert_eq!(endpoints.authorization, format!("{}/authorize", issuer));
+        assert_eq!(endpoints.token, format!("{}/token", issuer));
+        assert_eq!(endpoints.userinfo, format!("{}/userinfo", issuer));
+        server.stop(true).await;
+    }
+
+    #[actix_web::test]
+    async fn fetches_openid_users() {
+        let (issuer, server) = mock_provider();
+        let provider = provider(&issuer);
+        let endpoints = get_endpoints(&provider).await.unwrap();
+
+        let user =
+            fetch_external_user(&provider, &endpoints, "good", "verifier")
+                .await
+                .unwrap();
+
+        assert_eq!(user.subject, "mock-1");
+        assert_eq!(user.email.as_deref(), Some("ada@example.com"));
+        assert!(user.email_verified);
+        assert_eq!(user.username.as_deref(), Some("ada"));
+        server.stop(true).await;
+    }
+
+    #[actix_web::test]
+    async fn fetches_github_like_users() {
+        let (issuer, server) = mock_provider();
+        let provider = provider(&issuer);
+        let endpoints = get_endpoints(&provider).await.unwrap();
+
+        let user =
+            fetch_external_user(&provider, &endpoints, "github", "verifier")
+                .await
+                .unwrap();
+
+        assert_eq!(user.subject, "42");
+        assert_eq!(user.email, None);
+        assert!(!user.email_verified);
+        assert_eq!(user.username.as_deref(), Some("octocat"));
+        server.stop(true).await;
+    }
+
+    #[actix_web::test]
+    async fn refuses_unknown_codes() {
+        let (issuer, server) = mock_provider();
+        let provider = provider(&issuer);
+        let endpoints = get_endpoints(&provider).await.unwrap();
+
+        assert!(fetch_external_user(
+            &provider, &endpoints, "stolen", "verifier"
+        )
+        .await
+        .is_err());
+        server.stop(true).await;
+    }
+
+    #[test]
+    fn registers_users_without_a_usable_password() {
+        let provider = provider("http://127.0.0.1");
+
+        with_test_transaction(|conn| {
+            let subject = Uuid::new_v4().to_string();
+            let email = format!("{}@example.com", subject);
+
+            let user = resolve_user(
+                conn,
+                "mock",
+                &provider,
+                external(&subject, &email, true),
+                None,
+            )
+            .unwrap();
+            assert_eq!(user.password, services::password::UNUSABLE);
+            assert!(!services::password::verify(&user.password, ""));
+
+            // Logging in again finds the user by the linked identity.
+            let again = resolve_user(
+                conn,
+                "mock",
+                &provider,
+                external(&subject, &email, false),
+                None,
+            )
+            .unwrap();
+            assert_eq!(again.id, user.id);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn refuses_unverified_emails_and_other_users_identities() {
+        let provider = provider("http://127.0.0.1");
+
+        with_test_transaction(|conn| {
+            let subject = Uuid::new_v4().to_string();
+            let email = format!("{}@example.com", subject);
+
+            assert!(resolve_user(
+                conn,
+                "mock",
+                &provider,
+                external(&subject, &email, false),
+                None,
+            )
+            .is_err());
+
+            let owner = create_test_user(conn);
+            let other = create_test_user(conn);
+            resolve_user(
+                conn,
+                "mock",
+                &provider,
+                external(&subject, &email, false),
+                Some(owner),
+            )
+            .unwrap();
+            assert!(resolve_user(
+                conn,
+                "mock",
+                &provider,
+                external(&subject, &email, false),
+                Some(other),
+            )
+            .is_err());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,992 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::{ready, Future, Ready},
+    iter::Peekable,
+    net::IpAddr,
+    pin::Pin,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+    vec,
+};
+
+use actix_session::SessionExt;
+use actix_web::{
+    body::EitherBody,
+    dev::{
+        self, forward_ready, Service, ServiceRequest, ServiceResponse,
+        Transform,
+    },
+    http::header::{self, HeaderValue},
+    web, Error, HttpResponse,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{config, RateLimit};
+
+const BUCKET_SCRIPT: &str = r"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate))
+return wait
+";
+
+/// Where token buckets are stored.
+pub trait RateLimitBackend: Send + Sync {
+    /// Takes one token from the bucket at `key`, or returns how long to wait
+    /// before one is available.
+    fn acquire(&self, key: &str, limit: &RateLimit) -> Result<(), Duration>;
+}
+
+/// Buckets kept in process memory. Only suitable for a single instance and
+/// for tests.
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    buckets: HashMap<String, Bucket>,
+    last_sweep: u128,
+}
+
+struct Bucket {
+    tokens: f64,
+    ts: u128,
+    /// When the bucket is full again, and so can be forgotten.
+    idle_at: u128,
+}
+
+/// How often idle buckets are evicted, in milliseconds.
+const SWEEP_INTERVAL_MS: u128 = 60_000;
+
+impl MemoryBackend {
+    fn acquire_at(
+        &self,
+        key: &str,
+        limit: &RateLimit,
+        now: u128,
+    ) -> Result<(), Duration> {
+        let rate = limit.refill_per_ms();
+        let capacity = limit.capacity as f64;
+        let mut state = self.state.lock().unwrap();
+
+        // A full bucket is the same as a missing one, so buckets idle for
+        // their whole period are dropped rather than kept forever.
+        if now.saturating_sub(state.last_sweep) >= SWEEP_INTERVAL_MS {
+            state.buckets.retain(|_, bucket| bucket.idle_at > now);
+            state.last_sweep = now;
+        }
+
+        let bucket = state.buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            ts: now,
+            idle_at: now,
+        });
+
+        bucket.tokens = capacity
+            .min(bucket.tokens + now.saturating_sub(bucket.ts) as f64 * rate);
+        bucket.ts = now;
+
+        let result = if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_millis(
+                ((1.0 - bucket.tokens) / rate).ceil() as u64
+            ))
+        };
+        bucket.idle_at =
+            now + ((capacity - bucket.tokens) / rate).ceil() as u128;
+
+        result
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+}
+
+impl RateLimitBackend for MemoryBackend {
+    fn acquire(&self, key: &str, limit: &RateLimit) -> Result<(), Duration> {
+        self.acquire_at(key, limit, now_ms())
+    }
+}
+
+/// Buckets shared by every instance through Redis.
+pub struct RedisBackend {
+    pool: r2d2::Pool<redis::Client>,
+    script: redis::Script,
+}
+
+impl RedisBackend {
+    pub fn new(url: &str) -> Self {
+        let client = redis::Client::open(url).expect("Invalid Redis URL.");
+        Self {
+            pool: r2d2::Pool::builder()
+                .build(client)
+                .expect("could not build Redis connection pool"),
+            script: redis::Script::new(BUCKET_SCRIPT),
+        }
+    }
+}
+
+impl RateLimitBackend for RedisBackend {
+    fn acquire(&self, key: &str, limit: &RateLimit) -> Result<(), Duration> {
+        let conn = self.pool.get();
+
+        // Fail open: an unreachable Redis should not take the API down.
+        let wait = match conn {
+            Ok(mut conn) => match self
+                .script
+                .key(key)
+                .arg(limit.capacity)
+                .arg(limit.refill_per_ms())
+                .arg(now_ms() as u64)
+                .invoke::<u64>(&mut *conn)
+            {
+                Ok(wait) => wait,
+                Err(e) => {
+                    log::error!("Rate limit check failed, allowing: {}", e);
+                    0
+                }
+            },
+            Err(e) => {
+                log::error!("Rate limit Redis unavailable, allowing: {}", e);
+                0
+            }
+        };
+
+        match wait {
+            0 => Ok(()),
+            wait => Err(Duration::from_millis(wait)),
+        }
+    }
+}
+
+/// Applies the global limit and the per-operation limits to a client.
+pub struct RateLimiter {
+    backend: Arc<dyn RateLimitBackend>,
+    global: Option<RateLimit>,
+    operations: HashMap<String, RateLimit>,
+    api_tokens: HashSet<String>,
+    trusted_proxies: HashSet<IpAddr>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        backend: Arc<dyn RateLimitBackend>,
+        global: Option<RateLimit>,
+        operations: HashMap<String, RateLimit>,
+        api_tokens: HashSet<String>,
+        trusted_proxies: HashSet<IpAddr>,
+    ) -> Self {
+        Self {
+            backend,
+            global,
+            operations,
+            api_tokens,
+            trusted_proxies,
+        }
+    }
+
+    pub fn from_config() -> Self {
+        let config = &config().rate_limit;
+        let backend: Arc<dyn RateLimitBackend> = match config.backend.as_str() {
+            "memory" => Arc::new(MemoryBackend::default()),
+            _ => Arc::new(RedisBackend::new(&config.redis_url)),
+        };
+
+        Self::new(
+            backend,
+            config.global,
+            config.operations.clone(),
+            config.api_tokens.clone(),
+            config.trusted_proxies.clone(),
+        )
+    }
+
+    /// Checks every limit that applies to `client` running `operations`
+    /// (`questions.create`, ...), returning the longest wait if any is
+    /// exhausted. `votes.*` stands for every limited operation of `votes`,
+    /// and `*` for every limited operation.
+    pub fn check(
+        &self,
+        client: &str,
+        operations: &[String],
+    ) -> Result<(), Duration> {
+        let mut retry_after = None;
+
+        if let Some(global) = &self.global {
+            if let Err(wait) = self
+                .backend
+                .acquire(&format!("ratelimit:*:{}", client), global)
+            {
+                retry_after = Some(wait);
+            }
+        }
+
+        match retry_after.max(self.check_operations(client, operations).err()) {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    /// `check` without the global limit.
+    fn check_operations(
+        &self,
+        client: &str,
+        operations: &[String],
+    ) -> Result<(), Duration> {
+        let mut retry_after = None;
+
+        for operation in operations {
+            let limits: Vec<_> = match operation.strip_suffix('*') {
+                Some(prefix) => self
+                    .operations
+                    .iter()
+                    .filter(|(operation, _)| operation.starts_with(prefix))
+                    .collect(),
+                None => self
+                    .operations
+                    .get_key_value(operation)
+                    .into_iter()
+                    .collect(),
+            };
+
+            for (operation, limit) in limits {
+                let key = format!("ratelimit:{}:{}", operation, client);
+                if let Err(wait) = self.backend.acquire(&key, limit) {
+                    retry_after = retry_after.max(Some(wait));
+                }
+            }
+        }
+
+        match retry_after {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    /// The bucket key of the request: the logged in user, else a known API
+    /// token, else the client IP. Unknown tokens are ignored so clients
+    /// cannot dodge their IP's limit by making tokens up.
+    fn client_key(&self, req: &ServiceRequest) -> String {
+        if let Ok(Some(user_id)) = req.get_session().get::<Uuid>("userId") {
+            return format!("user:{}", user_id);
+        }
+
+        let token = req
+            .headers()
+            .get("X-Api-Token")
+            .or_else(|| req.headers().get(header::AUTHORIZATION))
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_start_matches("Bearer ").trim())
+            .filter(|token| self.api_tokens.contains(*token));
+        if let Some(token) = token {
+            // Tokens are secrets, so they are not stored in keys as is.
+            return format!("token:{:x}", Sha256::digest(token.as_bytes()));
+        }
+
+        let forwarded_for = req
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok());
+        match client_ip(
+            req.peer_addr().map(|addr| addr.ip()),
+            forwarded_for,
+            &self.trusted_proxies,
+        ) {
+            Some(ip) => format!("ip:{}", ip),
+            None => "ip:unknown".to_owned(),
+        }
+    }
+}
+
+/// The IP a request came from. `X-Forwarded-For` is only believed when sent
+/// by a trusted proxy, and is read from the right, each proxy appending the
+/// address it got the request from, up to the first untrusted address.
+/// Anything further left was written by the client and could be made up.
+fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &HashSet<IpAddr>,
+) -> Option<IpAddr> {
+    let mut ip = peer?;
+    if !trusted_proxies.contains(&ip) {
+        return Some(ip);
+    }
+
+    for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+        match hop.trim().parse() {
+            Ok(hop) => {
+                ip = hop;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    Some(ip)
+}
+
+/// Middleware rate limiting GraphQL requests, keyed by the logged in user,
+/// then the API token, then the client IP.
+#[derive(Clone)]
+pub struct RateLimitMiddleware {
+    limiter: Arc<RateLimiter>,
+    operation: Option<&'static str>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+            operation: None,
+        }
+    }
+
+    /// Limits every request as `operation` (e.g. `uploads.create`) instead
+    /// of reading GraphQL operations from the body, and without the global
+    /// limit.
+    pub fn for_operation(
+        limiter: Arc<RateLimiter>,
+        operation: &'static str,
+    ) -> Self {
+        Self {
+            limiter,
+            operation: Some(operation),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+        + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitService {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+            operation: self.operation,
+        }))
+    }
+}
+
+pub struct RateLimitService<S> {
+    service: Rc<S>,
+    limiter: Arc<RateLimiter>,
+    operation: Option<&'static str>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+        + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiter = self.limiter.clone();
+        let operation = self.operation;
+
+        Box::pin(async move {
+            let checked = match operation {
+                Some(operation) => limiter.check_operations(
+                    &limiter.client_key(&req),
+                    &[operation.to_owned()],
+                ),
+                None => {
+                    let body = req.extract::<web::Bytes>().await?;
+                    let operations = if body.is_empty() {
+                        web::Query::<HashMap<String, String>>::from_query(
+                            req.query_string(),
+                        )
+                        .ok()
+                        .and_then(|q| {
+                            q.get("query").map(|q| mutation_fields(q))
+                        })
+                        .unwrap_or_default()
+                    } else {
+                        body_operations(&body)
+                    };
+                    req.set_payload(bytes_to_payload(body));
+
+                    limiter.check(&limiter.client_key(&req), &operations)
+                }
+            };
+
+            if let Err(wait) = checked {
+                let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                let response = HttpResponse::TooManyRequests()
+                    .insert_header((
+                        header::RETRY_AFTER,
+                        HeaderValue::from(secs),
+                    ))
+                    .json(serde_json::json!({
+                        "errors": [{
+                            "message": format!(
+                                "Too many requests. Retry in {} seconds.",
+                                secs
+                            )
+                        }]
+                    }));
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+fn bytes_to_payload(buf: web::Bytes) -> dev::Payload {
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(buf);
+    dev::Payload::from(payload)
+}
+
+fn body_operations(body: &[u8]) -> Vec<String> {
+    let requests = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Array(requests)) => requests,
+        Ok(request) => vec![request],
+        Err(_) => return vec![],
+    };
+
+    requests
+        .iter()
+        .filter_map(|r| r.get("query").and_then(|q| q.as_str()))
+        .flat_map(mutation_fields)
+        .collect()
+}
+
+/// Lists the `parent.field` pairs selected by the mutations in a GraphQL
+/// document, e.g. `votes.create`, following fragments. A spread of a fragment
+/// the document does not define gives `parent.*`, or `*` directly under the
+/// mutation, as any field could be behind it.
+fn mutation_fields(query: &str) -> Vec<String> {
+    let mut parser = Parser {
+        tokens: tokenize(query).into_iter().peekable(),
+    };
+    let mut mutations = vec![];
+    let mut fragments = HashMap::new();
+
+    while let Some(token) = parser.next() {
+        match token {
+            Token::Punct('{') => {
+                parser.selection_set();
+            }
+            Token::Name(keyword) => {
+                let name = parser.name();
+                if keyword == "fragment" {
+                    // `on Type`
+                    parser.name();
+                    parser.name();
+                }
+                parser.directives();
+                if !parser.eat('{') {
+                    continue;
+                }
+                let selections = parser.selection_set();
+                match keyword.as_str() {
+                    "mutation" => mutations.push(selections),
+                    "fragment" => {
+                        if let Some(name) = name {
+                            fragments.insert(name, selections);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut fields = vec![];
+    for mutation in &mutations {
+        let mut parents = vec![];
+        if !flatten(mutation, &fragments, &mut vec![], &mut parents) {
+            fields.push("*".to_owned());
+        }
+        for (parent, children) in parents {
+            let mut operations = vec![];
+            if !flatten(children, &fragments, &mut vec![], &mut operations) {
+                fields.push(format!("{}.*", parent));
+            }
+            for (operation, _) in operations {
+                fields.push(format!("{}.{}", parent, operation));
+            }
+        }
+    }
+
+    fields
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Punct(char),
+    Spread,
+}
+
+enum Selection {
+    Field(String, Vec<Selection>),
+    Spread(String),
+    Inline(Vec<Selection>),
+}
+
+/// Splits a document into names and the punctuation that gives it its
+/// shape. Strings, comments and arguments are dropped, as only the
+/// selections matter here.
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    let mut parens = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '"' => {
+                if chars.next_if_eq(&'"').is_some() {
+                    if chars.next_if_eq(&'"').is_some() {
+                        // Block string, only ended by `"""`.
+                        let mut quotes = 0;
+                        for c in chars.by_ref() {
+                            match c {
+                                '"' if quotes == 2 => break,
+                                '"' => quotes += 1,
+                                _ => quotes = 0,
+                            }
+                        }
+                    }
+                    continue;
+                }
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '(' => parens += 1,
+            ')' => parens -= 1,
+            _ if parens > 0 => {}
+            '.' if chars.next_if_eq(&'.').is_some()
+                && chars.next_if_eq(&'.').is_some() =>
+            {
+                tokens.push(Token::Spread)
+            }
+            '{' | '}' | ':' | '@' => tokens.push(Token::Punct(c)),
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|&c| c.is_alphanumeric() || c == '_')
+                {
+                    name.push(c);
+                }
+                tokens.push(Token::Name(name));
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+struct Parser {
+    tokens: Peekable<vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        self.tokens.next()
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek()
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        self.tokens.next_if_eq(&Token::Punct(punct)).is_some()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        match self.tokens.next_if(|t| matches!(t, Token::Name(_))) {
+            Some(Token::Name(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn directives(&mut self) {
+        while self.eat('@') {
+            self.name();
+        }
+    }
+
+    /// Reads selections up to the `}` closing the set just opened.
+    fn selection_set(&mut self) -> Vec<Selection> {
+        let mut selections = vec![];
+
+        while let Some(token) = self.next() {
+            match token {
+                Token::Punct('}') => break,
+                Token::Spread => {
+                    let is_fragment = matches!(
+                        self.peek(),
+                        Some(Token::Name(name)) if name != "on"
+                    );
+                    if is_fragment {
+                        if let Some(name) = self.name() {
+                            self.directives();
+                            selections.push(Selection::Spread(name));
+                        }
+                    } else {
+                        if self.name().is_some() {
+                            // `on Type`
+                            self.name();
+                        }
+                        self.directives();
+                        if self.eat('{') {
+                            selections
+                                .push(Selection::Inline(self.selection_set()));
+                        }
+                    }
+                }
+                Token::Name(mut name) => {
+                    if self.eat(':') {
+                        name = self.name().unwrap_or_default();
+                    }
+                    self.directives();
+                    let children = if self.eat('{') {
+                        self.selection_set()
+                    } else {
+                        vec![]
+                    };
+                    selections.push(Selection::Field(name, children));
+                }
+                _ => {}
+            }
+        }
+
+        selections
+    }
+}
+
+/// Collects the fields of `selections`, looking into fragments, and returns
+/// whether every spread fragment was found. A fragment already being
+/// expanded is skipped so that cycles end.
+fn flatten<'a>(
+    selections: &'a [Selection],
+    fragments: &'a HashMap<String, Vec<Selection>>,
+    expanding: &mut Vec<&'a str>,
+    fields: &mut Vec<(&'a str, &'a [Selection])>,
+) -> bool {
+    let mut resolved = true;
+
+    for selection in selections {
+        match selection {
+            Selection::Field(name, children) => {
+                fields.push((name, children));
+            }
+            Selection::Inline(selections) => {
+                resolved &= flatten(selections, fragments, expanding, fields);
+            }
+            Selection::Spread(name) => {
+                if expanding.contains(&name.as_str()) {
+                    continue;
+                }
+                match fragments.get(name) {
+                    Some(selections) => {
+                        expanding.push(name);
+                        resolved &=
+                            flatten(selections, fragments, expanding, fields);
+                        expanding.pop();
+                    }
+                    None => resolved = false,
+                }
+            }
+        }
+    }
+
+    resolved
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_backend_limits_to_capacity() {
+        let backend = MemoryBackend::default();
+        let limit = RateLimit::new(2, 60);
+
+        assert!(backend.acquire_at("a", &limit, 0).is_ok());
+        assert!(backend.acquire_at("a", &limit, 0).is_ok());
+        assert_eq!(
+            backend.acquire_at("a", &limit, 0),
+            Err(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn memory_backend_refills_over_time() {
+        let backend = MemoryBackend::default();
+        let limit = RateLimit::new(2, 60);
+
+        backend.acquire_at("a", &limit, 0).unwrap();
+        backend.acquire_at("a", &limit, 0).unwrap();
+
+        assert!(backend.acquire_at("a", &limit, 29_999).is_err());
+        assert!(backend.acquire_at("a", &limit, 60_000).is_ok());
+    }
+
+    #[test]
+    fn memory_backend_keeps_keys_apart() {
+        let backend = MemoryBackend::default();
+        let limit = RateLimit::new(1, 60);
+
+        assert!(backend.acquire_at("a", &limit, 0).is_ok());
+        assert!(backend.acquire_at("a", &limit, 0).is_err());
+        assert!(backend.acquire_at("b", &limit, 0).is_ok());
+    }
+
+    #[test]
+    fn memory_backend_evicts_idle_buckets() {
+        let backend = MemoryBackend::default();
+        let limit = RateLimit::new(2, 60);
+
+        for key in ["a", "b", "c"] {
+            backend.acquire_at(key, &limit, 0).unwrap();
+        }
+        assert_eq!(backend.len(), 3);
+
+        backend.acquire_at("d", &limit, SWEEP_INTERVAL_MS).unwrap();
+        assert_eq!(backend.len(), 1);
+    }
+
+    #[test]
+    fn memory_backend_keeps_busy_buckets() {
+        let backend = MemoryBackend::default();
+        let limit = RateLimit::new(2, 3600);
+
+        backend.acquire_at("a", &limit, 0).unwrap();
+        backend.acquire_at("a", &limit, 0).unwrap();
+        backend.acquire_at("b", &limit, SWEEP_INTERVAL_MS).unwrap();
+
+        assert_eq!(backend.len(), 2);
+        assert!(backend.acquire_at("a", &limit, SWEEP_INTERVAL_MS).is_err());
+    }
+
+    #[test]
+    fn client_ip_ignores_forwarded_for_from_clients() {
+        let trusted = HashSet::from(["10.0.0.1".parse().unwrap()]);
+
+        assert_eq!(
+            client_ip(
+                Some("203.0.113.7".parse().unwrap()),
+                Some("198.51.100.1"),
+                &trusted
+            ),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(Some("203.0.113.7".parse().unwrap()), None, &trusted),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn client_ip_reads_forwarded_for_from_trusted_proxies() {
+        let trusted = HashSet::from([
+            "10.0.0.1".parse().unwrap(),
+            "10.0.0.2".parse().unwrap(),
+        ]);
+
+        // The client wrote the first entry itself, so it is skipped.
+        assert_eq!(
+            client_ip(
+                Some("10.0.0.1".parse().unwrap()),
+                Some("1.2.3.4, 203.0.113.7, 10.0.0.2"),
+                &trusted
+            ),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(
+                Some("10.0.0.1".parse().unwrap()),
+                Some("junk, 10.0.0.2"),
+                &trusted
+            ),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(Some("10.0.0.1".parse().unwrap()), None, &trusted),
+            Some("10.0.0.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn mutation_fields_lists_top_level_mutations() {
+        assert_eq!(
+            mutation_fields(
+                r#"mutation { votes { create(questionId: "x") { vote { id } } }
+                    questions { delete(questionId: "y") } }"#
+            ),
+            vec!["votes.create", "questions.delete"]
+        );
+    }
+
+    #[test]
+    fn mutation_fields_ignores_queries() {
+        assert!(mutation_fields("{ questions { getAll { id } } }").is_empty());
+        assert!(mutation_fields("query { votes { create { id } } }").is_empty());
+    }
+
+    #[test]
+    fn mutation_fields_sees_through_aliases() {
+        assert_eq!(
+            mutation_fields(
+                r#"mutation { v: votes { a: create(questionId: "x") { id }
+                    b: create(questionId: "y") { id } } }"#
+            ),
+            vec!["votes.create", "votes.create"]
+        );
+    }
+
+    #[test]
+    fn mutation_fields_handles_names_and_variables() {
+        assert_eq!(
+            mutation_fields(
+                "mutation Vote($id: String!) { votes { create(questionId: $id) \
+                 { id } } }"
+            ),
+            vec!["votes.create"]
+        );
+    }
+
+    #[test]
+    fn mutation_fields_skips_strings_and_comments() {
+        assert_eq!(
+            mutation_fields(
+                "mutation {\n# users { delete }\n votes { create(text: \
+                 \"} mutation { users { delete } }\") { id } } }"
+            ),
+            vec!["votes.create"]
+        );
+    }
+
+    #[test]
+    fn mutation_fields_follows_fragments() {
+        assert_eq!(
+            mutation_fields(
+                r#"mutation { ...Votes questions { ...Delete } }
+                fragment Votes on Mutation { votes { ... on VoteMutation {
+                    create(questionId: "x") { id } } } }
+                fragment Delete on QuestionMutation { delete(questionId: "y")
+                    ...Delete }"#
+            ),
+            vec!["votes.create", "questions.delete"]
+        );
+    }
+
+    #[test]
+    fn mutation_fields_ignores_fragments_of_queries() {
+        assert!(mutation_fields(
+            "query { ...Q } fragment Q on Query { votes { create { id } } }"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn mutation_fields_marks_unknown_fragments() {
+        assert_eq!(
+            mutation_fields("mutation { votes { ...Missing } ...Other }"),
+            vec!["*", "votes.*"]
+        );
+    }
+
+    #[test]
+    fn check_charges_every_operation_behind_a_wildcard() {
+        let limiter = RateLimiter::new(
+            Arc::new(MemoryBackend::default()),
+            None,
+            HashMap::from([
+                ("votes.create".to_owned(), RateLimit::new(1, 60)),
+                ("questions.create".to_owned(), RateLimit::new(1, 60)),
+            ]),
+            HashSet::new(),
+            HashSet::new(),
+        );
+
+        assert!(limiter.check("a", &["votes.*".to_owned()]).is_ok());
+        assert!(limiter.check("a", &["votes.create".to_owned()]).is_err());
+        assert!(limiter.check("a", &["questions.create".to_owned()]).is_ok());
+        assert!(limiter.check("b", &["*".to_owned()]).is_ok());
+        assert!(limiter
+            .check("b", &["questions.create".to_owned()])
+            .is_err());
+    }
+
+    #[test]
+    fn check_operations_leaves_the_global_limit_alone() {
+        let limiter = RateLimiter::new(
+            Arc::new(MemoryBackend::default()),
+            Some(RateLimit::new(1, 60)),
+            HashMap::from([(
+                "uploads.create".to_owned(),
+                RateLimit::new(2, 60),
+            )]),
+            HashSet::new(),
+            HashSet::new(),
+        );
+        let uploads = ["uploads.create".to_owned()];
+
+        assert!(limiter.check_operations("a", &uploads).is_ok());
+        assert!(limiter.check_operations("a", &uploads).is_ok());
+        assert!(limiter.check_operations("a", &uploads).is_err());
+        assert!(limiter.check("a", &[]).is_ok());
+        assert!(limiter.check("a", &[]).is_err());
+    }
+}
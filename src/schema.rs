@@ -1,5 +1,144 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    bookmarks (user_id, question_id) {
+        user_id -> Uuid,
+        question_id -> Uuid,
+        follow -> Bool,
+        notified_votes -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    comment_reactions (comment_id, user_id, kind) {
+        comment_id -> Uuid,
+        user_id -> Uuid,
+        kind -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    comments (id) {
+        id -> Uuid,
+        question_id -> Uuid,
+        user_id -> Uuid,
+        parent_id -> Nullable<Uuid>,
+        body -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+        hidden_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    follows (follower_id, followee_id) {
+        follower_id -> Uuid,
+        followee_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    identities (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    images (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        question_id -> Nullable<Uuid>,
+        position -> Int4,
+        content_type -> Varchar,
+        width -> Int4,
+        height -> Int4,
+        size_bytes -> Int4,
+        storage_key -> Varchar,
+        thumbnail_key -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    link_previews (url) {
+        url -> Varchar,
+        title -> Nullable<Varchar>,
+        description -> Nullable<Varchar>,
+        image_url -> Nullable<Varchar>,
+        error -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        fetched_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    login_challenges (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        attempts -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    moderation_actions (id) {
+        id -> Uuid,
+        moderator_id -> Nullable<Uuid>,
+        action -> Varchar,
+        target_type -> Varchar,
+        target_id -> Uuid,
+        report_id -> Nullable<Uuid>,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        question_id -> Nullable<Uuid>,
+        kind -> Varchar,
+        message -> Text,
+        created_at -> Timestamp,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    question_links (question_id, url) {
+        question_id -> Uuid,
+        url -> Varchar,
+        position -> Int4,
+    }
+}
+
+diesel::table! {
+    question_options (id) {
+        id -> Uuid,
+        question_id -> Uuid,
+        position -> Int4,
+        label -> Varchar,
+    }
+}
+
+diesel::table! {
+    question_tags (question_id, tag_id) {
+        question_id -> Uuid,
+        tag_id -> Uuid,
+    }
+}
+
 diesel::table! {
     questions (id) {
         id -> Uuid,
@@ -7,6 +146,56 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         user_id -> Uuid,
+        deleted_at -> Nullable<Timestamp>,
+        status -> Varchar,
+        opens_at -> Nullable<Timestamp>,
+        closes_at -> Nullable<Timestamp>,
+        results_visibility -> Varchar,
+        question_type -> Varchar,
+        scale_min -> Nullable<Int4>,
+        scale_max -> Nullable<Int4>,
+        scale_step -> Nullable<Int4>,
+        min_label -> Nullable<Varchar>,
+        max_label -> Nullable<Varchar>,
+        credit_budget -> Nullable<Int4>,
+        voter_visibility -> Varchar,
+        description -> Nullable<Text>,
+        description_html -> Nullable<Text>,
+        source_url -> Nullable<Varchar>,
+        hidden_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reports (id) {
+        id -> Uuid,
+        reporter_id -> Uuid,
+        target_type -> Varchar,
+        target_id -> Uuid,
+        reason -> Varchar,
+        note -> Nullable<Text>,
+        status -> Varchar,
+        created_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        resolved_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Uuid,
+        name -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
@@ -19,13 +208,40 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         last_login -> Nullable<Timestamp>,
+        role -> Varchar,
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled -> Bool,
+        totp_last_step -> Nullable<Int8>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    vote_events (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        question_id -> Uuid,
+        kind -> Varchar,
+        value -> Nullable<Int4>,
+        option_ids -> Array<Uuid>,
+        weights -> Array<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    vote_options (vote_id, option_id) {
+        vote_id -> Uuid,
+        option_id -> Uuid,
+        rank -> Int4,
+        weight -> Int4,
     }
 }
 
 diesel::table! {
     votes (id) {
         id -> Uuid,
-        value -> Int4,
+        value -> Nullable<Int4>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
         user_id -> Uuid,
@@ -33,12 +249,54 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(bookmarks -> questions (question_id));
+diesel::joinable!(bookmarks -> users (user_id));
+diesel::joinable!(comment_reactions -> comments (comment_id));
+diesel::joinable!(comment_reactions -> users (user_id));
+diesel::joinable!(comments -> questions (question_id));
+diesel::joinable!(comments -> users (user_id));
+diesel::joinable!(identities -> users (user_id));
+diesel::joinable!(images -> questions (question_id));
+diesel::joinable!(images -> users (user_id));
+diesel::joinable!(login_challenges -> users (user_id));
+diesel::joinable!(moderation_actions -> reports (report_id));
+diesel::joinable!(moderation_actions -> users (moderator_id));
+diesel::joinable!(notifications -> questions (question_id));
+diesel::joinable!(notifications -> users (user_id));
+diesel::joinable!(question_links -> link_previews (url));
+diesel::joinable!(question_links -> questions (question_id));
+diesel::joinable!(question_options -> questions (question_id));
+diesel::joinable!(question_tags -> questions (question_id));
+diesel::joinable!(question_tags -> tags (tag_id));
 diesel::joinable!(questions -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(vote_events -> questions (question_id));
+diesel::joinable!(vote_events -> users (user_id));
+diesel::joinable!(vote_options -> question_options (option_id));
+diesel::joinable!(vote_options -> votes (vote_id));
 diesel::joinable!(votes -> questions (question_id));
 diesel::joinable!(votes -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    bookmarks,
+    comment_reactions,
+    comments,
+    follows,
+    identities,
+    images,
+    link_previews,
+    login_challenges,
+    moderation_actions,
+    notifications,
+    question_links,
+    question_options,
+    question_tags,
     questions,
+    recovery_codes,
+    reports,
+    tags,
     users,
+    vote_events,
+    vote_options,
     votes,
 );
@@ -1,3 +1,16 @@
+pub(crate) mod bookmark;
+pub(crate) mod comment;
+pub(crate) mod follow;
+pub(crate) mod identity;
+pub(crate) mod image;
+pub(crate) mod link;
+pub(crate) mod markdown;
+pub(crate) mod notification;
+pub(crate) mod password;
 pub(crate) mod question;
+pub(crate) mod report;
+pub(crate) mod tag;
+pub(crate) mod tally;
+pub(crate) mod totp;
 pub(crate) mod user;
 pub(crate) mod vote;
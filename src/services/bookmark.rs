@@ -0,0 +1,147 @@
+use chrono::NaiveDateTime;
+use diesel::{pg::upsert::excluded, prelude::*};
+use uuid::Uuid;
+
+use crate::{
+    models::{bookmark::Bookmark, question::Question},
+    schema::{bookmarks, questions},
+};
+use bookmarks::dsl::*;
+
+/// Bookmarks the question for the user, or changes whether they follow it
+/// if they bookmarked it already.
+pub fn upsert(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    questionid: Uuid,
+    follows: bool,
+) -> QueryResult<Bookmark> {
+    diesel::insert_into(bookmarks)
+        .values((
+            user_id.eq(userid),
+            question_id.eq(questionid),
+            follow.eq(follows),
+        ))
+        .on_conflict((user_id, question_id))
+        .do_update()
+        .set(follow.eq(excluded(follow)))
+        .get_result(conn)
+}
+
+/// Removes the bookmark. Returns whether there was one.
+pub fn delete(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    questionid: Uuid,
+) -> QueryResult<bool> {
+    diesel::delete(bookmarks.find((userid, questionid)))
+        .execute(conn)
+        .map(|deleted| deleted > 0)
+}
+
+pub fn get(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    questionid: Uuid,
+) -> QueryResult<Bookmark> {
+    bookmarks.find((userid, questionid)).first(conn)
+}
+
+/// The user's bookmarked questions, most recently bookmarked first, after
+/// the `cursor` bookmark if given. Deleted questions are left out.
+pub fn get_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<(Bookmark, Question)>> {
+    let mut query = bookmarks
+        .inner_join(questions::table)
+        .filter(user_id.eq(userid))
+        .filter(questions::deleted_at.is_null())
+        .into_boxed();
+
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            created_at
+                .lt(cursor_at)
+                .or(created_at.eq(cursor_at).and(question_id.lt(cursor_id))),
+        );
+    }
+
+    query
+        .order((created_at.desc(), question_id.desc()))
+        .select((bookmarks::all_columns, questions::all_columns))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        services,
+    };
+
+    #[test]
+    fn bookmarking_again_only_changes_following() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let questionid = create_test_question(conn, userid);
+
+            let bookmark = upsert(conn, userid, questionid, false)?;
+            assert!(!bookmark.follow);
+            let followed = upsert(conn, userid, questionid, true)?;
+            assert!(followed.follow);
+            assert_eq!(followed.created_at, bookmark.created_at);
+            assert_eq!(get_by_user_id(conn, userid, 10, None)?.len(), 1);
+
+            assert!(delete(conn, userid, questionid)?);
+            assert!(!delete(conn, userid, questionid)?);
+            assert!(get(conn, userid, questionid).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn pages_through_bookmarks_of_live_questions() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let other = create_test_user(conn);
+            let mut questionids: Vec<Uuid> = (0..3)
+                .map(|_| {
+                    let questionid = create_test_question(conn, other);
+                    upsert(conn, userid, questionid, false).unwrap();
+                    questionid
+                })
+                .collect();
+            let deleted = create_test_question(conn, other);
+            upsert(conn, userid, deleted, true)?;
+            services::question::delete(conn, deleted)?;
+            upsert(conn, other, questionids[0], true)?;
+
+            // Bookmarks made in one transaction share their time, so the
+            // question id breaks the tie.
+            questionids.sort();
+            questionids.reverse();
+            let first = get_by_user_id(conn, userid, 2, None)?;
+            let (last, _) = first.last().unwrap();
+            let cursor = Some((last.created_at, last.question_id));
+            let rest = get_by_user_id(conn, userid, 2, cursor)?;
+
+            let listed: Vec<Uuid> = first
+                .iter()
+                .chain(&rest)
+                .map(|(bookmark, question)| {
+                    assert_eq!(bookmark.question_id, question.id);
+                    question.id
+                })
+                .collect();
+            assert_eq!(listed, questionids);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,443 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    dsl::{count_star, sql},
+    pg::Pg,
+    prelude::*,
+    sql_types::{Bool, Double},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        comment::{Comment, CommentInput, CommentSort, ReactionKind},
+        user::User,
+    },
+    schema::{self, comment_reactions, comments},
+};
+use schema::comments::dsl::*;
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_comment: CommentInput,
+) -> QueryResult<Comment> {
+    diesel::insert_into(comments)
+        .values(&new_comment)
+        .get_result(conn)
+}
+
+pub fn get_by_id(
+    conn: &mut PgConnection,
+    comment_uuid: Uuid,
+) -> QueryResult<Comment> {
+    comments
+        .find(comment_uuid)
+        .filter(deleted_at.is_null())
+        .first(conn)
+}
+
+/// Gets one of the question's comments, as long as it was not deleted.
+/// Replies must be on the same question as their parent.
+pub fn get_by_id_and_question_id(
+    conn: &mut PgConnection,
+    comment_uuid: Uuid,
+    questionid: Uuid,
+) -> QueryResult<Comment> {
+    comments
+        .find(comment_uuid)
+        .filter(question_id.eq(questionid))
+        .filter(deleted_at.is_null())
+        .first(conn)
+}
+
+/// Whether the user `userid` may edit the comment: only its author.
+pub fn can_edit(comment: &Comment, userid: Option<Uuid>) -> bool {
+    userid == Some(comment.user_id)
+}
+
+/// Whether `user` may delete the comment: its author, or a moderator.
+pub fn can_delete(comment: &Comment, user: &User) -> bool {
+    comment.user_id == user.id || user.can_moderate()
+}
+
+/// Gets the question's comments, or the replies to `parentid`, `limit` at a
+/// time after the `cursor` comment. Deleted and hidden comments are only
+/// kept as long as they have replies, to hold the thread together.
+pub fn get_paginated(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+    parentid: Option<Uuid>,
+    sort: CommentSort,
+    limit: i32,
+    cursor: Option<Uuid>,
+) -> QueryResult<Vec<Comment>> {
+    let mut query = comments
+        .filter(question_id.eq(questionid))
+        .filter(
+            deleted_at
+                .is_null()
+                .and(hidden_at.is_null())
+                .or(has_replies()),
+        )
+        .into_boxed();
+
+    query = match parentid {
+        Some(parentid) => query.filter(parent_id.eq(parentid)),
+        None => query.filter(parent_id.is_null()),
+    };
+
+    if let Some(cursor) = cursor {
+        let cursor_key: f64 =
+            comments.find(cursor).select(sort_key(sort)).first(conn)?;
+
+        query = if sort == CommentSort::Old {
+            query.filter(
+                sort_key(sort)
+                    .gt(cursor_key)
+                    .or(sort_key(sort).eq(cursor_key).and(id.gt(cursor))),
+            )
+        } else {
+            query.filter(
+                sort_key(sort)
+                    .lt(cursor_key)
+                    .or(sort_key(sort).eq(cursor_key).and(id.lt(cursor))),
+            )
+        };
+    }
+
+    query = if sort == CommentSort::Old {
+        query.order((sort_key(sort).asc(), id.asc()))
+    } else {
+        query.order((sort_key(sort).desc(), id.desc()))
+    };
+
+    query.limit(limit as i64).load(conn)
+}
+
+fn has_replies(
+) -> Box<dyn BoxableExpression<comments::table, Pg, SqlType = Bool>> {
+    Box::new(sql::<Bool>(
+        "EXISTS (SELECT 1 FROM comments AS replies \
+         WHERE replies.parent_id = comments.id \
+         AND replies.deleted_at IS NULL \
+         AND replies.hidden_at IS NULL)",
+    ))
+}
+
+fn sort_key(
+    sort: CommentSort,
+) -> Box<dyn BoxableExpression<comments::table, Pg, SqlType = Double>> {
+    match sort {
+        CommentSort::New | CommentSort::Old => Box::new(sql::<Double>(
+            "extract(epoch FROM comments.created_at)::float8",
+        )),
+        CommentSort::Top => Box::new(sql::<Double>(
+            "(SELECT COUNT(*) FROM comment_reactions \
+             WHERE comment_reactions.comment_id = comments.id)::float8",
+        )),
+    }
+}
+
+/// Counts the replies to a comment that were neither deleted nor hidden.
+pub fn count_replies(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+) -> QueryResult<i64> {
+    comments
+        .filter(parent_id.eq(commentid))
+        .filter(deleted_at.is_null())
+        .filter(hidden_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+/// Counts the question's comments, replies included, that were neither
+/// deleted nor hidden.
+pub fn count_for_question(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<i64> {
+    comments
+        .filter(question_id.eq(questionid))
+        .filter(deleted_at.is_null())
+        .filter(hidden_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+pub fn update_body(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+    new_body: &str,
+) -> QueryResult<Comment> {
+    diesel::update(comments.find(commentid))
+        .set((body.eq(new_body), updated_at.eq(Utc::now().naive_utc())))
+        .get_result(conn)
+}
+
+/// Soft deletes a comment. Its replies stay.
+pub fn delete(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+) -> QueryResult<Comment> {
+    diesel::update(comments.find(commentid))
+        .set(deleted_at.eq(Utc::now().naive_utc()))
+        .get_result(conn)
+}
+
+/// Hides the comment until a moderator reviews it, or shows it again.
+pub fn set_hidden(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+    hidden: bool,
+) -> QueryResult<usize> {
+    let at = hidden.then(|| Utc::now().naive_utc());
+
+    diesel::update(comments.find(commentid))
+        .set(hidden_at.eq(at))
+        .execute(conn)
+}
+
+/// Hard deletes comments deleted before `before` that no reply holds on
+/// to.
+pub fn purge_deleted(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> QueryResult<usize> {
+    diesel::delete(comments.filter(deleted_at.lt(before)).filter(
+        diesel::dsl::not(sql::<Bool>(
+            "EXISTS (SELECT 1 FROM comments AS replies \
+                 WHERE replies.parent_id = comments.id)",
+        )),
+    ))
+    .execute(conn)
+}
+
+/// Adds the user's reaction to a comment. Reacting twice alike does
+/// nothing.
+pub fn react(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+    userid: Uuid,
+    reaction: ReactionKind,
+) -> QueryResult<()> {
+    diesel::insert_into(comment_reactions::table)
+        .values((
+            comment_reactions::comment_id.eq(commentid),
+            comment_reactions::user_id.eq(userid),
+            comment_reactions::kind.eq(reaction),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map(|_| ())
+}
+
+pub fn unreact(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+    userid: Uuid,
+    reaction: ReactionKind,
+) -> QueryResult<()> {
+    diesel::delete(
+        comment_reactions::table
+            .filter(comment_reactions::comment_id.eq(commentid))
+            .filter(comment_reactions::user_id.eq(userid))
+            .filter(comment_reactions::kind.eq(reaction)),
+    )
+    .execute(conn)
+    .map(|_| ())
+}
+
+/// The number of reactions of each kind a comment got.
+pub fn count_reactions(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+) -> QueryResult<Vec<(ReactionKind, i64)>> {
+    comment_reactions::table
+        .filter(comment_reactions::comment_id.eq(commentid))
+        .group_by(comment_reactions::kind)
+        .select((comment_reactions::kind, count_star()))
+        .order(comment_reactions::kind)
+        .load(conn)
+}
+
+/// The kinds of reactions the user gave a comment.
+pub fn get_reactions_by_user_id(
+    conn: &mut PgConnection,
+    commentid: Uuid,
+    userid: Uuid,
+) -> QueryResult<Vec<ReactionKind>> {
+    comment_reactions::table
+        .filter(comment_reactions::comment_id.eq(commentid))
+        .filter(comment_reactions::user_id.eq(userid))
+        .select(comment_reactions::kind)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        schema::users,
+    };
+
+    fn comment_on(
+        conn: &mut PgConnection,
+        questionid: Uuid,
+        userid: Uuid,
+        parentid: Option<Uuid>,
+    ) -> Comment {
+        create(
+            conn,
+            CommentInput {
+                question_id: questionid,
+                user_id: userid,
+                parent_id: parentid,
+                body: "A comment.".to_owned(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn ids(found: Vec<Comment>) -> Vec<Uuid> {
+        found.into_iter().map(|comment| comment.id).collect()
+    }
+
+    #[test]
+    fn only_authors_edit_and_moderators_also_delete() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let other = create_test_user(conn);
+            let moderator = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let comment = comment_on(conn, questionid, author, None);
+
+            diesel::update(users::table.find(moderator))
+                .set((
+                    users::role.eq("moderator"),
+                    users::totp_enabled.eq(true),
+                ))
+                .execute(conn)?;
+            let get_user = |conn: &mut PgConnection, userid: Uuid| {
+                users::table.find(userid).first::<User>(conn)
+            };
+
+            assert!(can_edit(&comment, Some(author)));
+            assert!(!can_edit(&comment, Some(moderator)));
+            assert!(!can_edit(&comment, None));
+            assert!(can_delete(&comment, &get_user(conn, author)?));
+            assert!(can_delete(&comment, &get_user(conn, moderator)?));
+            assert!(!can_delete(&comment, &get_user(conn, other)?));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn replies_stay_on_their_question() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let other_questionid = create_test_question(conn, author);
+            let comment = comment_on(conn, questionid, author, None);
+
+            assert!(
+                get_by_id_and_question_id(conn, comment.id, questionid).is_ok()
+            );
+            assert!(get_by_id_and_question_id(
+                conn,
+                comment.id,
+                other_questionid
+            )
+            .is_err());
+
+            delete(conn, comment.id)?;
+            assert!(get_by_id_and_question_id(conn, comment.id, questionid)
+                .is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn deleted_comments_hold_their_thread_together() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let parent = comment_on(conn, questionid, author, None);
+            let first = comment_on(conn, questionid, author, Some(parent.id));
+            let second = comment_on(conn, questionid, author, Some(parent.id));
+            let page = |conn: &mut PgConnection, parentid, sort| {
+                get_paginated(conn, questionid, parentid, sort, 10, None)
+                    .map(ids)
+            };
+
+            assert_eq!(page(conn, None, CommentSort::New)?, [parent.id]);
+            let mut replies = page(conn, Some(parent.id), CommentSort::Old)?;
+            assert_eq!(replies.len(), 2);
+            assert!(
+                replies.contains(&first.id) && replies.contains(&second.id)
+            );
+            replies.reverse();
+            assert_eq!(page(conn, Some(parent.id), CommentSort::New)?, replies);
+            assert_eq!(count_replies(conn, parent.id)?, 2);
+            assert_eq!(count_for_question(conn, questionid)?, 3);
+
+            // The parent stays listed while it has replies.
+            delete(conn, parent.id)?;
+            assert_eq!(page(conn, None, CommentSort::New)?, [parent.id]);
+            assert_eq!(count_for_question(conn, questionid)?, 2);
+
+            set_hidden(conn, first.id, true)?;
+            delete(conn, second.id)?;
+            assert!(page(conn, None, CommentSort::New)?.is_empty());
+            assert_eq!(count_for_question(conn, questionid)?, 0);
+
+            // The hidden reply still holds its deleted parent.
+            let later = Utc::now().naive_utc() + chrono::Duration::seconds(1);
+            assert_eq!(purge_deleted(conn, later)?, 1);
+            assert_eq!(purge_deleted(conn, later)?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn top_comments_have_the_most_reactions() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let reader = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let quiet = comment_on(conn, questionid, author, None);
+            let liked = comment_on(conn, questionid, author, None);
+
+            react(conn, liked.id, reader, ReactionKind::Like)?;
+            react(conn, liked.id, reader, ReactionKind::Like)?;
+            react(conn, liked.id, author, ReactionKind::Like)?;
+            assert_eq!(
+                count_reactions(conn, liked.id)?,
+                [(ReactionKind::Like, 2)]
+            );
+            assert_eq!(
+                ids(get_paginated(
+                    conn,
+                    questionid,
+                    None,
+                    CommentSort::Top,
+                    10,
+                    None
+                )?),
+                [liked.id, quiet.id]
+            );
+
+            unreact(conn, liked.id, reader, ReactionKind::Like)?;
+            assert_eq!(
+                get_reactions_by_user_id(conn, liked.id, author)?,
+                [ReactionKind::Like]
+            );
+            assert!(
+                get_reactions_by_user_id(conn, liked.id, reader)?.is_empty()
+            );
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    models::{follow::Follow, user::User},
+    schema::{follows, users},
+};
+use follows::dsl::*;
+
+/// Makes `follower` follow `followee`. Returns whether they did not already.
+pub fn follow(
+    conn: &mut PgConnection,
+    follower: Uuid,
+    followee: Uuid,
+) -> QueryResult<bool> {
+    diesel::insert_into(follows)
+        .values((follower_id.eq(follower), followee_id.eq(followee)))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map(|inserted| inserted > 0)
+}
+
+/// Returns whether `follower` followed `followee`.
+pub fn unfollow(
+    conn: &mut PgConnection,
+    follower: Uuid,
+    followee: Uuid,
+) -> QueryResult<bool> {
+    diesel::delete(follows.find((follower, followee)))
+        .execute(conn)
+        .map(|deleted| deleted > 0)
+}
+
+pub fn get(
+    conn: &mut PgConnection,
+    follower: Uuid,
+    followee: Uuid,
+) -> QueryResult<Follow> {
+    follows.find((follower, followee)).first(conn)
+}
+
+/// The users `follower` follows, most recently followed first, after the
+/// `cursor` follow if given. Deleted users are left out.
+pub fn get_following(
+    conn: &mut PgConnection,
+    follower: Uuid,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<User>> {
+    let mut query = follows
+        .inner_join(users::table.on(users::id.eq(followee_id)))
+        .filter(follower_id.eq(follower))
+        .filter(users::deleted_at.is_null())
+        .into_boxed();
+
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            created_at
+                .lt(cursor_at)
+                .or(created_at.eq(cursor_at).and(followee_id.lt(cursor_id))),
+        );
+    }
+
+    query
+        .order((created_at.desc(), followee_id.desc()))
+        .select(users::all_columns)
+        .limit(limit as i64)
+        .load(conn)
+}
+
+/// The users following `followee`, most recent first, after the `cursor`
+/// follow if given. Deleted users are left out.
+pub fn get_followers(
+    conn: &mut PgConnection,
+    followee: Uuid,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<User>> {
+    let mut query = follows
+        .inner_join(users::table.on(users::id.eq(follower_id)))
+        .filter(followee_id.eq(followee))
+        .filter(users::deleted_at.is_null())
+        .into_boxed();
+
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            created_at
+                .lt(cursor_at)
+                .or(created_at.eq(cursor_at).and(follower_id.lt(cursor_id))),
+        );
+    }
+
+    query
+        .order((created_at.desc(), follower_id.desc()))
+        .select(users::all_columns)
+        .limit(limit as i64)
+        .load(conn)
+}
+
+pub fn count_following(
+    conn: &mut PgConnection,
+    follower: Uuid,
+) -> QueryResult<i64> {
+    follows
+        .filter(follower_id.eq(follower))
+        .count()
+        .get_result(conn)
+}
+
+pub fn count_followers(
+    conn: &mut PgConnection,
+    followee: Uuid,
+) -> QueryResult<i64> {
+    follows
+        .filter(followee_id.eq(followee))
+        .count()
+        .get_result(conn)
+}
@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::identities::dsl::*;
+use crate::{
+    models::identity::{Identity, IdentityInput},
+    schema::identities,
+};
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_identity: IdentityInput,
+) -> QueryResult<Identity> {
+    diesel::insert_into(identities::table)
+        .values(&new_identity)
+        .get_result(conn)
+}
+
+pub fn get_by_provider_and_subject(
+    conn: &mut PgConnection,
+    provider_name: &str,
+    subject_id: &str,
+) -> QueryResult<Identity> {
+    identities
+        .filter(provider.eq(provider_name))
+        .filter(subject.eq(subject_id))
+        .first(conn)
+}
+
+pub fn get_all_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+) -> QueryResult<Vec<Identity>> {
+    identities
+        .filter(user_id.eq(userid))
+        .order_by(created_at.asc())
+        .load(conn)
+}
+
+/// Deletes the user's identity, unless `keep_last` and it is their last one.
+/// Returns whether it was deleted.
+pub fn delete(
+    conn: &mut PgConnection,
+    identityid: Uuid,
+    userid: Uuid,
+    keep_last: bool,
+) -> QueryResult<bool> {
+    conn.transaction(|conn| {
+        // Locked, so of two concurrent deletes the second sees the first.
+        let linked: Vec<Uuid> = identities
+            .filter(user_id.eq(userid))
+            .select(id)
+            .for_update()
+            .load(conn)?;
+
+        if !linked.contains(&identityid) || (keep_last && linked.len() == 1) {
+            return Ok(false);
+        }
+
+        diesel::delete(identities.find(identityid))
+            .execute(conn)
+            .map(|deleted| deleted > 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{create_test_user, with_test_transaction};
+
+    fn link(conn: &mut PgConnection, userid: Uuid) -> Uuid {
+        create(
+            conn,
+            IdentityInput {
+                user_id: userid,
+                provider: "mock".to_owned(),
+                subject: Uuid::new_v4().to_string(),
+                email: None,
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    #[test]
+    fn keeps_the_last_identity_when_asked() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let other = create_test_user(conn);
+            let first = link(conn, userid);
+            let second = link(conn, userid);
+
+            assert!(!delete(conn, first, other, false)?);
+            assert!(delete(conn, first, userid, true)?);
+            assert!(!delete(conn, second, userid, true)?);
+            assert_eq!(get_all_by_user_id(conn, userid)?.len(), 1);
+            assert!(delete(conn, second, userid, false)?);
+            assert!(get_all_by_user_id(conn, userid)?.is_empty());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    models::image::{Image, ImageInput},
+    schema::{images, users},
+};
+use images::dsl::*;
+
+pub fn create(
+    conn: &mut PgConnection,
+    new_image: ImageInput,
+) -> QueryResult<Image> {
+    diesel::insert_into(images)
+        .values(&new_image)
+        .get_result(conn)
+}
+
+/// Stores an upload unless the user already has `max_unattached` uploads
+/// waiting to be attached, in which case `None` is returned.
+pub fn create_unattached(
+    conn: &mut PgConnection,
+    new_image: ImageInput,
+    max_unattached: i64,
+) -> QueryResult<Option<Image>> {
+    conn.transaction(|conn| {
+        // Locked, so concurrent uploads of a user are counted one at a time.
+        users::table
+            .find(new_image.user_id)
+            .select(users::id)
+            .for_update()
+            .first::<Uuid>(conn)?;
+
+        if count_unattached_by_user_id(conn, new_image.user_id)?
+            >= max_unattached
+        {
+            return Ok(None);
+        }
+
+        create(conn, new_image).map(Some)
+    })
+}
+
+pub fn get_by_ids(
+    conn: &mut PgConnection,
+    image_ids: &[Uuid],
+) -> QueryResult<Vec<Image>> {
+    images.filter(id.eq_any(image_ids)).load(conn)
+}
+
+/// The question's images, in order.
+pub fn get_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<Image>> {
+    images
+        .filter(question_id.eq(questionid))
+        .order(position.asc())
+        .load(conn)
+}
+
+/// Counts the user's uploads not attached to a question yet.
+pub fn count_unattached_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+) -> QueryResult<i64> {
+    images
+        .filter(user_id.eq(userid))
+        .filter(question_id.is_null())
+        .count()
+        .get_result(conn)
+}
+
+/// Makes `image_ids` the question's images, in order. Images it no longer
+/// shows are detached, to be collected with other unused uploads.
+pub fn set_for_question(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+    image_ids: &[Uuid],
+) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        diesel::update(images.filter(question_id.eq(questionid)))
+            .set(question_id.eq(None::<Uuid>))
+            .execute(conn)?;
+
+        for (i, imageid) in image_ids.iter().enumerate() {
+            diesel::update(images.find(imageid))
+                .set((question_id.eq(questionid), position.eq(i as i32)))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Deletes the uploads never attached to a question, or detached since,
+/// that were uploaded before `before`, returning them so their files can be
+/// deleted in turn.
+pub fn delete_unused(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> QueryResult<Vec<Image>> {
+    diesel::delete(
+        images
+            .filter(question_id.is_null())
+            .filter(created_at.lt(before)),
+    )
+    .get_results(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{create_test_user, with_test_transaction};
+
+    fn upload(userid: Uuid) -> ImageInput {
+        let imageid = Uuid::new_v4();
+        ImageInput {
+            id: imageid,
+            user_id: userid,
+            content_type: "image/png".to_owned(),
+            width: 1,
+            height: 1,
+            size_bytes: 1,
+            storage_key: format!("{}.png", imageid),
+            thumbnail_key: format!("{}-thumb.png", imageid),
+        }
+    }
+
+    #[test]
+    fn create_unattached_stops_at_the_limit() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let other = create_test_user(conn);
+
+            assert!(create_unattached(conn, upload(userid), 2)?.is_some());
+            assert!(create_unattached(conn, upload(userid), 2)?.is_some());
+            assert!(create_unattached(conn, upload(userid), 2)?.is_none());
+            assert!(create_unattached(conn, upload(other), 2)?.is_some());
+            assert_eq!(count_unattached_by_user_id(conn, userid)?, 2);
+            Ok(())
+        });
+    }
+}
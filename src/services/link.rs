@@ -0,0 +1,106 @@
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    link_preview::PageMetadata,
+    models::question::LinkPreview,
+    schema::{link_previews, question_links},
+};
+use link_previews::dsl::*;
+
+/// Replaces the question's links with `urls`, in order, queuing previews
+/// for the ones not seen before.
+pub fn set_for_question(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+    urls: &[String],
+) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        let previews: Vec<_> = urls.iter().map(|link| url.eq(link)).collect();
+        let links: Vec<_> = urls
+            .iter()
+            .enumerate()
+            .map(|(i, link)| {
+                (
+                    question_links::question_id.eq(questionid),
+                    question_links::url.eq(link),
+                    question_links::position.eq(i as i32),
+                )
+            })
+            .collect();
+
+        diesel::delete(
+            question_links::table
+                .filter(question_links::question_id.eq(questionid)),
+        )
+        .execute(conn)?;
+
+        if !links.is_empty() {
+            diesel::insert_into(link_previews)
+                .values(&previews)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            diesel::insert_into(question_links::table)
+                .values(&links)
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// The previews of the question's links, in order.
+pub fn get_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<LinkPreview>> {
+    link_previews
+        .inner_join(question_links::table)
+        .filter(question_links::question_id.eq(questionid))
+        .order(question_links::position)
+        .select(link_previews::all_columns)
+        .load(conn)
+}
+
+/// URLs waiting for a preview, oldest first.
+pub fn get_pending(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> QueryResult<Vec<String>> {
+    link_previews
+        .filter(fetched_at.is_null())
+        .order(created_at)
+        .select(url)
+        .limit(limit)
+        .load(conn)
+}
+
+/// Stores a fetched preview, or why it could not be fetched.
+pub fn set_preview(
+    conn: &mut PgConnection,
+    preview_url: &str,
+    preview: Result<PageMetadata, String>,
+) -> QueryResult<usize> {
+    let (metadata, new_error) = match preview {
+        Ok(metadata) => (metadata, None),
+        Err(e) => (PageMetadata::default(), Some(e)),
+    };
+
+    diesel::update(link_previews.find(preview_url))
+        .set((
+            title.eq(metadata.title),
+            description.eq(metadata.description),
+            image_url.eq(metadata.image_url),
+            error.eq(new_error),
+            fetched_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+}
+
+/// Deletes the previews no question links to anymore.
+pub fn delete_unused(conn: &mut PgConnection) -> QueryResult<usize> {
+    diesel::delete(link_previews.filter(diesel::dsl::not(
+        url.eq_any(question_links::table.select(question_links::url)),
+    )))
+    .execute(conn)
+}
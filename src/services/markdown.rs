@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+/// Tags kept in rendered descriptions. Anything else, headings included, is
+/// reduced to its text.
+const ALLOWED_TAGS: [&str; 12] = [
+    "a",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "strong",
+    "ul",
+];
+
+/// Renders a description to HTML that is safe to show as is. Raw HTML in
+/// the markdown is shown as text, images as their alt text, and links only
+/// keep `http`, `https` and `mailto` targets.
+pub fn render(markdown: &str) -> String {
+    let events = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH)
+        .filter_map(|event| match event {
+            Event::Html(html) => Some(Event::Text(html)),
+            Event::Start(Tag::Image(..)) | Event::End(Tag::Image(..)) => None,
+            event => Some(event),
+        });
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events);
+
+    ammonia::Builder::default()
+        .tags(HashSet::from(ALLOWED_TAGS))
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow noopener noreferrer ugc"))
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+/// The `http` and `https` links in a description, in order, without
+/// repeats.
+pub fn links(markdown: &str) -> Vec<String> {
+    let mut links: Vec<String> = vec![];
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+        if let Event::Start(Tag::Link(_, url, _)) = event {
+            if is_web_url(&url) && !links.iter().any(|link| *link == *url) {
+                links.push(url.to_string());
+            }
+        }
+    }
+
+    links
+}
+
+/// Whether `url` is an absolute `http` or `https` URL with a host.
+pub fn is_web_url(url: &str) -> bool {
+    match url.parse::<awc::http::Uri>() {
+        Ok(uri) => {
+            matches!(uri.scheme_str(), Some("http") | Some("https"))
+                && uri.host().map_or(false, |host| !host.is_empty())
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_raw_html_as_text() {
+        let html =
+            render("<script>alert(1)</script>\n\nHi <b onclick=x>there</b>");
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<b"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("there"));
+    }
+
+    #[test]
+    fn keeps_only_safe_links() {
+        let html =
+            render("[ok](https://example.com) [bad](javascript:alert(1))");
+
+        assert!(html.contains(r#"href="https://example.com""#));
+        assert!(html.contains(r#"rel="nofollow noopener noreferrer ugc""#));
+        assert!(!html.contains("javascript"));
+        assert!(html.contains("bad"));
+    }
+
+    #[test]
+    fn reduces_other_tags_to_text() {
+        let html = render("# Title\n\n![A cat](https://example.com/cat.png)");
+
+        assert!(!html.contains("<h1"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("Title"));
+        assert!(html.contains("A cat"));
+    }
+
+    #[test]
+    fn renders_basic_formatting() {
+        let html = render("**bold** _em_ ~~gone~~ `code`\n\n- item");
+
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<em>em</em>"));
+        assert!(html.contains("<del>gone</del>"));
+        assert!(html.contains("<code>code</code>"));
+        assert!(html.contains("<li>item</li>"));
+    }
+
+    #[test]
+    fn lists_web_links_once() {
+        assert_eq!(
+            links(
+                "[a](https://a.example) [mail](mailto:x@y.example) \
+                 [again](https://a.example) <http://b.example/page> \
+                 [rel](/relative)"
+            ),
+            vec!["https://a.example", "http://b.example/page"]
+        );
+    }
+
+    #[test]
+    fn recognizes_web_urls() {
+        assert!(is_web_url("https://example.com/a?b=c"));
+        assert!(is_web_url("http://example.com"));
+        assert!(!is_web_url("ftp://example.com"));
+        assert!(!is_web_url("example.com"));
+        assert!(!is_web_url("/relative"));
+        assert!(!is_web_url("javascript:alert(1)"));
+    }
+}
@@ -0,0 +1,338 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    dsl::sql,
+    pg::Pg,
+    prelude::*,
+    sql_types::{Bool, Text},
+};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        notification::{Notification, NotificationKind},
+        question::{Question, ResultsVisibility},
+    },
+    schema::{bookmarks, notifications},
+};
+use notifications::dsl::*;
+
+/// Notifies everyone following the question. Returns how many were.
+pub fn notify_followers(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+    notification_kind: NotificationKind,
+    text: &str,
+) -> QueryResult<usize> {
+    bookmarks::table
+        .filter(bookmarks::question_id.eq(questionid))
+        .filter(bookmarks::follow.eq(true))
+        .select((
+            bookmarks::user_id,
+            bookmarks::question_id.nullable(),
+            notification_kind.into_sql::<Text>(),
+            text.into_sql::<Text>(),
+        ))
+        .insert_into(notifications)
+        .into_columns((user_id, question_id, kind, message))
+        .execute(conn)
+}
+
+/// Sends the same notification to each of the users.
+pub fn notify_users(
+    conn: &mut PgConnection,
+    userids: &[Uuid],
+    questionid: Option<Uuid>,
+    notification_kind: NotificationKind,
+    text: &str,
+) -> QueryResult<usize> {
+    let rows: Vec<_> = userids
+        .iter()
+        .map(|userid| {
+            (
+                user_id.eq(*userid),
+                question_id.eq(questionid),
+                kind.eq(notification_kind),
+                message.eq(text),
+            )
+        })
+        .collect();
+
+    diesel::insert_into(notifications)
+        .values(&rows)
+        .execute(conn)
+}
+
+/// Notifies the question's followers that it reached `threshold` votes,
+/// unless they were notified of it, or of a higher one, already. Followers
+/// who may not see the question's results are left out, as the vote count
+/// is one.
+pub fn notify_vote_threshold(
+    conn: &mut PgConnection,
+    question: &Question,
+    threshold: i32,
+    text: &str,
+) -> QueryResult<usize> {
+    let followers = bookmarks::table
+        .filter(bookmarks::question_id.eq(question.id))
+        .filter(bookmarks::follow.eq(true))
+        .filter(bookmarks::notified_votes.lt(threshold));
+
+    conn.transaction(|conn| {
+        let notified = followers
+            .filter(sees_results(question))
+            .select((
+                bookmarks::user_id,
+                bookmarks::question_id.nullable(),
+                NotificationKind::VotesReached.into_sql::<Text>(),
+                text.into_sql::<Text>(),
+            ))
+            .insert_into(notifications)
+            .into_columns((user_id, question_id, kind, message))
+            .execute(conn)?;
+
+        diesel::update(followers.filter(sees_results(question)))
+            .set(bookmarks::notified_votes.eq(threshold))
+            .execute(conn)?;
+
+        Ok(notified)
+    })
+}
+
+/// Whether the follower may see the question's results, as
+/// `check_results_visible` decides it: always, after voting or closing, and
+/// always for the author and moderators.
+fn sees_results(
+    question: &Question,
+) -> Box<dyn BoxableExpression<bookmarks::table, Pg, SqlType = Bool>> {
+    let privileged = bookmarks::user_id.eq(question.user_id).or(sql::<Bool>(
+        "EXISTS (SELECT 1 FROM users WHERE users.id = bookmarks.user_id \
+         AND users.role IN ('moderator', 'admin') AND users.totp_enabled)",
+    ));
+
+    match question.results_visibility {
+        ResultsVisibility::Always => Box::new(sql::<Bool>("TRUE")),
+        ResultsVisibility::AfterVote => Box::new(privileged.or(sql::<Bool>(
+            "EXISTS (SELECT 1 FROM votes \
+             WHERE votes.question_id = bookmarks.question_id \
+             AND votes.user_id = bookmarks.user_id)",
+        ))),
+        ResultsVisibility::AfterClose
+            if question.is_closed(Utc::now().naive_utc()) =>
+        {
+            Box::new(sql::<Bool>("TRUE"))
+        }
+        ResultsVisibility::AfterClose | ResultsVisibility::AuthorOnly => {
+            Box::new(privileged)
+        }
+    }
+}
+
+/// The user's notifications, newest first, after the `cursor` notification
+/// if given.
+pub fn get_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    unread_only: bool,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<Notification>> {
+    let mut query = notifications.filter(user_id.eq(userid)).into_boxed();
+
+    if unread_only {
+        query = query.filter(read_at.is_null());
+    }
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            created_at
+                .lt(cursor_at)
+                .or(created_at.eq(cursor_at).and(id.lt(cursor_id))),
+        );
+    }
+
+    query
+        .order((created_at.desc(), id.desc()))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+pub fn get_by_id(
+    conn: &mut PgConnection,
+    notificationid: Uuid,
+) -> QueryResult<Notification> {
+    notifications.find(notificationid).first(conn)
+}
+
+pub fn count_unread(conn: &mut PgConnection, userid: Uuid) -> QueryResult<i64> {
+    notifications
+        .filter(user_id.eq(userid))
+        .filter(read_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+/// Marks the user's notifications as read, only `notification_ids` if
+/// given. Returns how many were unread.
+pub fn mark_read(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    notification_ids: Option<&[Uuid]>,
+) -> QueryResult<usize> {
+    let unread = notifications
+        .filter(user_id.eq(userid))
+        .filter(read_at.is_null());
+    let now = Utc::now().naive_utc();
+
+    match notification_ids {
+        Some(notification_ids) => {
+            diesel::update(unread.filter(id.eq_any(notification_ids)))
+                .set(read_at.eq(now))
+                .execute(conn)
+        }
+        None => diesel::update(unread).set(read_at.eq(now)).execute(conn),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    // Not `super::*`: the `notifications` columns it brings in would shadow
+    // `assert_eq!`'s own bindings.
+    use super::{
+        count_unread, get_by_user_id, mark_read, notify_followers,
+        notify_users, notify_vote_threshold,
+    };
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        models::{
+            notification::NotificationKind,
+            question::{Question, ResultsVisibility},
+        },
+        schema::votes,
+        services,
+    };
+
+    fn follow(conn: &mut PgConnection, userid: Uuid, questionid: Uuid) {
+        services::bookmark::upsert(conn, userid, questionid, true).unwrap();
+    }
+
+    fn question_with(
+        conn: &mut PgConnection,
+        author: Uuid,
+        visibility: ResultsVisibility,
+    ) -> Question {
+        let questionid = create_test_question(conn, author);
+
+        services::question::update_results_visibility(
+            conn, questionid, visibility,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn each_threshold_reaches_followers_once() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let follower = create_test_user(conn);
+            let reader = create_test_user(conn);
+            let question =
+                question_with(conn, author, ResultsVisibility::Always);
+            follow(conn, follower, question.id);
+            // Bookmarking without following is only for coming back later.
+            services::bookmark::upsert(conn, reader, question.id, false)?;
+
+            assert_eq!(notify_vote_threshold(conn, &question, 10, "10")?, 1);
+            assert_eq!(notify_vote_threshold(conn, &question, 10, "10")?, 0);
+            assert_eq!(notify_vote_threshold(conn, &question, 5, "5")?, 0);
+            assert_eq!(notify_vote_threshold(conn, &question, 50, "50")?, 1);
+
+            let received: Vec<String> =
+                get_by_user_id(conn, follower, false, 10, None)?
+                    .into_iter()
+                    .map(|notification| notification.message)
+                    .collect();
+            assert_eq!(received.len(), 2);
+            assert!(received.contains(&"10".to_owned()));
+            assert!(received.contains(&"50".to_owned()));
+            assert_eq!(count_unread(conn, reader)?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn thresholds_skip_followers_who_cannot_see_results() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let voter = create_test_user(conn);
+            let onlooker = create_test_user(conn);
+            let question =
+                question_with(conn, author, ResultsVisibility::AfterVote);
+            for userid in [author, voter, onlooker] {
+                follow(conn, userid, question.id);
+            }
+            diesel::insert_into(votes::table)
+                .values((
+                    votes::user_id.eq(voter),
+                    votes::question_id.eq(question.id),
+                    votes::value.eq(1),
+                ))
+                .execute(conn)?;
+
+            assert_eq!(notify_vote_threshold(conn, &question, 10, "10")?, 2);
+            assert_eq!(count_unread(conn, author)?, 1);
+            assert_eq!(count_unread(conn, voter)?, 1);
+            assert_eq!(count_unread(conn, onlooker)?, 0);
+
+            // Once the onlooker may see the results, they hear of the
+            // threshold they missed, once.
+            let question = services::question::update_results_visibility(
+                conn,
+                question.id,
+                ResultsVisibility::Always,
+            )?;
+            assert_eq!(notify_vote_threshold(conn, &question, 10, "10")?, 1);
+            assert_eq!(notify_vote_threshold(conn, &question, 10, "10")?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn followers_hear_of_closing_until_they_read_it() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let follower = create_test_user(conn);
+            let question =
+                question_with(conn, author, ResultsVisibility::AuthorOnly);
+            follow(conn, follower, question.id);
+
+            assert_eq!(
+                notify_followers(
+                    conn,
+                    question.id,
+                    NotificationKind::QuestionClosed,
+                    "Closed."
+                )?,
+                1
+            );
+            notify_users(
+                conn,
+                &[follower],
+                None,
+                NotificationKind::ReportResolved,
+                "Resolved.",
+            )?;
+            let received = get_by_user_id(conn, follower, true, 10, None)?;
+            assert_eq!(received.len(), 2);
+
+            assert_eq!(mark_read(conn, follower, Some(&[received[0].id]))?, 1);
+            assert_eq!(count_unread(conn, follower)?, 1);
+            assert_eq!(mark_read(conn, follower, None)?, 1);
+            assert_eq!(mark_read(conn, follower, None)?, 0);
+            assert!(get_by_user_id(conn, follower, true, 10, None)?.is_empty());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use actix_web::web;
+use argon2::{ThreadMode, Variant, Version};
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+use crate::config::config;
+
+/// Stored instead of a hash for accounts registered through an external
+/// provider until they set a password. Never verifies.
+pub const UNUSABLE: &str = "!";
+
+fn argon2_config() -> argon2::Config<'static> {
+    let config = &config().password;
+
+    argon2::Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: config.memory_kib,
+        time_cost: config.iterations,
+        lanes: config.parallelism,
+        thread_mode: ThreadMode::from_threads(config.parallelism),
+        ..argon2::Config::default()
+    }
+}
+
+pub fn hash(plain: &str) -> String {
+    let salt: [u8; 16] = rand::thread_rng().gen();
+
+    argon2::hash_encoded(plain.as_bytes(), &salt, &argon2_config()).unwrap()
+}
+
+pub fn verify(encoded: &str, plain: &str) -> bool {
+    argon2::verify_encoded(encoded, plain.as_bytes()).unwrap_or(false)
+}
+
+/// Whether `encoded` was made with another variant or weaker parameters than
+/// the configured ones, and should be replaced on the next successful login.
+pub fn needs_rehash(encoded: &str) -> bool {
+    let config = &config().password;
+    let mut parts = encoded.split('$').skip(1);
+
+    if parts.next() != Some("argon2id") || parts.next() != Some("v=19") {
+        return true;
+    }
+
+    let params = parts.next().unwrap_or_default();
+    let param = |name: &str| {
+        params
+            .split(',')
+            .find_map(|p| p.strip_prefix(name)?.strip_prefix('=')?.parse().ok())
+            .unwrap_or(0)
+    };
+
+    param("m") < config.memory_kib
+        || param("t") < config.iterations
+        || param("p") != config.parallelism
+}
+
+/// Hashes on the blocking thread pool so a worker is not stalled.
+pub async fn hash_blocking(plain: String) -> String {
+    web::block(move || hash(&plain))
+        .await
+        .expect("Password hashing was cancelled.")
+}
+
+/// Verifies on the blocking thread pool so a worker is not stalled.
+pub async fn verify_blocking(encoded: String, plain: String) -> bool {
+    web::block(move || verify(&encoded, &plain))
+        .await
+        .unwrap_or(false)
+}
+
+/// Validates on the blocking thread pool, as the breached password corpus is
+/// read from disk. A cancelled check rejects the password.
+pub async fn validate_blocking(
+    plain: String,
+    user_inputs: Vec<String>,
+) -> Vec<String> {
+    web::block(move || {
+        let user_inputs: Vec<&str> =
+            user_inputs.iter().map(String::as_str).collect();
+
+        validate(&plain, &user_inputs)
+    })
+    .await
+    .unwrap_or_else(|_| {
+        vec!["Password could not be checked, please try again.".to_owned()]
+    })
+}
+
+/// Checks `plain` against the configured policy, returning what is wrong with
+/// it. `user_inputs` (username, email, ...) count against its strength.
+///
+/// `register` and `changePassword` both go through this, and so must any
+/// other way of setting a password.
+pub fn validate(plain: &str, user_inputs: &[&str]) -> Vec<String> {
+    let config = &config().password;
+    let mut problems = vec![];
+
+    if plain.chars().count() < config.min_length {
+        problems.push(format!(
+            "Password must be at least {} characters.",
+            config.min_length
+        ));
+        return problems;
+    }
+
+    if let Ok(entropy) = zxcvbn::zxcvbn(plain, user_inputs) {
+        if entropy.score() < config.min_score {
+            problems.push("Password is too easy to guess.".to_owned());
+
+            if let Some(feedback) = entropy.feedback() {
+                if let Some(warning) = feedback.warning() {
+                    problems.push(format!("{}", warning));
+                }
+                problems.extend(
+                    feedback.suggestions().iter().map(|s| s.to_string()),
+                );
+            }
+        }
+    }
+
+    if is_breached(plain) {
+        problems.push(
+            "Password appears in a known data breach. Please choose another."
+                .to_owned(),
+        );
+    }
+
+    problems
+}
+
+/// Looks `plain` up in the local breached password corpus, if configured.
+/// Only the range file for the first 5 hex characters of its SHA-1 is read.
+fn is_breached(plain: &str) -> bool {
+    let dir = match &config().password.breached_dir {
+        Some(dir) => dir,
+        None => return false,
+    };
+    let digest = format!("{:X}", Sha1::digest(plain.as_bytes()));
+    let (prefix, suffix) = digest.split_at(5);
+
+    let file = File::open(dir.join(prefix))
+        .or_else(|_| File::open(dir.join(format!("{}.txt", prefix))));
+
+    match file {
+        Ok(file) => {
+            BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .any(|line| match line.trim().split_once(':') {
+                    // Padding entries have a count of 0.
+                    Some((hash, count)) => {
+                        hash.eq_ignore_ascii_case(suffix) && count != "0"
+                    }
+                    None => line.trim().eq_ignore_ascii_case(suffix),
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_short_passwords() {
+        let problems = validate("Ab1!", &[]);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Password must be at least"));
+    }
+
+    #[test]
+    fn rejects_guessable_passwords() {
+        assert!(!validate("password1", &[]).is_empty());
+        assert!(!validate("jdoe2022jdoe", &["jdoe"]).is_empty());
+    }
+
+    #[test]
+    fn accepts_strong_passwords() {
+        assert!(validate("x7#Qp!vR2m&Lz9wK", &["jdoe"]).is_empty());
+    }
+
+    #[test]
+    fn verifies_only_its_own_hash() {
+        let encoded = hash("x7#Qp!vR2m&Lz9wK");
+
+        assert!(verify(&encoded, "x7#Qp!vR2m&Lz9wK"));
+        assert!(!verify(&encoded, "x7#Qp!vR2m&Lz9wk"));
+        assert!(!verify(UNUSABLE, ""));
+        assert!(!verify(UNUSABLE, UNUSABLE));
+        assert!(!needs_rehash(&encoded));
+    }
+}
@@ -1,53 +1,1000 @@
-use diesel::{dsl::sql, prelude::*, sql_types::Text};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    dsl::sql,
+    pg::Pg,
+    prelude::*,
+    sql_types::{self, Bool, Double, Integer, Nullable, Text, Timestamp},
+};
 use uuid::Uuid;
 
 use crate::{
-    models::question::{Question, QuestionInput},
-    schema::{self, questions},
+    config::config,
+    models::question::{
+        Question, QuestionInput, QuestionOption, QuestionSort, QuestionStatus,
+        ResultsVisibility, VoterVisibility,
+    },
+    models::user::User,
+    schema::{self, question_options, question_tags, questions, votes},
 };
 use schema::questions::dsl::*;
 
+/// Creates a question along with its options, listed in order, and its
+/// tags.
 pub fn create(
     conn: &mut PgConnection,
     new_question: QuestionInput,
+    option_labels: &[String],
+    tag_names: &[String],
 ) -> QueryResult<Question> {
-    diesel::insert_into(questions::table)
-        .values(&new_question)
-        .get_result(conn)
+    conn.transaction(|conn| {
+        let question: Question = diesel::insert_into(questions::table)
+            .values(&new_question)
+            .get_result(conn)?;
+        let options: Vec<_> = option_labels
+            .iter()
+            .enumerate()
+            .map(|(i, option_label)| {
+                (
+                    question_options::question_id.eq(question.id),
+                    question_options::position.eq(i as i32),
+                    question_options::label.eq(option_label),
+                )
+            })
+            .collect();
+
+        if !options.is_empty() {
+            diesel::insert_into(question_options::table)
+                .values(&options)
+                .execute(conn)?;
+        }
+
+        super::tag::set_for_question(conn, question.id, tag_names)?;
+
+        Ok(question)
+    })
+}
+
+pub fn get_options(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+) -> QueryResult<Vec<QuestionOption>> {
+    question_options::table
+        .filter(question_options::question_id.eq(question_uuid))
+        .order(question_options::position.asc())
+        .load(conn)
 }
 
 pub fn get_by_id(
     conn: &mut PgConnection,
     question_uuid: Uuid,
 ) -> QueryResult<Question> {
-    questions.find(question_uuid).first(conn)
+    questions
+        .find(question_uuid)
+        .filter(deleted_at.is_null())
+        .first(conn)
+}
+
+/// Gets a deleted question that can still be restored.
+pub fn get_deleted_by_id(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    deleted_after: NaiveDateTime,
+) -> QueryResult<Question> {
+    questions
+        .find(question_uuid)
+        .filter(deleted_at.gt(deleted_after))
+        .first(conn)
 }
 
 pub fn get_by_text(
     conn: &mut PgConnection,
-    question_text: &String,
+    question_text: &str,
+) -> QueryResult<Question> {
+    questions
+        .filter(text.eq(question_text))
+        .filter(deleted_at.is_null())
+        .first(conn)
+}
+
+/// Questions whose normalized text (lower case, without accents or
+/// punctuation) is at least `threshold` similar to `question_text`'s, most
+/// similar first. Drafts are left out, except the user's own.
+pub fn get_similar(
+    conn: &mut PgConnection,
+    question_text: &str,
+    userid: Uuid,
+    threshold: f32,
+    limit: i64,
+) -> QueryResult<Vec<Question>> {
+    conn.transaction(|conn| {
+        // `%` can use the trigram index, but only with this threshold.
+        diesel::sql_query(
+            "SELECT set_config('pg_trgm.similarity_threshold', $1, true)",
+        )
+        .bind::<Text, _>(threshold.to_string())
+        .execute(conn)?;
+
+        questions
+            .filter(deleted_at.is_null())
+            .filter(status.ne(QuestionStatus::Draft).or(user_id.eq(userid)))
+            .filter(
+                sql::<Bool>(
+                    "questions.normalized_text % normalize_question_text(",
+                )
+                .bind::<Text, _>(question_text.to_owned())
+                .sql(")"),
+            )
+            .order(
+                sql::<Double>(
+                    "similarity(questions.normalized_text, \
+                     normalize_question_text(",
+                )
+                .bind::<Text, _>(question_text.to_owned())
+                .sql(")) DESC"),
+            )
+            .limit(limit)
+            .load(conn)
+    })
+}
+
+/// Whether `user` may change the question: its author, or a moderator.
+pub fn can_edit(question: &Question, user: &User) -> bool {
+    question.user_id == user.id || user.can_moderate()
+}
+
+/// Whether the user `userid` may see who voted what on the question, or
+/// why not. Public voters are still subject to the results visibility.
+pub fn check_voters_visible(
+    question: &Question,
+    userid: Option<Uuid>,
+) -> Result<(), &'static str> {
+    match question.voter_visibility {
+        VoterVisibility::Anonymous => Err("Voters are anonymous."),
+        VoterVisibility::Author if userid != Some(question.user_id) => {
+            Err("Only the author can see the voters.")
+        }
+        VoterVisibility::Author | VoterVisibility::Public => Ok(()),
+    }
+}
+
+pub fn update_text(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    new_text: &str,
+) -> QueryResult<Question> {
+    diesel::update(questions.find(question_uuid))
+        .set((text.eq(new_text), updated_at.eq(diesel::dsl::now)))
+        .get_result(conn)
+}
+
+/// Sets the question's description, in markdown and rendered, and its
+/// source.
+pub fn update_description(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    new_description: Option<&str>,
+    new_description_html: Option<&str>,
+    new_source_url: Option<&str>,
+) -> QueryResult<Question> {
+    diesel::update(questions.find(question_uuid))
+        .set((
+            description.eq(new_description),
+            description_html.eq(new_description_html),
+            source_url.eq(new_source_url),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+pub fn update_status(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    new_status: QuestionStatus,
+    new_opens_at: Option<NaiveDateTime>,
+    new_closes_at: Option<NaiveDateTime>,
+) -> QueryResult<Question> {
+    diesel::update(questions.find(question_uuid))
+        .set((
+            status.eq(new_status),
+            opens_at.eq(new_opens_at),
+            closes_at.eq(new_closes_at),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+pub fn update_results_visibility(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    visibility: ResultsVisibility,
+) -> QueryResult<Question> {
+    diesel::update(questions.find(question_uuid))
+        .set((
+            results_visibility.eq(visibility),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+pub fn update_voter_visibility(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    visibility: VoterVisibility,
 ) -> QueryResult<Question> {
-    questions.filter(text.like(question_text)).first(conn)
+    diesel::update(questions.find(question_uuid))
+        .set((
+            voter_visibility.eq(visibility),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+/// Opens drafts whose opening time has passed.
+pub fn open_scheduled(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> QueryResult<Vec<Question>> {
+    diesel::update(
+        questions
+            .filter(status.eq(QuestionStatus::Draft))
+            .filter(opens_at.le(now))
+            .filter(deleted_at.is_null()),
+    )
+    .set(status.eq(QuestionStatus::Open))
+    .get_results(conn)
+}
+
+/// Closes open questions whose closing time has passed.
+pub fn close_expired(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> QueryResult<Vec<Question>> {
+    diesel::update(
+        questions
+            .filter(status.eq(QuestionStatus::Open))
+            .filter(closes_at.le(now))
+            .filter(deleted_at.is_null()),
+    )
+    .set(status.eq(QuestionStatus::Closed))
+    .get_results(conn)
 }
 
+/// Marks the question as deleted. Its votes are kept so it can be restored.
+pub fn delete(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+) -> QueryResult<usize> {
+    diesel::update(questions.find(question_uuid).filter(deleted_at.is_null()))
+        .set(deleted_at.eq(diesel::dsl::now))
+        .execute(conn)
+}
+
+/// Marks every question by the user as deleted, at the same time so they
+/// can be restored together.
 pub fn delete_all_by_user_id(
     conn: &mut PgConnection,
     userid: Uuid,
+    at: NaiveDateTime,
 ) -> QueryResult<usize> {
-    diesel::delete(questions.filter(user_id.eq(userid))).execute(conn)
+    diesel::update(
+        questions
+            .filter(user_id.eq(userid))
+            .filter(deleted_at.is_null()),
+    )
+    .set(deleted_at.eq(at))
+    .execute(conn)
 }
 
+/// Hides the question until a moderator reviews it, or shows it again.
+pub fn set_hidden(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+    hidden: bool,
+) -> QueryResult<usize> {
+    let at = hidden.then(|| Utc::now().naive_utc());
+
+    diesel::update(questions.find(question_uuid))
+        .set(hidden_at.eq(at))
+        .execute(conn)
+}
+
+pub fn restore(
+    conn: &mut PgConnection,
+    question_uuid: Uuid,
+) -> QueryResult<Question> {
+    diesel::update(questions.find(question_uuid))
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .get_result(conn)
+}
+
+/// Restores the user's questions deleted along with their account.
+pub fn restore_all_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    at: NaiveDateTime,
+) -> QueryResult<usize> {
+    diesel::update(
+        questions
+            .filter(user_id.eq(userid))
+            .filter(deleted_at.eq(at)),
+    )
+    .set(deleted_at.eq(None::<NaiveDateTime>))
+    .execute(conn)
+}
+
+/// Hard deletes questions deleted before `before`, with their votes.
+pub fn purge_deleted(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let purged = questions.filter(deleted_at.lt(before)).select(id);
+
+        diesel::delete(votes::table.filter(votes::question_id.eq_any(purged)))
+            .execute(conn)?;
+        diesel::delete(questions.filter(deleted_at.lt(before))).execute(conn)
+    })
+}
+
+/// Gets questions open to voters, most voted first. With `tag_ids`, only
+/// questions with all of those tags.
 pub fn get_paginated(
     conn: &mut PgConnection,
     limit: i32,
     cursor: Option<Uuid>,
+    tag_ids: &[Uuid],
 ) -> QueryResult<Vec<Question>> {
-    let mut query = questions.into_boxed();
+    let mut query = questions
+        .filter(deleted_at.is_null())
+        .filter(hidden_at.is_null())
+        .filter(status.ne(QuestionStatus::Draft))
+        .into_boxed();
     if let Some(cursor) = cursor {
         query = query.filter(id.lt(cursor));
     }
+    for tagid in tag_ids {
+        query = query.filter(
+            id.eq_any(
+                question_tags::table
+                    .filter(question_tags::tag_id.eq(*tagid))
+                    .select(question_tags::question_id),
+            ),
+        );
+    }
     query
         .order_by(sql::<Text>("(SELECT COUNT(value) FROM votes WHERE votes.question_id = questions.id) DESC"))
         .limit(limit as i64)
         .load(conn)
 }
+
+/// Gets the home feed: questions open to voters, trending ones mixed with
+/// those by users `userid` follows, `limit` at a time after the `cursor`
+/// question. Each comes with whether its author is followed. With
+/// `exclude_voted`, questions the user voted on are left out.
+///
+/// Scores decay as time passes, so they are computed as of `at`: pass the
+/// first page's time along with the cursor and a question keeps its place.
+pub fn get_feed(
+    conn: &mut PgConnection,
+    userid: Option<Uuid>,
+    exclude_voted: bool,
+    limit: i32,
+    at: NaiveDateTime,
+    cursor: Option<Uuid>,
+) -> QueryResult<Vec<(Question, bool)>> {
+    let mut query = questions
+        .select((questions::all_columns, by_followed_user(userid)))
+        .filter(deleted_at.is_null())
+        .filter(hidden_at.is_null())
+        .filter(status.ne(QuestionStatus::Draft))
+        .into_boxed();
+
+    if let (Some(userid), true) = (userid, exclude_voted) {
+        query = query.filter(diesel::dsl::not(
+            id.eq_any(
+                votes::table
+                    .filter(votes::user_id.eq(userid))
+                    .select(votes::question_id),
+            ),
+        ));
+    }
+
+    if let Some(cursor) = cursor {
+        let cursor_key: f64 = questions
+            .find(cursor)
+            .select(feed_score(userid, at))
+            .first(conn)?;
+
+        query = query.filter(
+            feed_score(userid, at)
+                .lt(cursor_key)
+                .or(feed_score(userid, at).eq(cursor_key).and(id.lt(cursor))),
+        );
+    }
+
+    query
+        .order((feed_score(userid, at).desc(), id.desc()))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+fn by_followed_user(
+    userid: Option<Uuid>,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>(
+            "questions.user_id IN \
+             (SELECT followee_id FROM follows WHERE follower_id = ",
+        )
+        .bind::<Nullable<sql_types::Uuid>, _>(userid)
+        .sql(")"),
+    )
+}
+
+/// Votes in the days before `at`, plus the boost of followed authors, over a
+/// power of the question's age in hours at `at`, so new questions get their
+/// chance.
+fn feed_score(
+    userid: Option<Uuid>,
+    at: NaiveDateTime,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Double>> {
+    let config = &config().feed;
+
+    Box::new(
+        sql::<Double>(
+            "((SELECT COUNT(*) FROM votes \
+             WHERE votes.question_id = questions.id \
+             AND votes.created_at <= ",
+        )
+        .bind::<Timestamp, _>(at)
+        .sql(" AND votes.created_at > ")
+        .bind::<Timestamp, _>(at)
+        .sql(" - make_interval(days => ")
+        .bind::<Integer, _>(config.trending_days)
+        .sql(
+            "))::float8 + 1 + CASE WHEN questions.user_id IN \
+             (SELECT followee_id FROM follows WHERE follower_id = ",
+        )
+        .bind::<Nullable<sql_types::Uuid>, _>(userid)
+        .sql(") THEN ")
+        .bind::<Double, _>(config.followed_boost)
+        .sql(" ELSE 0 END) / power(extract(epoch FROM ")
+        .bind::<Timestamp, _>(at)
+        .sql(
+            " - COALESCE(questions.opens_at, questions.created_at))::float8 \
+             / 3600 + 2, 1.5)",
+        ),
+    )
+}
+
+/// A feed cursor: the time the feed is scored at, in milliseconds, and the
+/// last question's id.
+pub fn feed_cursor(at: NaiveDateTime, questionid: Uuid) -> String {
+    format!("{}_{}", at.timestamp_millis(), questionid)
+}
+
+/// The time and question id in a cursor made by `feed_cursor`.
+pub fn parse_feed_cursor(cursor: &str) -> Option<(NaiveDateTime, Uuid)> {
+    let (millis, questionid) = cursor.split_once('_')?;
+
+    Some((
+        feed_time(millis.parse().ok()?)?,
+        Uuid::parse_str(questionid).ok()?,
+    ))
+}
+
+/// `millis` since the epoch, which is all a feed cursor keeps of a time.
+pub fn feed_time(millis: i64) -> Option<NaiveDateTime> {
+    NaiveDateTime::from_timestamp_opt(
+        millis.div_euclid(1000),
+        millis.rem_euclid(1000) as u32 * 1_000_000,
+    )
+}
+
+/// Wraps matches in headlines. Neither can be part of a question's text, so
+/// headlines are escaped before they are turned into tags.
+const MATCH_START: &str = "\u{2}";
+const MATCH_END: &str = "\u{3}";
+
+/// Searches questions, stemming both English and French, with web search
+/// syntax (`"a phrase"`, `or`, `-excluded`). Returns each match with its rank
+/// and highlighted text, `limit` at a time after the `cursor` question.
+pub fn search(
+    conn: &mut PgConnection,
+    search_text: &str,
+    sort: QuestionSort,
+    limit: i32,
+    cursor: Option<Uuid>,
+) -> QueryResult<Vec<(Question, f64, String)>> {
+    let mut query = questions
+        .select((
+            questions::all_columns,
+            search_rank(search_text),
+            search_headline(search_text),
+        ))
+        .filter(deleted_at.is_null())
+        .filter(hidden_at.is_null())
+        .filter(status.ne(QuestionStatus::Draft))
+        .filter(search_matches(search_text))
+        .into_boxed();
+
+    if let Some(cursor) = cursor {
+        let cursor_key: f64 = questions
+            .find(cursor)
+            .select(sort_key(sort, search_text))
+            .first(conn)?;
+
+        query = query.filter(
+            sort_key(sort, search_text).lt(cursor_key).or(sort_key(
+                sort,
+                search_text,
+            )
+            .eq(cursor_key)
+            .and(id.lt(cursor))),
+        );
+    }
+
+    let results: Vec<(Question, f64, String)> = query
+        .order((sort_key(sort, search_text).desc(), id.desc()))
+        .limit(limit as i64)
+        .load(conn)?;
+
+    Ok(results
+        .into_iter()
+        .map(|(question, rank, headline)| {
+            (question, rank, highlight(&headline))
+        })
+        .collect())
+}
+
+fn search_matches(
+    search_text: &str,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>(
+            "questions.search_vector @@ \
+             (websearch_to_tsquery('english', ",
+        )
+        .bind::<Text, _>(search_text.to_owned())
+        .sql(") || websearch_to_tsquery('french', ")
+        .bind::<Text, _>(search_text.to_owned())
+        .sql("))"),
+    )
+}
+
+fn search_rank(
+    search_text: &str,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Double>> {
+    Box::new(
+        sql::<Double>(
+            "ts_rank(questions.search_vector, \
+             websearch_to_tsquery('english', ",
+        )
+        .bind::<Text, _>(search_text.to_owned())
+        .sql(") || websearch_to_tsquery('french', ")
+        .bind::<Text, _>(search_text.to_owned())
+        .sql("))::float8"),
+    )
+}
+
+/// Highlights the question's text in whichever language it matches,
+/// preferring English.
+fn search_headline(
+    search_text: &str,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Text>> {
+    let options = format!(
+        "StartSel={MATCH_START}, StopSel={MATCH_END}, HighlightAll=true"
+    );
+
+    Box::new(
+        sql::<Text>(
+            "CASE WHEN to_tsvector('english', questions.text) @@ \
+             websearch_to_tsquery('english', ",
+        )
+        .bind::<Text, _>(search_text.to_owned())
+        .sql(
+            ") THEN ts_headline('english', questions.text, \
+             websearch_to_tsquery('english', ",
+        )
+        .bind::<Text, _>(search_text.to_owned())
+        .sql("), ")
+        .bind::<Text, _>(options.clone())
+        .sql(
+            ") ELSE ts_headline('french', questions.text, \
+             websearch_to_tsquery('french', ",
+        )
+        .bind::<Text, _>(search_text.to_owned())
+        .sql("), ")
+        .bind::<Text, _>(options)
+        .sql(") END"),
+    )
+}
+
+fn sort_key(
+    sort: QuestionSort,
+    search_text: &str,
+) -> Box<dyn BoxableExpression<questions::table, Pg, SqlType = Double>> {
+    match sort {
+        QuestionSort::Relevance => search_rank(search_text),
+        QuestionSort::Popular => Box::new(sql::<Double>(
+            "(SELECT COUNT(*) FROM votes \
+             WHERE votes.question_id = questions.id)::float8",
+        )),
+    }
+}
+
+fn highlight(headline: &str) -> String {
+    headline
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        schema::users,
+    };
+
+    fn get_user(conn: &mut PgConnection, userid: Uuid) -> User {
+        users::table.find(userid).first(conn).unwrap()
+    }
+
+    #[test]
+    fn only_authors_and_moderators_can_edit() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let other = create_test_user(conn);
+            let moderator = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let question = get_by_id(conn, questionid)?;
+
+            diesel::update(users::table.find(moderator))
+                .set(users::role.eq("moderator"))
+                .execute(conn)?;
+
+            assert!(can_edit(&question, &get_user(conn, author)));
+            assert!(!can_edit(&question, &get_user(conn, other)));
+            // Moderators need TOTP before they can use their role.
+            assert!(!can_edit(&question, &get_user(conn, moderator)));
+
+            diesel::update(users::table.find(moderator))
+                .set(users::totp_enabled.eq(true))
+                .execute(conn)?;
+            assert!(can_edit(&question, &get_user(conn, moderator)));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn voters_are_visible_as_the_author_chose() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let other = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let question = get_by_id(conn, questionid)?;
+
+            // Questions start anonymous, even to their author.
+            assert_eq!(question.voter_visibility, VoterVisibility::Anonymous);
+            assert!(check_voters_visible(&question, Some(author)).is_err());
+
+            let question = update_voter_visibility(
+                conn,
+                questionid,
+                VoterVisibility::Author,
+            )?;
+            assert!(check_voters_visible(&question, Some(author)).is_ok());
+            assert!(check_voters_visible(&question, Some(other)).is_err());
+            assert!(check_voters_visible(&question, None).is_err());
+
+            let question = update_voter_visibility(
+                conn,
+                questionid,
+                VoterVisibility::Public,
+            )?;
+            assert!(check_voters_visible(&question, Some(other)).is_ok());
+            assert!(check_voters_visible(&question, None).is_ok());
+            Ok(())
+        });
+    }
+
+    /// Creates a question reading `question_text` with a word no other
+    /// test uses, returning its id and that word.
+    fn searchable_question(
+        conn: &mut PgConnection,
+        userid: Uuid,
+        question_text: &str,
+    ) -> (Uuid, String) {
+        let word = format!("zq{}", Uuid::new_v4().simple());
+        let questionid = create_test_question(conn, userid);
+
+        update_text(conn, questionid, &format!("{question_text} {word}"))
+            .unwrap();
+        (questionid, word)
+    }
+
+    #[test]
+    fn search_stems_english_and_french() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let (english, word) =
+                searchable_question(conn, author, "Should cities ban cars?");
+            let (french, mot) = searchable_question(
+                conn,
+                author,
+                "Faut-il interdire les voitures ?",
+            );
+
+            let found = search(
+                conn,
+                &format!("banning {word}"),
+                QuestionSort::Relevance,
+                10,
+                None,
+            )?;
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].0.id, english);
+            assert!(found[0].2.contains("<mark>ban</mark>"));
+
+            let found = search(
+                conn,
+                &format!("voiture {mot}"),
+                QuestionSort::Relevance,
+                10,
+                None,
+            )?;
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].0.id, french);
+            assert!(found[0].2.contains("<mark>voitures</mark>"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn search_leaves_out_drafts_hidden_and_deleted_questions() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let (_, word) = searchable_question(conn, author, "Visible?");
+            let add = |conn: &mut PgConnection, label: &str| {
+                let questionid = create_test_question(conn, author);
+                update_text(conn, questionid, &format!("{label} {word}"))
+                    .unwrap();
+                questionid
+            };
+
+            let draft = add(conn, "Draft?");
+            update_status(conn, draft, QuestionStatus::Draft, None, None)?;
+            let hidden = add(conn, "Hidden?");
+            set_hidden(conn, hidden, true)?;
+            let deleted = add(conn, "Deleted?");
+            diesel::update(questions.find(deleted))
+                .set(deleted_at.eq(diesel::dsl::now))
+                .execute(conn)?;
+
+            let found = search(conn, &word, QuestionSort::Relevance, 10, None)?;
+            assert_eq!(found.len(), 1);
+            assert!(found[0].0.text.starts_with("Visible?"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn search_pages_through_every_match_once() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let (_, word) = searchable_question(conn, author, "First?");
+            for i in 0..4 {
+                let questionid = create_test_question(conn, author);
+                update_text(conn, questionid, &format!("More {i}? {word}"))?;
+            }
+
+            let mut seen = vec![];
+            let mut cursor = None;
+            loop {
+                let page =
+                    search(conn, &word, QuestionSort::Relevance, 2, cursor)?;
+                if page.is_empty() {
+                    break;
+                }
+                cursor = page.last().map(|(question, _, _)| question.id);
+                seen.extend(
+                    page.into_iter().map(|(question, _, _)| question.id),
+                );
+            }
+
+            let mut unique = seen.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(seen.len(), 5);
+            assert_eq!(unique.len(), 5);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn highlights_are_escaped() {
+        assert_eq!(
+            highlight("<b>\u{2}Tom & Jerry\u{3}</b>"),
+            "&lt;b&gt;<mark>Tom &amp; Jerry</mark>&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn updates_change_only_their_question() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let untouchedid = create_test_question(conn, author);
+            let untouched = get_by_id(conn, untouchedid)?;
+            // Whole seconds, as Postgres keeps microseconds.
+            let closes =
+                NaiveDateTime::from_timestamp_opt(Utc::now().timestamp(), 0)
+                    .unwrap()
+                    + chrono::Duration::days(1);
+
+            let question = update_status(
+                conn,
+                questionid,
+                QuestionStatus::Closed,
+                None,
+                Some(closes),
+            )?;
+            assert_eq!(question.status, QuestionStatus::Closed);
+            assert_eq!(question.closes_at, Some(closes));
+
+            let question = update_text(conn, questionid, "Edited?")?;
+            assert_eq!(question.text, "Edited?");
+            assert_eq!(question.status, QuestionStatus::Closed);
+
+            let other = get_by_id(conn, untouched.id)?;
+            assert_eq!(other.text, untouched.text);
+            assert_eq!(other.status, untouched.status);
+            Ok(())
+        });
+    }
+
+    fn schedule(
+        conn: &mut PgConnection,
+        questionid: Uuid,
+        new_status: QuestionStatus,
+        new_opens_at: Option<NaiveDateTime>,
+        new_closes_at: Option<NaiveDateTime>,
+    ) {
+        diesel::update(questions.find(questionid))
+            .set((
+                status.eq(new_status),
+                opens_at.eq(new_opens_at),
+                closes_at.eq(new_closes_at),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+
+    #[test]
+    fn open_scheduled_opens_due_drafts() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let now = Utc::now().naive_utc();
+            let due = create_test_question(conn, author);
+            let later = create_test_question(conn, author);
+            let deleted = create_test_question(conn, author);
+            let unscheduled = create_test_question(conn, author);
+            let past = Some(now - chrono::Duration::minutes(1));
+
+            schedule(conn, due, QuestionStatus::Draft, past, None);
+            schedule(
+                conn,
+                later,
+                QuestionStatus::Draft,
+                Some(now + chrono::Duration::minutes(1)),
+                None,
+            );
+            schedule(conn, deleted, QuestionStatus::Draft, past, None);
+            delete(conn, deleted)?;
+            schedule(conn, unscheduled, QuestionStatus::Draft, None, None);
+
+            let opened: Vec<Uuid> =
+                open_scheduled(conn, now)?.iter().map(|q| q.id).collect();
+            assert!(opened.contains(&due));
+            assert!(!opened.contains(&later));
+            assert!(!opened.contains(&deleted));
+            assert!(!opened.contains(&unscheduled));
+            assert_eq!(get_by_id(conn, due)?.status, QuestionStatus::Open);
+            assert_eq!(get_by_id(conn, later)?.status, QuestionStatus::Draft);
+
+            // Already open, so not opened again.
+            let opened: Vec<Uuid> =
+                open_scheduled(conn, now)?.iter().map(|q| q.id).collect();
+            assert!(!opened.contains(&due));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn close_expired_closes_open_questions_past_their_deadline() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let now = Utc::now().naive_utc();
+            let expired = create_test_question(conn, author);
+            let running = create_test_question(conn, author);
+            let draft = create_test_question(conn, author);
+            let past = Some(now - chrono::Duration::minutes(1));
+
+            schedule(conn, expired, QuestionStatus::Open, None, past);
+            schedule(
+                conn,
+                running,
+                QuestionStatus::Open,
+                None,
+                Some(now + chrono::Duration::minutes(1)),
+            );
+            schedule(conn, draft, QuestionStatus::Draft, None, past);
+
+            let closed: Vec<Uuid> =
+                close_expired(conn, now)?.iter().map(|q| q.id).collect();
+            assert!(closed.contains(&expired));
+            assert!(!closed.contains(&running));
+            assert!(!closed.contains(&draft));
+            assert_eq!(
+                get_by_id(conn, expired)?.status,
+                QuestionStatus::Closed
+            );
+            assert_eq!(get_by_id(conn, running)?.status, QuestionStatus::Open);
+            assert_eq!(get_by_id(conn, draft)?.status, QuestionStatus::Draft);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn deleted_questions_are_gone_until_restored() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+
+            assert_eq!(delete(conn, questionid)?, 1);
+            assert_eq!(delete(conn, questionid)?, 0);
+            assert!(get_by_id(conn, questionid).is_err());
+
+            restore(conn, questionid)?;
+            assert!(get_by_id(conn, questionid).is_ok());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn feed_cursors_round_trip() {
+        let at = feed_time(1_671_000_123_456).unwrap();
+        let questionid = Uuid::new_v4();
+
+        assert_eq!(
+            parse_feed_cursor(&feed_cursor(at, questionid)),
+            Some((at, questionid))
+        );
+    }
+
+    #[test]
+    fn feed_times_keep_milliseconds() {
+        assert_eq!(
+            feed_time(1_671_000_123_456).unwrap().timestamp_millis(),
+            1_671_000_123_456
+        );
+        assert_eq!(feed_time(-1).unwrap().timestamp_millis(), -1);
+    }
+
+    #[test]
+    fn rejects_malformed_feed_cursors() {
+        let questionid = Uuid::new_v4();
+
+        assert_eq!(parse_feed_cursor(&questionid.to_string()), None);
+        assert_eq!(parse_feed_cursor(&format!("soon_{}", questionid)), None);
+        assert_eq!(parse_feed_cursor("1671000123456_"), None);
+        assert_eq!(parse_feed_cursor(""), None);
+    }
+}
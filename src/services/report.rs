@@ -0,0 +1,542 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    models::report::{
+        ModerationAction, ModerationActionInput, ModerationActionKind, Report,
+        ReportInput, ReportStatus, ReportTargetType,
+    },
+    schema::{self, comments, moderation_actions, questions},
+    services,
+};
+use schema::reports::dsl::*;
+
+/// Files a report, hiding its target once it has `hide_threshold` pending
+/// reports.
+pub fn create(
+    conn: &mut PgConnection,
+    new_report: ReportInput,
+    hide_threshold: i64,
+) -> QueryResult<Report> {
+    conn.transaction(|conn| {
+        let report: Report = diesel::insert_into(reports)
+            .values(&new_report)
+            .get_result(conn)?;
+
+        let pending = count_pending_for_target(
+            conn,
+            report.target_type,
+            report.target_id,
+        )?;
+        if pending < hide_threshold
+            || is_target_hidden(conn, report.target_type, report.target_id)?
+        {
+            return Ok(report);
+        }
+
+        set_target_hidden(conn, report.target_type, report.target_id, true)?;
+        log_action(
+            conn,
+            ModerationActionInput {
+                moderator_id: None,
+                action: ModerationActionKind::Hide,
+                target_type: report.target_type,
+                target_id: report.target_id,
+                report_id: Some(report.id),
+                note: Some(format!("{} pending reports", pending)),
+            },
+        )?;
+
+        Ok(report)
+    })
+}
+
+pub fn get_by_id(
+    conn: &mut PgConnection,
+    reportid: Uuid,
+) -> QueryResult<Report> {
+    reports.find(reportid).first(conn)
+}
+
+/// The report the user already filed against the content, if any.
+pub fn get_by_reporter(
+    conn: &mut PgConnection,
+    reporterid: Uuid,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<Option<Report>> {
+    reports
+        .filter(reporter_id.eq(reporterid))
+        .filter(target_type.eq(targettype))
+        .filter(target_id.eq(targetid))
+        .first(conn)
+        .optional()
+}
+
+pub fn count_pending_for_target(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<i64> {
+    reports
+        .filter(target_type.eq(targettype))
+        .filter(target_id.eq(targetid))
+        .filter(status.eq(ReportStatus::Pending))
+        .count()
+        .get_result(conn)
+}
+
+/// The moderation queue: pending reports, oldest first, after the `cursor`
+/// report if given.
+pub fn get_pending(
+    conn: &mut PgConnection,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<Report>> {
+    let mut query = reports
+        .filter(status.eq(ReportStatus::Pending))
+        .into_boxed();
+
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            created_at
+                .gt(cursor_at)
+                .or(created_at.eq(cursor_at).and(id.gt(cursor_id))),
+        );
+    }
+
+    query
+        .order((created_at.asc(), id.asc()))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+/// Resolves a pending report as the moderator decided, and logs it:
+/// - `Approved` shows the target again and resolves every pending report on
+///   it,
+/// - `Removed` deletes the target and resolves every pending report on it,
+/// - `Dismissed` resolves this report only, showing the target again if
+///   fewer than `hide_threshold` pending reports remain.
+///
+/// Returns the resolved reports, none if the report was resolved already.
+pub fn resolve(
+    conn: &mut PgConnection,
+    report: &Report,
+    new_status: ReportStatus,
+    moderatorid: Uuid,
+    moderator_note: Option<String>,
+    hide_threshold: i64,
+) -> QueryResult<Vec<Report>> {
+    conn.transaction(|conn| {
+        let pending = reports
+            .filter(target_type.eq(report.target_type))
+            .filter(target_id.eq(report.target_id))
+            .filter(status.eq(ReportStatus::Pending));
+        let resolution = (
+            status.eq(new_status),
+            resolved_at.eq(Utc::now().naive_utc()),
+            resolved_by.eq(moderatorid),
+        );
+
+        let resolved: Vec<Report> = match new_status {
+            ReportStatus::Approved | ReportStatus::Removed => {
+                diesel::update(pending).set(resolution).get_results(conn)?
+            }
+            _ => diesel::update(pending.filter(id.eq(report.id)))
+                .set(resolution)
+                .get_results(conn)?,
+        };
+        if !resolved.iter().any(|resolved| resolved.id == report.id) {
+            return Ok(vec![]);
+        }
+
+        let action = match new_status {
+            ReportStatus::Approved => {
+                set_target_hidden(
+                    conn,
+                    report.target_type,
+                    report.target_id,
+                    false,
+                )?;
+                ModerationActionKind::Approve
+            }
+            ReportStatus::Removed => {
+                remove_target(conn, report.target_type, report.target_id)?;
+                ModerationActionKind::Remove
+            }
+            _ => {
+                let remaining = count_pending_for_target(
+                    conn,
+                    report.target_type,
+                    report.target_id,
+                )?;
+                if remaining < hide_threshold {
+                    set_target_hidden(
+                        conn,
+                        report.target_type,
+                        report.target_id,
+                        false,
+                    )?;
+                }
+                ModerationActionKind::Dismiss
+            }
+        };
+
+        log_action(
+            conn,
+            ModerationActionInput {
+                moderator_id: Some(moderatorid),
+                action,
+                target_type: report.target_type,
+                target_id: report.target_id,
+                report_id: Some(report.id),
+                note: moderator_note,
+            },
+        )?;
+
+        Ok(resolved)
+    })
+}
+
+/// Whether a moderator removed the target, which must then not be restored.
+/// The audit trail is checked, as reports go away with their reporter.
+pub fn is_removed(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        moderation_actions::table
+            .filter(moderation_actions::target_type.eq(targettype))
+            .filter(moderation_actions::target_id.eq(targetid))
+            .filter(
+                moderation_actions::action.eq(ModerationActionKind::Remove),
+            ),
+    ))
+    .get_result(conn)
+}
+
+/// The question the target is, or is about, deleted or not.
+pub fn get_target_question_id(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<Uuid> {
+    match targettype {
+        ReportTargetType::Question => Ok(targetid),
+        ReportTargetType::Comment => comments::table
+            .find(targetid)
+            .select(comments::question_id)
+            .first(conn),
+    }
+}
+
+fn is_target_hidden(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<bool> {
+    let hidden: Option<NaiveDateTime> = match targettype {
+        ReportTargetType::Question => questions::table
+            .find(targetid)
+            .select(questions::hidden_at)
+            .first(conn)?,
+        ReportTargetType::Comment => comments::table
+            .find(targetid)
+            .select(comments::hidden_at)
+            .first(conn)?,
+    };
+
+    Ok(hidden.is_some())
+}
+
+fn set_target_hidden(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+    hidden: bool,
+) -> QueryResult<usize> {
+    match targettype {
+        ReportTargetType::Question => {
+            services::question::set_hidden(conn, targetid, hidden)
+        }
+        ReportTargetType::Comment => {
+            services::comment::set_hidden(conn, targetid, hidden)
+        }
+    }
+}
+
+/// Deletes the target, hidden so that it stays out of sight should it be
+/// restored some other way.
+fn remove_target(
+    conn: &mut PgConnection,
+    targettype: ReportTargetType,
+    targetid: Uuid,
+) -> QueryResult<()> {
+    set_target_hidden(conn, targettype, targetid, true)?;
+
+    match targettype {
+        ReportTargetType::Question => {
+            services::question::delete(conn, targetid).map(|_| ())
+        }
+        ReportTargetType::Comment => {
+            services::comment::delete(conn, targetid).map(|_| ())
+        }
+    }
+}
+
+/// Adds an entry to the moderation audit trail.
+pub fn log_action(
+    conn: &mut PgConnection,
+    new_action: ModerationActionInput,
+) -> QueryResult<ModerationAction> {
+    diesel::insert_into(moderation_actions::table)
+        .values(&new_action)
+        .get_result(conn)
+}
+
+/// The moderation audit trail, newest first, after the `cursor` action if
+/// given, only about `targetid` if given.
+pub fn get_actions(
+    conn: &mut PgConnection,
+    targetid: Option<Uuid>,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<ModerationAction>> {
+    let mut query = moderation_actions::table.into_boxed();
+
+    if let Some(targetid) = targetid {
+        query = query.filter(moderation_actions::target_id.eq(targetid));
+    }
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            moderation_actions::created_at.lt(cursor_at).or(
+                moderation_actions::created_at
+                    .eq(cursor_at)
+                    .and(moderation_actions::id.lt(cursor_id)),
+            ),
+        );
+    }
+
+    query
+        .order((
+            moderation_actions::created_at.desc(),
+            moderation_actions::id.desc(),
+        ))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+pub fn get_action_by_id(
+    conn: &mut PgConnection,
+    actionid: Uuid,
+) -> QueryResult<ModerationAction> {
+    moderation_actions::table.find(actionid).first(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        models::{comment::CommentInput, report::ReportReason},
+    };
+
+    const THRESHOLD: i64 = 2;
+
+    /// Reports the target by as many new users as `reporters`.
+    fn report(
+        conn: &mut PgConnection,
+        targettype: ReportTargetType,
+        targetid: Uuid,
+        reporters: usize,
+    ) -> Vec<Report> {
+        (0..reporters)
+            .map(|_| {
+                let reporterid = create_test_user(conn);
+
+                create(
+                    conn,
+                    ReportInput {
+                        reporter_id: reporterid,
+                        target_type: targettype,
+                        target_id: targetid,
+                        reason: ReportReason::Spam,
+                        note: None,
+                    },
+                    THRESHOLD,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn actions_on(
+        conn: &mut PgConnection,
+        targetid: Uuid,
+    ) -> Vec<ModerationActionKind> {
+        get_actions(conn, Some(targetid), 10, None)
+            .unwrap()
+            .into_iter()
+            .map(|logged| logged.action)
+            .collect()
+    }
+
+    fn question_hidden(conn: &mut PgConnection, questionid: Uuid) -> bool {
+        is_target_hidden(conn, ReportTargetType::Question, questionid).unwrap()
+    }
+
+    #[test]
+    fn enough_pending_reports_hide_their_target_once() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+
+            report(conn, ReportTargetType::Question, questionid, 1);
+            assert!(!question_hidden(conn, questionid));
+
+            report(conn, ReportTargetType::Question, questionid, 2);
+            assert!(question_hidden(conn, questionid));
+            // Hidden by nobody, once.
+            let logged = get_actions(conn, Some(questionid), 10, None)?;
+            assert_eq!(logged.len(), 1);
+            assert_eq!(logged[0].action, ModerationActionKind::Hide);
+            assert_eq!(logged[0].moderator_id, None);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn approving_shows_the_target_and_closes_every_report() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let moderator = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let filed = report(conn, ReportTargetType::Question, questionid, 3);
+
+            let resolved = resolve(
+                conn,
+                &filed[0],
+                ReportStatus::Approved,
+                moderator,
+                None,
+                THRESHOLD,
+            )?;
+            assert_eq!(resolved.len(), 3);
+            assert!(resolved.iter().all(|resolved| {
+                resolved.status == ReportStatus::Approved
+                    && resolved.resolved_by == Some(moderator)
+            }));
+            assert!(!question_hidden(conn, questionid));
+            assert_eq!(
+                count_pending_for_target(
+                    conn,
+                    ReportTargetType::Question,
+                    questionid
+                )?,
+                0
+            );
+
+            // The other reports were resolved along with it.
+            assert!(resolve(
+                conn,
+                &filed[1],
+                ReportStatus::Dismissed,
+                moderator,
+                None,
+                THRESHOLD
+            )?
+            .is_empty());
+            // Both were logged in this transaction, so in no set order.
+            let logged = actions_on(conn, questionid);
+            assert_eq!(logged.len(), 2);
+            assert!(logged.contains(&ModerationActionKind::Hide));
+            assert!(logged.contains(&ModerationActionKind::Approve));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn dismissing_shows_the_target_below_the_threshold() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let moderator = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let filed = report(conn, ReportTargetType::Question, questionid, 3);
+            let dismiss = |conn: &mut PgConnection, filed: &Report| {
+                resolve(
+                    conn,
+                    filed,
+                    ReportStatus::Dismissed,
+                    moderator,
+                    None,
+                    THRESHOLD,
+                )
+            };
+
+            assert_eq!(dismiss(conn, &filed[0])?.len(), 1);
+            assert!(question_hidden(conn, questionid));
+
+            dismiss(conn, &filed[1])?;
+            assert!(!question_hidden(conn, questionid));
+            assert_eq!(
+                count_pending_for_target(
+                    conn,
+                    ReportTargetType::Question,
+                    questionid
+                )?,
+                1
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn removing_deletes_the_target_for_good() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let moderator = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let comment = services::comment::create(
+                conn,
+                CommentInput {
+                    question_id: questionid,
+                    user_id: author,
+                    parent_id: None,
+                    body: "Buy now!".to_owned(),
+                },
+            )?;
+            let filed = report(conn, ReportTargetType::Comment, comment.id, 1);
+
+            assert!(!is_removed(conn, ReportTargetType::Comment, comment.id)?);
+            resolve(
+                conn,
+                &filed[0],
+                ReportStatus::Removed,
+                moderator,
+                Some("Spam.".to_owned()),
+                THRESHOLD,
+            )?;
+
+            assert!(services::comment::get_by_id(conn, comment.id).is_err());
+            assert!(is_target_hidden(
+                conn,
+                ReportTargetType::Comment,
+                comment.id
+            )?);
+            assert!(is_removed(conn, ReportTargetType::Comment, comment.id)?);
+            assert_eq!(
+                get_target_question_id(
+                    conn,
+                    ReportTargetType::Comment,
+                    comment.id
+                )?,
+                questionid
+            );
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,285 @@
+use diesel::{dsl::count, prelude::*};
+use uuid::Uuid;
+
+use crate::{
+    models::{question::QuestionStatus, tag::Tag},
+    schema::{question_tags, questions, tags},
+};
+use tags::dsl::*;
+
+pub fn get_by_id(conn: &mut PgConnection, tagid: Uuid) -> QueryResult<Tag> {
+    tags.find(tagid).first(conn)
+}
+
+pub fn get_by_name(
+    conn: &mut PgConnection,
+    tag_name: &str,
+) -> QueryResult<Tag> {
+    tags.filter(name.eq(tag_name)).first(conn)
+}
+
+/// The question's tags, by name.
+pub fn get_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<Tag>> {
+    tags.inner_join(question_tags::table)
+        .filter(question_tags::question_id.eq(questionid))
+        .order(name.asc())
+        .select(tags::all_columns)
+        .load(conn)
+}
+
+/// Replaces the question's tags with the named ones, creating any that do
+/// not exist yet.
+pub fn set_for_question(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+    names: &[String],
+) -> QueryResult<Vec<Tag>> {
+    conn.transaction(|conn| {
+        let new_tags: Vec<_> =
+            names.iter().map(|tag_name| name.eq(tag_name)).collect();
+
+        if !new_tags.is_empty() {
+            diesel::insert_into(tags)
+                .values(&new_tags)
+                .on_conflict(name)
+                .do_nothing()
+                .execute(conn)?;
+        }
+
+        let tagged: Vec<Tag> = tags
+            .filter(name.eq_any(names))
+            .order(name.asc())
+            .load(conn)?;
+
+        diesel::delete(
+            question_tags::table
+                .filter(question_tags::question_id.eq(questionid)),
+        )
+        .execute(conn)?;
+
+        let links: Vec<_> = tagged
+            .iter()
+            .map(|tag| {
+                (
+                    question_tags::question_id.eq(questionid),
+                    question_tags::tag_id.eq(tag.id),
+                )
+            })
+            .collect();
+
+        if !links.is_empty() {
+            diesel::insert_into(question_tags::table)
+                .values(&links)
+                .execute(conn)?;
+        }
+
+        Ok(tagged)
+    })
+}
+
+/// Tags starting with `prefix`, most used first.
+pub fn autocomplete(
+    conn: &mut PgConnection,
+    prefix: &str,
+    limit: i64,
+) -> QueryResult<Vec<Tag>> {
+    // Tag names never contain `%` or `_`, so the prefix needs no escaping.
+    tags.left_join(question_tags::table)
+        .filter(name.like(format!("{prefix}%")))
+        .group_by(id)
+        .order((count(question_tags::question_id.nullable()).desc(), name))
+        .select(tags::all_columns)
+        .limit(limit)
+        .load(conn)
+}
+
+/// The tags used by the most questions open to voters, with their counts.
+pub fn get_popular(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> QueryResult<Vec<(Tag, i64)>> {
+    tags.inner_join(question_tags::table.inner_join(questions::table))
+        .filter(questions::deleted_at.is_null())
+        .filter(questions::hidden_at.is_null())
+        .filter(questions::status.ne(QuestionStatus::Draft))
+        .group_by(id)
+        .order((count(question_tags::question_id).desc(), name))
+        .select((tags::all_columns, count(question_tags::question_id)))
+        .limit(limit)
+        .load(conn)
+}
+
+pub fn rename(
+    conn: &mut PgConnection,
+    tagid: Uuid,
+    new_name: &str,
+) -> QueryResult<Tag> {
+    diesel::update(tags.find(tagid))
+        .set(name.eq(new_name))
+        .get_result(conn)
+}
+
+/// Moves the questions tagged `source` over to `target`, then deletes
+/// `source`.
+pub fn merge(
+    conn: &mut PgConnection,
+    source: Uuid,
+    target: Uuid,
+) -> QueryResult<Tag> {
+    conn.transaction(|conn| {
+        let moved = question_tags::table
+            .filter(question_tags::tag_id.eq(source))
+            .select((
+                question_tags::question_id,
+                target.into_sql::<diesel::sql_types::Uuid>(),
+            ));
+
+        moved
+            .insert_into(question_tags::table)
+            .into_columns((question_tags::question_id, question_tags::tag_id))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        diesel::delete(tags.find(source)).execute(conn)?;
+
+        tags.find(target).first(conn)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, with_test_transaction,
+        },
+        services,
+    };
+
+    /// Names no other test uses, all starting with the same prefix.
+    fn tag_names() -> (String, [String; 3]) {
+        let prefix = format!("t{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let names = ["a", "b", "c"].map(|suffix| format!("{prefix}-{suffix}"));
+
+        (prefix, names)
+    }
+
+    fn names_of(conn: &mut PgConnection, questionid: Uuid) -> Vec<String> {
+        get_by_question_id(conn, questionid)
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect()
+    }
+
+    #[test]
+    fn setting_tags_replaces_the_questions_tags() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let (_, [a, b, c]) = tag_names();
+
+            set_for_question(conn, questionid, &[b.clone(), a.clone()])?;
+            assert_eq!(names_of(conn, questionid), [a.clone(), b.clone()]);
+
+            set_for_question(conn, questionid, &[b.clone(), c.clone()])?;
+            assert_eq!(names_of(conn, questionid), [b, c]);
+            // Tags outlive their last question.
+            assert!(get_by_name(conn, &a).is_ok());
+
+            set_for_question(conn, questionid, &[])?;
+            assert!(names_of(conn, questionid).is_empty());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn merging_moves_every_question_onto_the_target() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let only_source = create_test_question(conn, author);
+            let both = create_test_question(conn, author);
+            let only_target = create_test_question(conn, author);
+            let (_, [a, b, _]) = tag_names();
+
+            set_for_question(conn, only_source, &[a.clone()])?;
+            set_for_question(conn, both, &[a.clone(), b.clone()])?;
+            set_for_question(conn, only_target, &[b.clone()])?;
+            let source = get_by_name(conn, &a)?;
+            let target = get_by_name(conn, &b)?;
+
+            assert_eq!(merge(conn, source.id, target.id)?.id, target.id);
+            assert_eq!(names_of(conn, only_source), [b.clone()]);
+            assert_eq!(names_of(conn, both), [b.clone()]);
+            assert_eq!(names_of(conn, only_target), [b]);
+            assert!(get_by_id(conn, source.id).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn renaming_keeps_the_tags_questions() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+            let (_, [a, b, c]) = tag_names();
+
+            set_for_question(conn, questionid, &[a.clone()])?;
+            let other = create_test_question(conn, author);
+            set_for_question(conn, other, &[b.clone()])?;
+            let tag = get_by_name(conn, &a)?;
+
+            assert_eq!(rename(conn, tag.id, &c)?.name, c);
+            assert_eq!(names_of(conn, questionid), [c]);
+            assert!(get_by_name(conn, &a).is_err());
+            // Names stay unique; this aborts the transaction, so it goes
+            // last.
+            assert!(rename(conn, tag.id, &b).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn most_used_tags_come_first() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let (prefix, [a, b, c]) = tag_names();
+
+            for tagged in [vec![a.clone(), b.clone()], vec![a.clone()]] {
+                let questionid = create_test_question(conn, author);
+                set_for_question(conn, questionid, &tagged)?;
+            }
+            // Drafts and hidden questions count for autocompletion only.
+            let draft = create_test_question(conn, author);
+            services::question::update_status(
+                conn,
+                draft,
+                QuestionStatus::Draft,
+                None,
+                None,
+            )?;
+            let hidden = create_test_question(conn, author);
+            services::question::set_hidden(conn, hidden, true)?;
+            for questionid in [draft, hidden] {
+                set_for_question(conn, questionid, &[c.clone()])?;
+            }
+
+            let completed: Vec<String> = autocomplete(conn, &prefix, 10)?
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect();
+            // Ties go by name.
+            assert_eq!(completed, [a.clone(), c, b.clone()]);
+
+            let popular: Vec<(String, i64)> = get_popular(conn, i64::MAX)?
+                .into_iter()
+                .map(|(tag, uses)| (tag.name, uses))
+                .filter(|(tag_name, _)| tag_name.starts_with(&prefix))
+                .collect();
+            assert_eq!(popular, [(a, 2), (b, 1)]);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,644 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::models::{
+    question::QuestionType,
+    tally::{OptionScore, TallyMethod, TallyResult, TallyRound},
+    vote::{OpinionSnapshot, ShiftPeriod, VoteEvent, VoteEventKind},
+};
+
+/// Counts `ballots` for `options`, listed in the question's order. A ballot
+/// lists option ids from most to least preferred and may leave options out;
+/// unknown and repeated options are ignored.
+///
+/// Ties are broken the same way every time: by the scores of earlier rounds,
+/// most recent first, then by the order the options are listed in (earlier
+/// options win, later ones are eliminated first).
+pub fn tally(
+    method: TallyMethod,
+    options: &[Uuid],
+    ballots: &[Vec<Uuid>],
+) -> TallyResult {
+    let ballots: Vec<Vec<Uuid>> = ballots
+        .iter()
+        .map(|ballot| clean_ballot(options, ballot))
+        .filter(|ballot| !ballot.is_empty())
+        .collect();
+
+    let (winner, rounds) = if ballots.is_empty() || options.is_empty() {
+        (None, vec![])
+    } else {
+        match method {
+            TallyMethod::InstantRunoff => instant_runoff(options, &ballots),
+            TallyMethod::Schulze => schulze(options, &ballots),
+            TallyMethod::Borda => borda(options, &ballots),
+        }
+    };
+
+    TallyResult {
+        method,
+        ballots: ballots.len() as i32,
+        winner,
+        rounds,
+    }
+}
+
+/// The credits spent by casting `votes` on each option: `n` votes on one
+/// option cost `n²`. Saturates rather than overflowing, since the votes
+/// come straight from the voter.
+pub fn quadratic_cost(picks: &[(Uuid, i32)]) -> i64 {
+    picks
+        .iter()
+        .map(|(_, votes)| *votes as i64 * *votes as i64)
+        .fold(0, i64::saturating_add)
+}
+
+fn clean_ballot(options: &[Uuid], ballot: &[Uuid]) -> Vec<Uuid> {
+    let mut cleaned: Vec<Uuid> = Vec::with_capacity(ballot.len());
+
+    for option in ballot {
+        if options.contains(option) && !cleaned.contains(option) {
+            cleaned.push(*option);
+        }
+    }
+
+    cleaned
+}
+
+/// Each round counts every ballot for its highest ranked continuing option.
+/// An option with more than half of the ballots still counted wins,
+/// otherwise the weakest option is eliminated.
+fn instant_runoff(
+    options: &[Uuid],
+    ballots: &[Vec<Uuid>],
+) -> (Option<Uuid>, Vec<TallyRound>) {
+    let mut continuing = options.to_vec();
+    let mut history: Vec<HashMap<Uuid, i32>> = vec![];
+    let mut rounds = vec![];
+
+    loop {
+        let mut counts: HashMap<Uuid, i32> =
+            continuing.iter().map(|option| (*option, 0)).collect();
+        let mut exhausted = 0;
+
+        for ballot in ballots {
+            match ballot.iter().find(|option| counts.contains_key(option)) {
+                Some(option) => *counts.get_mut(option).unwrap() += 1,
+                None => exhausted += 1,
+            }
+        }
+
+        let counted = ballots.len() as i32 - exhausted;
+        let scores = continuing
+            .iter()
+            .map(|option| OptionScore {
+                option_id: *option,
+                score: counts[option],
+            })
+            .collect();
+        history.push(counts);
+
+        let leader = break_tie(options, &continuing, &history, false);
+
+        if continuing.len() == 1
+            || history.last().unwrap()[&leader] * 2 > counted
+        {
+            rounds.push(TallyRound {
+                round: rounds.len() as i32 + 1,
+                scores,
+                eliminated: None,
+                exhausted,
+            });
+            return (Some(leader), rounds);
+        }
+
+        let eliminated = break_tie(options, &continuing, &history, true);
+        continuing.retain(|option| *option != eliminated);
+        rounds.push(TallyRound {
+            round: rounds.len() as i32 + 1,
+            scores,
+            eliminated: Some(eliminated),
+            exhausted,
+        });
+    }
+}
+
+/// Picks the strongest (or, with `weakest`, the weakest) continuing option,
+/// comparing scores from the latest round backwards, then listing order.
+fn break_tie(
+    options: &[Uuid],
+    continuing: &[Uuid],
+    history: &[HashMap<Uuid, i32>],
+    weakest: bool,
+) -> Uuid {
+    let key = |option: &&Uuid| {
+        let scores: Vec<i32> =
+            history.iter().rev().map(|counts| counts[*option]).collect();
+        let position = options.iter().position(|o| o == *option);
+        (scores, Reverse(position))
+    };
+
+    let picked = if weakest {
+        continuing.iter().min_by_key(key)
+    } else {
+        continuing.iter().max_by_key(key)
+    };
+
+    *picked.expect("At least one option is still counted.")
+}
+
+/// Scores each option by the number of options it beats through its
+/// strongest path. The winner is beaten by none, the first listed if
+/// several are.
+fn schulze(
+    options: &[Uuid],
+    ballots: &[Vec<Uuid>],
+) -> (Option<Uuid>, Vec<TallyRound>) {
+    let n = options.len();
+    let mut preferred = vec![vec![0; n]; n];
+
+    for ballot in ballots {
+        let ranks: Vec<Option<usize>> = options
+            .iter()
+            .map(|option| ballot.iter().position(|o| o == option))
+            .collect();
+
+        for i in 0..n {
+            for j in 0..n {
+                let prefers = match (ranks[i], ranks[j]) {
+                    (Some(a), Some(b)) => a < b,
+                    (Some(_), None) => true,
+                    _ => false,
+                };
+                if i != j && prefers {
+                    preferred[i][j] += 1;
+                }
+            }
+        }
+    }
+
+    let mut strength = vec![vec![0; n]; n];
+
+    for i in 0..n {
+        for j in 0..n {
+            if i != j && preferred[i][j] > preferred[j][i] {
+                strength[i][j] = preferred[i][j];
+            }
+        }
+    }
+
+    for k in 0..n {
+        for i in (0..n).filter(|&i| i != k) {
+            for j in (0..n).filter(|&j| j != i && j != k) {
+                strength[i][j] =
+                    strength[i][j].max(strength[i][k].min(strength[k][j]));
+            }
+        }
+    }
+
+    let beats = |i: usize, j: usize| strength[i][j] > strength[j][i];
+    let scores = (0..n)
+        .map(|i| OptionScore {
+            option_id: options[i],
+            score: (0..n).filter(|&j| beats(i, j)).count() as i32,
+        })
+        .collect();
+    let winner = (0..n)
+        .find(|&i| (0..n).all(|j| !beats(j, i)))
+        .map(|i| options[i]);
+
+    (
+        winner,
+        vec![TallyRound {
+            round: 1,
+            scores,
+            eliminated: None,
+            exhausted: 0,
+        }],
+    )
+}
+
+/// Gives each ranked option one point for every option in the question
+/// ranked below it, so a first choice among `n` options scores `n - 1`.
+/// Ties go to the option with the most first choices.
+fn borda(
+    options: &[Uuid],
+    ballots: &[Vec<Uuid>],
+) -> (Option<Uuid>, Vec<TallyRound>) {
+    let n = options.len() as i32;
+    let mut points: HashMap<Uuid, (i32, i32)> =
+        options.iter().map(|option| (*option, (0, 0))).collect();
+
+    for ballot in ballots {
+        for (rank, option) in ballot.iter().enumerate() {
+            let (score, firsts) = points.get_mut(option).unwrap();
+            *score += n - 1 - rank as i32;
+            if rank == 0 {
+                *firsts += 1;
+            }
+        }
+    }
+
+    let winner = options
+        .iter()
+        .enumerate()
+        .max_by_key(|(position, option)| (points[*option], Reverse(*position)))
+        .map(|(_, option)| *option);
+    let scores = options
+        .iter()
+        .map(|option| OptionScore {
+            option_id: *option,
+            score: points[option].0,
+        })
+        .collect();
+
+    (
+        winner,
+        vec![TallyRound {
+            round: 1,
+            scores,
+            eliminated: None,
+            exhausted: 0,
+        }],
+    )
+}
+
+/// Replays a question's vote `events`, oldest first, into where the votes
+/// stood at the end of each period that saw any. Options are scored like
+/// the question's stats: by votes cast, or by first choices when ranked.
+pub fn opinion_shift(
+    question_type: QuestionType,
+    options: &[Uuid],
+    events: &[VoteEvent],
+    period: ShiftPeriod,
+) -> Vec<OpinionSnapshot> {
+    let mut standing: HashMap<Uuid, &VoteEvent> = HashMap::new();
+    let mut snapshots = vec![];
+    let mut events = events.iter().peekable();
+
+    while let Some(first) = events.peek() {
+        let start = period_start(first.created_at, period);
+        let (mut created, mut changed, mut retracted) = (0, 0, 0);
+
+        while let Some(event) = events
+            .next_if(|event| period_start(event.created_at, period) == start)
+        {
+            match event.kind {
+                VoteEventKind::Created => {
+                    created += 1;
+                    standing.insert(event.user_id, event);
+                }
+                VoteEventKind::Changed => {
+                    changed += 1;
+                    standing.insert(event.user_id, event);
+                }
+                VoteEventKind::Retracted => {
+                    retracted += 1;
+                    standing.remove(&event.user_id);
+                }
+            }
+        }
+
+        let values: Vec<i64> = standing
+            .values()
+            .filter_map(|event| event.value.map(i64::from))
+            .collect();
+        let average = match values.len() {
+            0 => None,
+            n => Some(format!(
+                "{:.2}",
+                values.iter().sum::<i64>() as f64 / n as f64
+            )),
+        };
+
+        let mut scores: HashMap<Uuid, i32> = HashMap::new();
+        for event in standing.values() {
+            let picks = event.option_ids.iter().zip(&event.weights);
+            let counted = if question_type == QuestionType::Ranked {
+                1
+            } else {
+                event.option_ids.len()
+            };
+            for (option, weight) in picks.take(counted) {
+                *scores.entry(*option).or_default() += weight;
+            }
+        }
+
+        snapshots.push(OpinionSnapshot {
+            period_start: start,
+            total: standing.len() as i32,
+            average,
+            options: options
+                .iter()
+                .map(|option| OptionScore {
+                    option_id: *option,
+                    score: *scores.get(option).unwrap_or(&0),
+                })
+                .collect(),
+            created,
+            changed,
+            retracted,
+        });
+    }
+
+    snapshots
+}
+
+fn period_start(at: NaiveDateTime, period: ShiftPeriod) -> NaiveDateTime {
+    const DAY: i64 = 86400;
+    let (length, offset) = match period {
+        ShiftPeriod::Hour => (3600, 0),
+        ShiftPeriod::Day => (DAY, 0),
+        // Weeks start on Monday, and 1970-01-01 was a Thursday.
+        ShiftPeriod::Week => (7 * DAY, 4 * DAY),
+    };
+    let secs = at.timestamp();
+
+    NaiveDateTime::from_timestamp_opt(
+        secs - (secs - offset).rem_euclid(length),
+        0,
+    )
+    .unwrap_or(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection, prelude::*, sample};
+
+    use super::*;
+
+    const METHODS: [TallyMethod; 3] = [
+        TallyMethod::InstantRunoff,
+        TallyMethod::Schulze,
+        TallyMethod::Borda,
+    ];
+
+    fn options(n: u128) -> Vec<Uuid> {
+        (1..=n).map(Uuid::from_u128).collect()
+    }
+
+    /// Options along with ballots ranking any of them in any order.
+    fn election() -> impl Strategy<Value = (Vec<Uuid>, Vec<Vec<Uuid>>)> {
+        (1..=5u128).prop_flat_map(|n| {
+            let options = options(n);
+            let ballot = sample::subsequence(options.clone(), 0..=n as usize)
+                .prop_shuffle();
+
+            (Just(options), collection::vec(ballot, 0..40))
+        })
+    }
+
+    /// Whether `a` is ranked above `b` on more ballots than the reverse.
+    fn beats(ballots: &[Vec<Uuid>], a: Uuid, b: Uuid) -> bool {
+        let prefers = |x: Uuid, y: Uuid| {
+            ballots
+                .iter()
+                .filter(|ballot| {
+                    match (
+                        ballot.iter().position(|o| *o == x),
+                        ballot.iter().position(|o| *o == y),
+                    ) {
+                        (Some(x), Some(y)) => x < y,
+                        (Some(_), None) => true,
+                        _ => false,
+                    }
+                })
+                .count()
+        };
+
+        prefers(a, b) > prefers(b, a)
+    }
+
+    proptest! {
+        #[test]
+        fn is_deterministic((options, ballots) in election()) {
+            for method in METHODS {
+                prop_assert_eq!(
+                    tally(method, &options, &ballots),
+                    tally(method, &options, &ballots)
+                );
+            }
+        }
+
+        #[test]
+        fn ignores_ballot_order(
+            (options, ballots, shuffled) in election().prop_flat_map(
+                |(options, ballots)| {
+                    let shuffled = Just(ballots.clone()).prop_shuffle();
+                    (Just(options), Just(ballots), shuffled)
+                }
+            )
+        ) {
+            for method in METHODS {
+                prop_assert_eq!(
+                    tally(method, &options, &ballots),
+                    tally(method, &options, &shuffled)
+                );
+            }
+        }
+
+        #[test]
+        fn elects_a_ranked_option((options, ballots) in election()) {
+            for method in METHODS {
+                let result = tally(method, &options, &ballots);
+
+                match result.winner {
+                    Some(winner) => {
+                        prop_assert!(options.contains(&winner));
+                        prop_assert!(ballots.iter().any(|b| !b.is_empty()));
+                    }
+                    None => prop_assert_eq!(result.ballots, 0),
+                }
+            }
+        }
+
+        #[test]
+        fn elects_the_majority_first_choice(
+            (options, ballots) in election()
+        ) {
+            let majority = options.iter().find(|option| {
+                ballots.iter().filter(|b| b.first() == Some(option)).count()
+                    * 2
+                    > ballots.iter().filter(|b| !b.is_empty()).count()
+            });
+
+            if let Some(majority) = majority {
+                for method in [TallyMethod::InstantRunoff, TallyMethod::Schulze]
+                {
+                    prop_assert_eq!(
+                        tally(method, &options, &ballots).winner,
+                        Some(*majority)
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn schulze_elects_the_condorcet_winner(
+            (options, ballots) in election()
+        ) {
+            let counted = ballots.iter().any(|b| !b.is_empty());
+            let condorcet = options.iter().find(|&&a| {
+                options.iter().all(|&b| a == b || beats(&ballots, a, b))
+            });
+
+            if let Some(condorcet) = condorcet.filter(|_| counted) {
+                prop_assert_eq!(
+                    tally(TallyMethod::Schulze, &options, &ballots).winner,
+                    Some(*condorcet)
+                );
+            }
+        }
+
+        #[test]
+        fn borda_elects_the_highest_score((options, ballots) in election()) {
+            let result = tally(TallyMethod::Borda, &options, &ballots);
+
+            if let Some(winner) = result.winner {
+                let scores = &result.rounds[0].scores;
+                let best = scores.iter().map(|s| s.score).max().unwrap();
+                let winner_score = scores
+                    .iter()
+                    .find(|s| s.option_id == winner)
+                    .unwrap()
+                    .score;
+
+                prop_assert_eq!(winner_score, best);
+            }
+        }
+
+        #[test]
+        fn breaks_full_ties_by_listing_order(n in 1..=6u128) {
+            // One ballot for each option only: every method sees a tie.
+            let options = options(n);
+            let ballots: Vec<Vec<Uuid>> =
+                options.iter().map(|option| vec![*option]).collect();
+
+            for method in METHODS {
+                prop_assert_eq!(
+                    tally(method, &options, &ballots).winner,
+                    Some(options[0])
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn instant_runoff_transfers_eliminated_ballots() {
+        let [a, b, c]: [Uuid; 3] = options(3).try_into().unwrap();
+        let ballots = [
+            vec![vec![a, b]; 4],
+            vec![vec![b, a]; 3],
+            vec![vec![c, b]; 2],
+        ]
+        .concat();
+
+        let result = tally(TallyMethod::InstantRunoff, &[a, b, c], &ballots);
+
+        assert_eq!(result.winner, Some(b));
+        assert_eq!(result.rounds.len(), 2);
+        assert_eq!(result.rounds[0].eliminated, Some(c));
+        assert_eq!(result.rounds[1].eliminated, None);
+    }
+
+    #[test]
+    fn schulze_follows_strongest_paths() {
+        // The example from Schulze's paper, where E wins.
+        let [a, b, c, d, e]: [Uuid; 5] = options(5).try_into().unwrap();
+        let ballots = [
+            vec![vec![a, c, b, e, d]; 5],
+            vec![vec![a, d, e, c, b]; 5],
+            vec![vec![b, e, d, a, c]; 8],
+            vec![vec![c, a, b, e, d]; 3],
+            vec![vec![c, a, e, b, d]; 7],
+            vec![vec![c, b, a, d, e]; 2],
+            vec![vec![d, c, e, b, a]; 7],
+            vec![vec![e, b, a, d, c]; 8],
+        ]
+        .concat();
+
+        let result = tally(TallyMethod::Schulze, &[a, b, c, d, e], &ballots);
+
+        assert_eq!(result.winner, Some(e));
+    }
+
+    #[test]
+    fn borda_breaks_ties_by_first_choices() {
+        let [a, b, c]: [Uuid; 3] = options(3).try_into().unwrap();
+        // a and b both score 4, but b is first on two ballots.
+        let ballots = vec![vec![a, c, b], vec![b, a, c], vec![b, a, c]];
+
+        let result = tally(TallyMethod::Borda, &[a, b, c], &ballots);
+
+        assert_eq!(result.winner, Some(b));
+    }
+
+    #[test]
+    fn ignores_unknown_and_repeated_options() {
+        let [a, b]: [Uuid; 2] = options(2).try_into().unwrap();
+        let unknown = Uuid::from_u128(99);
+        let ballots = vec![vec![unknown, b, b, a], vec![unknown]];
+
+        let result = tally(TallyMethod::InstantRunoff, &[a, b], &ballots);
+
+        assert_eq!(result.ballots, 1);
+        assert_eq!(result.winner, Some(b));
+    }
+
+    #[test]
+    fn opinion_shift_replays_events_by_period() {
+        let [a, b]: [Uuid; 2] = options(2).try_into().unwrap();
+        let at = |day: u32, hour: u32| {
+            chrono::NaiveDate::from_ymd_opt(2022, 12, day)
+                .and_then(|date| date.and_hms_opt(hour, 0, 0))
+                .unwrap()
+        };
+        let event = |voter: Uuid, kind, value, created_at| VoteEvent {
+            id: Uuid::new_v4(),
+            user_id: voter,
+            question_id: Uuid::nil(),
+            kind,
+            value,
+            option_ids: vec![],
+            weights: vec![],
+            created_at,
+        };
+        let events = [
+            event(a, VoteEventKind::Created, Some(4), at(20, 10)),
+            event(b, VoteEventKind::Created, Some(2), at(20, 11)),
+            event(a, VoteEventKind::Changed, Some(1), at(21, 9)),
+            event(b, VoteEventKind::Retracted, None, at(21, 12)),
+        ];
+
+        let snapshots =
+            opinion_shift(QuestionType::Rating, &[], &events, ShiftPeriod::Day);
+
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].period_start, at(20, 0));
+        assert_eq!(snapshots[0].total, 2);
+        assert_eq!(snapshots[0].average.as_deref(), Some("3.00"));
+        assert_eq!(snapshots[0].created, 2);
+        assert_eq!(snapshots[1].period_start, at(21, 0));
+        assert_eq!(snapshots[1].total, 1);
+        assert_eq!(snapshots[1].average.as_deref(), Some("1.00"));
+        assert_eq!((snapshots[1].changed, snapshots[1].retracted), (1, 1));
+    }
+
+    #[test]
+    fn quadratic_cost_squares_votes() {
+        let [a, b]: [Uuid; 2] = options(2).try_into().unwrap();
+
+        assert_eq!(quadratic_cost(&[(a, 3), (b, 1)]), 10);
+        assert_eq!(quadratic_cost(&[]), 0);
+    }
+
+    #[test]
+    fn quadratic_cost_saturates() {
+        let picks: Vec<(Uuid, i32)> =
+            options(3).into_iter().map(|o| (o, i32::MAX)).collect();
+
+        assert_eq!(quadratic_cost(&picks), i64::MAX);
+    }
+}
@@ -0,0 +1,290 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base32::Alphabet;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use totp_lite::{totp_custom, Sha1};
+use uuid::Uuid;
+
+use crate::{
+    models::totp::{LoginChallengeRow, RecoveryCode},
+    schema::{login_challenges, recovery_codes},
+};
+
+const ISSUER: &str = "Votodroid";
+const STEP: u64 = 30;
+const DIGITS: u32 = 6;
+const RECOVERY_CODE_COUNT: usize = 10;
+const CHALLENGE_LIFETIME_MINUTES: i64 = 5;
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+const SECRET_ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = rand::thread_rng().gen();
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+pub fn provisioning_uri(username: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP}",
+        issuer = ISSUER,
+    )
+}
+
+/// Checks `code` against the current time step and its neighbours, skipping
+/// steps at or before `last_step` so a code cannot be replayed. Returns the
+/// matching step.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    last_step: Option<i64>,
+) -> Option<i64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    verify_code_at(secret, code, last_step, now)
+}
+
+/// `verify_code` at `now`, in seconds since the epoch.
+fn verify_code_at(
+    secret: &str,
+    code: &str,
+    last_step: Option<i64>,
+    now: u64,
+) -> Option<i64> {
+    let key = base32::decode(SECRET_ALPHABET, secret)?;
+    let current = (now / STEP) as i64;
+
+    ((current - 1).max(0)..=current + 1)
+        .filter(|step| last_step.map_or(true, |last| *step > last))
+        .find(|step| {
+            totp_custom::<Sha1>(STEP, DIGITS, &key, *step as u64 * STEP) == code
+        })
+}
+
+pub fn create_challenge(
+    conn: &mut PgConnection,
+    userid: Uuid,
+) -> QueryResult<LoginChallengeRow> {
+    let expires =
+        Utc::now().naive_utc() + Duration::minutes(CHALLENGE_LIFETIME_MINUTES);
+
+    diesel::insert_into(login_challenges::table)
+        .values((
+            login_challenges::user_id.eq(userid),
+            login_challenges::expires_at.eq(expires),
+        ))
+        .get_result(conn)
+}
+
+pub fn get_challenge(
+    conn: &mut PgConnection,
+    challengeid: Uuid,
+) -> QueryResult<LoginChallengeRow> {
+    login_challenges::table
+        .find(challengeid)
+        .filter(login_challenges::expires_at.gt(diesel::dsl::now))
+        .first(conn)
+}
+
+/// Whether a challenge has had too many wrong codes to be tried again.
+pub fn is_exhausted(challenge: &LoginChallengeRow) -> bool {
+    challenge.attempts >= MAX_CHALLENGE_ATTEMPTS
+}
+
+pub fn increment_challenge_attempts(
+    conn: &mut PgConnection,
+    challengeid: Uuid,
+) -> QueryResult<LoginChallengeRow> {
+    diesel::update(login_challenges::table.find(challengeid))
+        .set(login_challenges::attempts.eq(login_challenges::attempts + 1))
+        .get_result(conn)
+}
+
+pub fn delete_challenge(
+    conn: &mut PgConnection,
+    challengeid: Uuid,
+) -> QueryResult<usize> {
+    diesel::delete(login_challenges::table.find(challengeid)).execute(conn)
+}
+
+/// Deletes challenges that expired before `before`.
+pub fn delete_expired_challenges(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> QueryResult<usize> {
+    diesel::delete(
+        login_challenges::table.filter(login_challenges::expires_at.lt(before)),
+    )
+    .execute(conn)
+}
+
+/// A new set of recovery codes. Store them with `replace_recovery_codes`
+/// once hashed.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect()
+}
+
+/// Replaces the user's recovery codes with `hashes`. Hashing is left to the
+/// caller so it can be done off the worker thread.
+pub fn replace_recovery_codes(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    hashes: &[String],
+) -> QueryResult<usize> {
+    let rows: Vec<_> = hashes
+        .iter()
+        .map(|hash| {
+            (
+                recovery_codes::user_id.eq(userid),
+                recovery_codes::code_hash.eq(hash),
+            )
+        })
+        .collect();
+
+    conn.transaction(|conn| {
+        diesel::delete(
+            recovery_codes::table.filter(recovery_codes::user_id.eq(userid)),
+        )
+        .execute(conn)?;
+        diesel::insert_into(recovery_codes::table)
+            .values(&rows)
+            .execute(conn)
+    })
+}
+
+pub fn get_unused_recovery_codes(
+    conn: &mut PgConnection,
+    userid: Uuid,
+) -> QueryResult<Vec<RecoveryCode>> {
+    recovery_codes::table
+        .filter(recovery_codes::user_id.eq(userid))
+        .filter(recovery_codes::used_at.is_null())
+        .load(conn)
+}
+
+/// Marks a recovery code as used. Returns whether it was still unused, so
+/// only one of two concurrent uses of a code succeeds.
+pub fn use_recovery_code(
+    conn: &mut PgConnection,
+    codeid: Uuid,
+) -> QueryResult<bool> {
+    let used = diesel::update(
+        recovery_codes::table
+            .find(codeid)
+            .filter(recovery_codes::used_at.is_null()),
+    )
+    .set(recovery_codes::used_at.eq(diesel::dsl::now))
+    .execute(conn)?;
+
+    Ok(used > 0)
+}
+
+/// Recovery codes are compared lowercase and without surrounding spaces.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+fn generate_recovery_code() -> String {
+    const CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    let mut part = || -> String {
+        (0..5)
+            .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+            .collect()
+    };
+
+    format!("{}-{}", part(), part())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{create_test_user, with_test_transaction};
+
+    /// The RFC 6238 test secret, `12345678901234567890`.
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    /// Its code at 59 seconds, in time step 1.
+    const CODE: &str = "287082";
+
+    #[test]
+    fn verify_code_accepts_neighbouring_steps() {
+        assert_eq!(verify_code_at(SECRET, CODE, None, 59), Some(1));
+        assert_eq!(verify_code_at(SECRET, CODE, None, 5), Some(1));
+        assert_eq!(verify_code_at(SECRET, CODE, None, 89), Some(1));
+        assert_eq!(verify_code_at(SECRET, CODE, None, 90), None);
+        assert_eq!(verify_code_at(SECRET, "000000", None, 59), None);
+    }
+
+    #[test]
+    fn verify_code_rejects_replays() {
+        assert_eq!(verify_code_at(SECRET, CODE, Some(0), 59), Some(1));
+        assert_eq!(verify_code_at(SECRET, CODE, Some(1), 59), None);
+        assert_eq!(verify_code_at(SECRET, CODE, Some(2), 89), None);
+    }
+
+    #[test]
+    fn challenges_run_out_of_attempts() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let challenge = create_challenge(conn, userid)?;
+            assert!(!is_exhausted(&challenge));
+
+            for _ in 1..MAX_CHALLENGE_ATTEMPTS {
+                assert!(!is_exhausted(&increment_challenge_attempts(
+                    conn,
+                    challenge.id
+                )?));
+            }
+            assert!(is_exhausted(&increment_challenge_attempts(
+                conn,
+                challenge.id
+            )?));
+            assert!(is_exhausted(&get_challenge(conn, challenge.id)?));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn expired_challenges_are_not_found() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let challenge = create_challenge(conn, userid)?;
+            let expired = Utc::now().naive_utc() - Duration::seconds(1);
+            diesel::update(login_challenges::table.find(challenge.id))
+                .set(login_challenges::expires_at.eq(expired))
+                .execute(conn)?;
+
+            assert_eq!(
+                get_challenge(conn, challenge.id).err(),
+                Some(diesel::result::Error::NotFound)
+            );
+            assert_eq!(
+                delete_expired_challenges(conn, Utc::now().naive_utc())?,
+                1
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn recovery_codes_are_used_once() {
+        with_test_transaction(|conn| {
+            let userid = create_test_user(conn);
+            let hashes = vec!["a".to_owned(), "b".to_owned()];
+            replace_recovery_codes(conn, userid, &hashes)?;
+
+            let unused = get_unused_recovery_codes(conn, userid)?;
+            assert_eq!(unused.len(), 2);
+            assert!(use_recovery_code(conn, unused[0].id)?);
+            assert!(!use_recovery_code(conn, unused[0].id)?);
+            assert_eq!(get_unused_recovery_codes(conn, userid)?.len(), 1);
+
+            replace_recovery_codes(conn, userid, &hashes)?;
+            assert_eq!(get_unused_recovery_codes(conn, userid)?.len(), 2);
+            Ok(())
+        });
+    }
+}
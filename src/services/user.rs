@@ -1,51 +1,46 @@
 use crate::schema::users::dsl::*;
 use crate::{
     models::user::{RegisterUserInput, User},
-    schema::users,
+    schema::{questions, users, votes},
+    services,
 };
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
-use rand::distributions::Alphanumeric;
-use rand::Rng;
 use uuid::Uuid;
 
+/// `new_user.password` must already be hashed with
+/// `services::password::hash`.
 pub fn create_user(
     conn: &mut PgConnection,
-    mut new_user: RegisterUserInput,
+    new_user: RegisterUserInput,
 ) -> QueryResult<User> {
-    let user_salt: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(16)
-        .map(char::from)
-        .collect();
-
-    new_user.password = argon2::hash_encoded(
-        new_user.password.as_bytes(),
-        user_salt.as_bytes(),
-        &argon2::Config::default(),
-    )
-    .unwrap();
-
     diesel::insert_into(users::table)
         .values(&new_user)
         .get_result(conn)
 }
 
 pub fn get_by_id(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<User> {
-    users.find(user_id).first(conn)
+    users.find(user_id).filter(deleted_at.is_null()).first(conn)
 }
 
 pub fn get_by_username(
     conn: &mut PgConnection,
     name: &String,
 ) -> QueryResult<User> {
-    users.filter(username.eq(name)).first(conn)
+    users
+        .filter(username.eq(name))
+        .filter(deleted_at.is_null())
+        .first(conn)
 }
 
 pub fn get_by_email(
     conn: &mut PgConnection,
     user_email: &String,
 ) -> QueryResult<User> {
-    users.filter(email.eq(user_email)).first(conn)
+    users
+        .filter(email.eq(user_email))
+        .filter(deleted_at.is_null())
+        .first(conn)
 }
 
 pub fn update_last_login(
@@ -56,3 +51,120 @@ pub fn update_last_login(
         .set(last_login.eq(diesel::dsl::now))
         .get_result(conn)
 }
+
+pub fn set_totp_secret(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    secret: &str,
+) -> QueryResult<User> {
+    diesel::update(users.find(user_id))
+        .set((totp_secret.eq(secret), totp_enabled.eq(false)))
+        .get_result(conn)
+}
+
+pub fn enable_totp(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    step: i64,
+) -> QueryResult<User> {
+    diesel::update(users.find(user_id))
+        .set((totp_enabled.eq(true), totp_last_step.eq(step)))
+        .get_result(conn)
+}
+
+pub fn update_totp_last_step(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    step: i64,
+) -> QueryResult<User> {
+    diesel::update(users.find(user_id))
+        .set(totp_last_step.eq(step))
+        .get_result(conn)
+}
+
+pub fn update_password(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    hash: &str,
+) -> QueryResult<User> {
+    diesel::update(users.find(user_id))
+        .set((password.eq(hash), updated_at.eq(diesel::dsl::now)))
+        .get_result(conn)
+}
+
+/// Gets a deleted user, by username or email, that can still be restored.
+pub fn get_deleted_by_username_or_email(
+    conn: &mut PgConnection,
+    name_or_email: &str,
+    deleted_after: NaiveDateTime,
+) -> QueryResult<User> {
+    users
+        .filter(username.eq(name_or_email).or(email.eq(name_or_email)))
+        .filter(deleted_at.gt(deleted_after))
+        .first(conn)
+}
+
+pub fn get_deleted_by_id(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    deleted_after: NaiveDateTime,
+) -> QueryResult<User> {
+    users
+        .find(user_id)
+        .filter(deleted_at.gt(deleted_after))
+        .first(conn)
+}
+
+/// Marks the user and their questions as deleted.
+pub fn delete(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<User> {
+    let now = Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        services::question::delete_all_by_user_id(conn, user_id, now)?;
+        diesel::update(users.find(user_id))
+            .set(deleted_at.eq(now))
+            .get_result(conn)
+    })
+}
+
+/// Restores the user and the questions deleted along with them.
+pub fn restore(conn: &mut PgConnection, user: &User) -> QueryResult<User> {
+    conn.transaction(|conn| {
+        if let Some(at) = user.deleted_at {
+            services::question::restore_all_by_user_id(conn, user.id, at)?;
+        }
+        diesel::update(users.find(user.id))
+            .set(deleted_at.eq(None::<NaiveDateTime>))
+            .get_result(conn)
+    })
+}
+
+/// Hard deletes users deleted before `before`, with their questions and
+/// votes.
+pub fn purge_deleted(
+    conn: &mut PgConnection,
+    before: NaiveDateTime,
+) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let purged: Vec<Uuid> =
+            users.filter(deleted_at.lt(before)).select(id).load(conn)?;
+        let purged_questions: Vec<Uuid> = questions::table
+            .filter(questions::user_id.eq_any(&purged))
+            .select(questions::id)
+            .load(conn)?;
+
+        diesel::delete(
+            votes::table.filter(
+                votes::user_id
+                    .eq_any(&purged)
+                    .or(votes::question_id.eq_any(&purged_questions)),
+            ),
+        )
+        .execute(conn)?;
+        diesel::delete(
+            questions::table.filter(questions::user_id.eq_any(&purged)),
+        )
+        .execute(conn)?;
+        diesel::delete(users.filter(id.eq_any(&purged))).execute(conn)
+    })
+}
@@ -1,28 +1,69 @@
+use std::collections::HashMap;
+
 use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
-use diesel::{PgConnection, QueryResult};
+use diesel::{
+    dsl::sql, pg::upsert::excluded, sql_types::Timestamp, PgConnection,
+    QueryResult,
+};
 use uuid::Uuid;
 
 use crate::schema::votes::dsl::*;
 use crate::{
-    models::vote::{Vote, VoteInput},
-    schema::votes,
+    models::{
+        question::{Question, QuestionType},
+        vote::{AllocationInput, Vote, VoteEvent, VoteEventKind, VoteInput},
+    },
+    schema::{users, vote_events, vote_options, votes},
+    services,
 };
 
-pub fn create(
+/// Casts the user's vote on the question, or replaces the one they cast
+/// before, along with the options it picks, in order, and the votes cast on
+/// each. A single `INSERT ... ON CONFLICT` keeps concurrent calls from
+/// creating two votes.
+pub fn upsert(
     conn: &mut PgConnection,
     new_vote: VoteInput,
+    picks: &[(Uuid, i32)],
 ) -> QueryResult<Vote> {
-    diesel::insert_into(votes::table)
-        .values(&new_vote)
-        .get_result(conn)
+    conn.transaction(|conn| {
+        let vote: Vote = diesel::insert_into(votes::table)
+            .values(&new_vote)
+            .on_conflict((user_id, question_id))
+            .do_update()
+            .set((value.eq(excluded(value)), updated_at.eq(diesel::dsl::now)))
+            .get_result(conn)?;
+        // Both timestamps are the transaction's start time on insert only.
+        let kind = if vote.created_at == vote.updated_at {
+            VoteEventKind::Created
+        } else {
+            VoteEventKind::Changed
+        };
+
+        diesel::delete(
+            vote_options::table.filter(vote_options::vote_id.eq(vote.id)),
+        )
+        .execute(conn)?;
+        set_options(conn, vote.id, picks)?;
+        record_event(conn, &vote, kind, picks)?;
+        Ok(vote)
+    })
 }
 
+/// The question's votes. Like every count below, it leaves out the votes of
+/// deleted users.
 pub fn get_all_by_question_id(
     conn: &mut PgConnection,
     questionid: Uuid,
 ) -> QueryResult<Vec<Vote>> {
-    votes.filter(question_id.eq(questionid)).load(conn)
+    votes
+        .inner_join(users::table)
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .select(votes::all_columns)
+        .load(conn)
 }
 
 pub fn get_by_user_id_and_question_id(
@@ -36,14 +77,129 @@ pub fn get_by_user_id_and_question_id(
         .first(conn)
 }
 
-pub fn update(
+/// Withdraws the user's vote on the question. Returns whether there was one.
+pub fn retract(
     conn: &mut PgConnection,
-    voteid: Uuid,
-    new_value: i32,
-) -> QueryResult<Vote> {
-    diesel::update(votes.find(voteid))
-        .set((value.eq(new_value), updated_at.eq(diesel::dsl::now)))
+    userid: Uuid,
+    questionid: Uuid,
+) -> QueryResult<bool> {
+    conn.transaction(|conn| {
+        let vote: Option<Vote> = diesel::delete(
+            votes
+                .filter(user_id.eq(userid))
+                .filter(question_id.eq(questionid)),
+        )
         .get_result(conn)
+        .optional()?;
+
+        match vote {
+            Some(vote) => {
+                record_event(conn, &vote, VoteEventKind::Retracted, &[])?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    })
+}
+
+fn record_event(
+    conn: &mut PgConnection,
+    vote: &Vote,
+    kind: VoteEventKind,
+    picks: &[(Uuid, i32)],
+) -> QueryResult<usize> {
+    let retracted = kind == VoteEventKind::Retracted;
+
+    diesel::insert_into(vote_events::table)
+        .values((
+            vote_events::user_id.eq(vote.user_id),
+            vote_events::question_id.eq(vote.question_id),
+            vote_events::kind.eq(kind),
+            vote_events::value.eq(if retracted { None } else { vote.value }),
+            vote_events::option_ids.eq(picks
+                .iter()
+                .map(|(option, _)| *option)
+                .collect::<Vec<_>>()),
+            vote_events::weights
+                .eq(picks.iter().map(|(_, w)| *w).collect::<Vec<_>>()),
+            // The vote's row is locked by now, so unlike the transaction's
+            // start time this orders concurrent changes as they happened.
+            vote_events::created_at
+                .eq(sql::<Timestamp>("clock_timestamp()::timestamp")),
+        ))
+        .execute(conn)
+}
+
+/// The user's vote events, newest first, after the `cursor` event if given.
+pub fn get_events_by_user_id(
+    conn: &mut PgConnection,
+    userid: Uuid,
+    limit: i32,
+    cursor: Option<(NaiveDateTime, Uuid)>,
+) -> QueryResult<Vec<VoteEvent>> {
+    let mut query = vote_events::table
+        .filter(vote_events::user_id.eq(userid))
+        .into_boxed();
+
+    if let Some((cursor_at, cursor_id)) = cursor {
+        query = query.filter(
+            vote_events::created_at
+                .lt(cursor_at)
+                .or(vote_events::created_at
+                    .eq(cursor_at)
+                    .and(vote_events::id.lt(cursor_id))),
+        );
+    }
+
+    query
+        .order((vote_events::created_at.desc(), vote_events::id.desc()))
+        .limit(limit as i64)
+        .load(conn)
+}
+
+pub fn get_event_by_id(
+    conn: &mut PgConnection,
+    eventid: Uuid,
+) -> QueryResult<VoteEvent> {
+    vote_events::table.find(eventid).first(conn)
+}
+
+/// The question's vote events, oldest first.
+pub fn get_events_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<VoteEvent>> {
+    vote_events::table
+        .filter(vote_events::question_id.eq(questionid))
+        .order((vote_events::created_at.asc(), vote_events::id.asc()))
+        .load(conn)
+}
+
+fn set_options(
+    conn: &mut PgConnection,
+    voteid: Uuid,
+    picks: &[(Uuid, i32)],
+) -> QueryResult<usize> {
+    let rows: Vec<_> = picks
+        .iter()
+        .enumerate()
+        .map(|(i, (optionid, votecount))| {
+            (
+                vote_options::vote_id.eq(voteid),
+                vote_options::option_id.eq(optionid),
+                vote_options::rank.eq(i as i32),
+                vote_options::weight.eq(votecount),
+            )
+        })
+        .collect();
+
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    diesel::insert_into(vote_options::table)
+        .values(&rows)
+        .execute(conn)
 }
 
 pub fn avg_for_question(
@@ -51,19 +207,775 @@ pub fn avg_for_question(
     questionid: Uuid,
 ) -> QueryResult<Option<BigDecimal>> {
     votes
+        .inner_join(users::table)
         .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
         .select(diesel::dsl::avg(value))
         .first(conn)
 }
 
-pub fn count_for_question_with_value(
+/// Counts the question's votes for each value.
+pub fn count_by_value(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<(i32, i64)>> {
+    let counts: Vec<(Option<i32>, i64)> = votes
+        .inner_join(users::table)
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .filter(value.is_not_null())
+        .group_by(value)
+        .select((value, diesel::dsl::count_star()))
+        .order(value.asc())
+        .load(conn)?;
+
+    Ok(counts
+        .into_iter()
+        .filter_map(|(v, count)| v.map(|v| (v, count)))
+        .collect())
+}
+
+/// The question's votes with their voters' usernames, most recent first.
+/// Votes of deleted users are left out.
+pub fn get_all_with_usernames_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<(Vote, String)>> {
+    votes
+        .inner_join(users::table)
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .order(updated_at.desc())
+        .select((votes::all_columns, users::username))
+        .load(conn)
+}
+
+/// The options picked by each of the question's votes, in order, with the
+/// votes cast on each.
+pub fn get_picks_by_question_id(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<HashMap<Uuid, Vec<(Uuid, i32)>>> {
+    let picks: Vec<(Uuid, Uuid, i32)> = vote_options::table
+        .inner_join(votes.inner_join(users::table))
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .order((vote_options::vote_id, vote_options::rank))
+        .select((
+            vote_options::vote_id,
+            vote_options::option_id,
+            vote_options::weight,
+        ))
+        .load(conn)?;
+    let mut by_vote: HashMap<Uuid, Vec<(Uuid, i32)>> = HashMap::new();
+
+    for (voteid, optionid, votecount) in picks {
+        by_vote
+            .entry(voteid)
+            .or_default()
+            .push((optionid, votecount));
+    }
+
+    Ok(by_vote)
+}
+
+/// The options a vote picks, in order, with the votes cast on each.
+pub fn get_picks(
+    conn: &mut PgConnection,
+    voteid: Uuid,
+) -> QueryResult<Vec<(Uuid, i32)>> {
+    vote_options::table
+        .filter(vote_options::vote_id.eq(voteid))
+        .order(vote_options::rank)
+        .select((vote_options::option_id, vote_options::weight))
+        .load(conn)
+}
+
+/// The options picked by each of the question's votes, in the order they
+/// were ranked.
+pub fn get_ballots(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<Vec<Uuid>>> {
+    let picks: Vec<(Uuid, Uuid)> = vote_options::table
+        .inner_join(votes.inner_join(users::table))
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .order((vote_options::vote_id, vote_options::rank))
+        .select((vote_options::vote_id, vote_options::option_id))
+        .load(conn)?;
+
+    let mut ballots: Vec<Vec<Uuid>> = vec![];
+    let mut last_vote = None;
+
+    for (voteid, optionid) in picks {
+        if last_vote != Some(voteid) {
+            ballots.push(vec![]);
+            last_vote = Some(voteid);
+        }
+        ballots.last_mut().unwrap().push(optionid);
+    }
+
+    Ok(ballots)
+}
+
+/// Counts the question's votes for each option they picked.
+pub fn count_by_option(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<(Uuid, i64)>> {
+    vote_options::table
+        .inner_join(votes.inner_join(users::table))
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .group_by(vote_options::option_id)
+        .select((vote_options::option_id, diesel::dsl::count_star()))
+        .load(conn)
+}
+
+pub fn count_for_question(
     conn: &mut PgConnection,
     questionid: Uuid,
-    votevalue: i32,
 ) -> QueryResult<i64> {
     votes
+        .inner_join(users::table)
         .filter(question_id.eq(questionid))
-        .filter(value.eq(votevalue))
+        .filter(users::deleted_at.is_null())
         .count()
         .get_result(conn)
 }
+
+/// Sums the votes cast on each option of the question.
+pub fn sum_weights_by_option(
+    conn: &mut PgConnection,
+    questionid: Uuid,
+) -> QueryResult<Vec<(Uuid, i64)>> {
+    let sums: Vec<(Uuid, Option<i64>)> = vote_options::table
+        .inner_join(votes.inner_join(users::table))
+        .filter(question_id.eq(questionid))
+        .filter(users::deleted_at.is_null())
+        .group_by(vote_options::option_id)
+        .select((
+            vote_options::option_id,
+            diesel::dsl::sum(vote_options::weight),
+        ))
+        .load(conn)?;
+
+    Ok(sums
+        .into_iter()
+        .map(|(optionid, sum)| (optionid, sum.unwrap_or(0)))
+        .collect())
+}
+
+/// Checks that the answer suits the question's type, returning the options
+/// it picks with the votes cast on each, or the field at fault and why.
+pub fn check_answer(
+    conn: &mut PgConnection,
+    question: &Question,
+    answer: Option<i32>,
+    option_ids: Vec<String>,
+    allocations: Vec<AllocationInput>,
+) -> Result<Vec<(Uuid, i32)>, (&'static str, String)> {
+    let error = |field: &'static str, message: String| (field, message);
+
+    match question.question_type {
+        question_type if question_type.has_options() => {
+            if answer.is_some() {
+                return Err(error(
+                    "value",
+                    "Choice questions are answered with options.".to_owned(),
+                ));
+            }
+
+            let options = services::question::get_options(conn, question.id)
+                .map_err(|e| error("optionIds", e.to_string()))?;
+            let parse = |field: &'static str, option_id: &str| {
+                Uuid::parse_str(option_id)
+                    .ok()
+                    .filter(|optionid| {
+                        options.iter().any(|o| o.id == *optionid)
+                    })
+                    .ok_or_else(|| {
+                        error(
+                            field,
+                            "No option found with corresponding Id.".to_owned(),
+                        )
+                    })
+            };
+
+            if question_type == QuestionType::Quadratic {
+                if !option_ids.is_empty() {
+                    return Err(error(
+                        "optionIds",
+                        "Quadratic questions are answered with allocations."
+                            .to_owned(),
+                    ));
+                }
+
+                let mut picked: Vec<(Uuid, i32)> = vec![];
+
+                for allocation in allocations {
+                    let option_id =
+                        parse("allocations", &allocation.option_id)?;
+
+                    if allocation.votes < 0 {
+                        return Err(error(
+                            "allocations",
+                            "Votes cannot be negative.".to_owned(),
+                        ));
+                    }
+                    if picked.iter().any(|(optionid, _)| *optionid == option_id)
+                    {
+                        return Err(error(
+                            "allocations",
+                            "Each option can only be allocated once."
+                                .to_owned(),
+                        ));
+                    }
+                    if allocation.votes > 0 {
+                        picked.push((option_id, allocation.votes));
+                    }
+                }
+
+                let budget = question.credit_budget.unwrap_or(0) as i64;
+                let cost = services::tally::quadratic_cost(&picked);
+
+                if picked.is_empty() {
+                    return Err(error(
+                        "allocations",
+                        "Cast at least one vote.".to_owned(),
+                    ));
+                }
+                if cost > budget {
+                    return Err(error(
+                        "allocations",
+                        format!(
+                            "These votes cost {} credits, but only {} are available.",
+                            cost, budget
+                        ),
+                    ));
+                }
+
+                return Ok(picked);
+            }
+
+            if !allocations.is_empty() {
+                return Err(error(
+                    "allocations",
+                    "Only quadratic questions are answered with allocations."
+                        .to_owned(),
+                ));
+            }
+
+            let mut picked: Vec<Uuid> = vec![];
+
+            for option_id in option_ids {
+                let option_id = parse("optionIds", &option_id)?;
+
+                if !picked.contains(&option_id) {
+                    picked.push(option_id);
+                } else if question_type == QuestionType::Ranked {
+                    return Err(error(
+                        "optionIds",
+                        "Each option can only be ranked once.".to_owned(),
+                    ));
+                }
+            }
+
+            let picked: Vec<(Uuid, i32)> =
+                picked.into_iter().map(|optionid| (optionid, 1)).collect();
+
+            match (question_type, picked.len()) {
+                // Approving no option is a valid answer.
+                (QuestionType::Approval, _) => Ok(picked),
+                (_, 0) => Err(error(
+                    "optionIds",
+                    "Pick at least one option.".to_owned(),
+                )),
+                (QuestionType::SingleChoice, n) if n > 1 => {
+                    Err(error("optionIds", "Pick only one option.".to_owned()))
+                }
+                _ => Ok(picked),
+            }
+        }
+        question_type => {
+            if !option_ids.is_empty() || !allocations.is_empty() {
+                return Err(error(
+                    "optionIds",
+                    "Only choice questions are answered with options."
+                        .to_owned(),
+                ));
+            }
+
+            let answer = answer.ok_or_else(|| {
+                error("value", "A value is required.".to_owned())
+            })?;
+            let min = question.scale_min;
+            let max = question.scale_max;
+
+            let valid = match question_type {
+                QuestionType::YesNo => answer == 0 || answer == 1,
+                QuestionType::Rating => {
+                    let min = min.unwrap_or(0);
+                    let step = question.scale_step.unwrap_or(1).max(1);
+
+                    answer >= min
+                        && answer <= max.unwrap_or(5)
+                        && (answer - min) % step == 0
+                }
+                _ => {
+                    min.map_or(true, |min| answer >= min)
+                        && max.map_or(true, |max| answer <= max)
+                }
+            };
+
+            if valid {
+                return Ok(vec![]);
+            }
+
+            Err(error(
+                "value",
+                match (question_type, min, max) {
+                    (QuestionType::YesNo, _, _) => {
+                        "Value must be 0 (no) or 1 (yes)".to_owned()
+                    }
+                    (QuestionType::Rating, Some(min), Some(max)) => format!(
+                        "Value must be from {} to {} in steps of {}",
+                        min,
+                        max,
+                        question.scale_step.unwrap_or(1)
+                    ),
+                    (_, Some(min), Some(max)) => {
+                        format!("Value must be from {} to {}", min, max)
+                    }
+                    (_, Some(min), None) => {
+                        format!("Value must be at least {}", min)
+                    }
+                    (_, None, Some(max)) => {
+                        format!("Value must be at most {}", max)
+                    }
+                    _ => "Invalid value".to_owned(),
+                },
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    use super::*;
+    use crate::{
+        database::{
+            create_test_question, create_test_user, test_connection,
+            with_test_connection, with_test_transaction,
+        },
+        schema::{question_options, questions},
+    };
+
+    const VOTERS: usize = 8;
+
+    fn vote(conn: &mut PgConnection, userid: Uuid, questionid: Uuid, v: i32) {
+        upsert(
+            conn,
+            VoteInput {
+                value: Some(v),
+                user_id: userid,
+                question_id: questionid,
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn concurrent_upserts_keep_one_vote() {
+        with_test_connection(|conn| {
+            let userid = create_test_user(conn);
+            let questionid = create_test_question(conn, userid);
+
+            let barrier = Arc::new(Barrier::new(VOTERS));
+            let voters: Vec<_> = (0..VOTERS)
+                .map(|i| {
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        let mut conn = test_connection().unwrap();
+                        barrier.wait();
+                        upsert(
+                            &mut conn,
+                            VoteInput {
+                                value: Some(i as i32),
+                                user_id: userid,
+                                question_id: questionid,
+                            },
+                            &[],
+                        )
+                    })
+                })
+                .collect();
+            for voter in voters {
+                voter.join().unwrap().expect("Failed to upsert the vote.");
+            }
+
+            let rows: Vec<Vote> = votes
+                .filter(user_id.eq(userid))
+                .filter(question_id.eq(questionid))
+                .load(conn)
+                .unwrap();
+            let events = get_events_by_question_id(conn, questionid).unwrap();
+            let kinds: Vec<VoteEventKind> =
+                events.iter().map(|event| event.kind).collect();
+
+            diesel::delete(votes.filter(question_id.eq(questionid)))
+                .execute(conn)
+                .unwrap();
+            diesel::delete(questions::table.find(questionid))
+                .execute(conn)
+                .unwrap();
+            diesel::delete(users::table.find(userid))
+                .execute(conn)
+                .unwrap();
+
+            assert_eq!(rows.len(), 1);
+            assert_eq!(kinds.len(), VOTERS);
+            assert_eq!(kinds[0], VoteEventKind::Created);
+            assert!(kinds[1..]
+                .iter()
+                .all(|kind| *kind == VoteEventKind::Changed));
+            assert_eq!(events.last().unwrap().value, rows[0].value);
+        });
+    }
+
+    #[test]
+    fn leaves_out_votes_of_deleted_users() {
+        with_test_transaction(|conn| {
+            let author = create_test_user(conn);
+            let kept = create_test_user(conn);
+            let deleted = create_test_user(conn);
+            let questionid = create_test_question(conn, author);
+
+            vote(conn, kept, questionid, 1);
+            vote(conn, deleted, questionid, 3);
+            diesel::update(users::table.find(deleted))
+                .set(users::deleted_at.eq(diesel::dsl::now))
+                .execute(conn)?;
+
+            assert_eq!(count_for_question(conn, questionid)?, 1);
+            assert_eq!(count_by_value(conn, questionid)?, vec![(1, 1)]);
+            assert_eq!(
+                avg_for_question(conn, questionid)?,
+                Some(BigDecimal::from(1))
+            );
+            let all = get_all_by_question_id(conn, questionid)?;
+            assert_eq!(all.len(), 1);
+            assert_eq!(all[0].user_id, kept);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn records_every_change_and_retraction() {
+        with_test_connection(|conn| {
+            let userid = create_test_user(conn);
+            let questionid = create_test_question(conn, userid);
+
+            // Each call commits on its own, so the vote's timestamps differ
+            // after a change.
+            vote(conn, userid, questionid, 1);
+            vote(conn, userid, questionid, 3);
+            let retracted = retract(conn, userid, questionid).unwrap();
+            let retracted_again = retract(conn, userid, questionid).unwrap();
+            let remaining =
+                get_by_user_id_and_question_id(conn, userid, questionid);
+            let events = get_events_by_question_id(conn, questionid).unwrap();
+            let history =
+                get_events_by_user_id(conn, userid, 10, None).unwrap();
+
+            diesel::delete(questions::table.find(questionid))
+                .execute(conn)
+                .unwrap();
+            diesel::delete(users::table.find(userid))
+                .execute(conn)
+                .unwrap();
+
+            assert!(retracted);
+            assert!(!retracted_again);
+            assert!(remaining.is_err());
+            assert_eq!(
+                events
+                    .iter()
+                    .map(|event| (event.kind, event.value))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (VoteEventKind::Created, Some(1)),
+                    (VoteEventKind::Changed, Some(3)),
+                    (VoteEventKind::Retracted, None),
+                ]
+            );
+            // The user's history lists the same events, most recent first.
+            assert_eq!(
+                history.iter().map(|event| event.id).collect::<Vec<_>>(),
+                events
+                    .iter()
+                    .rev()
+                    .map(|event| event.id)
+                    .collect::<Vec<_>>()
+            );
+        });
+    }
+
+    /// Creates a question of the given type with `n` options, returning it
+    /// along with its options' ids.
+    fn typed_question(
+        conn: &mut PgConnection,
+        kind: QuestionType,
+        n: i32,
+    ) -> (Question, Vec<String>) {
+        let userid = create_test_user(conn);
+        let questionid = create_test_question(conn, userid);
+
+        diesel::update(questions::table.find(questionid))
+            .set(questions::question_type.eq(kind))
+            .execute(conn)
+            .unwrap();
+        let optionids = (0..n)
+            .map(|position| {
+                diesel::insert_into(question_options::table)
+                    .values((
+                        question_options::question_id.eq(questionid),
+                        question_options::position.eq(position),
+                        question_options::label
+                            .eq(format!("Option {}", position)),
+                    ))
+                    .returning(question_options::id)
+                    .get_result::<Uuid>(conn)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+
+        (
+            services::question::get_by_id(conn, questionid).unwrap(),
+            optionids,
+        )
+    }
+
+    fn check_value(
+        conn: &mut PgConnection,
+        question: &Question,
+        answer: i32,
+    ) -> bool {
+        check_answer(conn, question, Some(answer), vec![], vec![]).is_ok()
+    }
+
+    fn check_options(
+        conn: &mut PgConnection,
+        question: &Question,
+        option_ids: &[&String],
+    ) -> Result<Vec<(Uuid, i32)>, (&'static str, String)> {
+        let option_ids =
+            option_ids.iter().map(|option_id| option_id.to_string());
+
+        check_answer(conn, question, None, option_ids.collect(), vec![])
+    }
+
+    #[test]
+    fn yes_no_questions_take_zero_or_one() {
+        with_test_transaction(|conn| {
+            let (question, _) = typed_question(conn, QuestionType::YesNo, 0);
+
+            assert!(check_value(conn, &question, 0));
+            assert!(check_value(conn, &question, 1));
+            assert!(!check_value(conn, &question, 2));
+            assert!(!check_value(conn, &question, -1));
+            assert_eq!(
+                check_answer(conn, &question, None, vec![], vec![])
+                    .unwrap_err()
+                    .0,
+                "value"
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn ratings_stay_on_the_scale_and_its_steps() {
+        with_test_transaction(|conn| {
+            let (mut question, _) =
+                typed_question(conn, QuestionType::Rating, 0);
+
+            // Without a scale, ratings go from 0 to 5.
+            assert!(check_value(conn, &question, 0));
+            assert!(check_value(conn, &question, 5));
+            assert!(!check_value(conn, &question, 6));
+
+            question.scale_min = Some(1);
+            question.scale_max = Some(9);
+            question.scale_step = Some(2);
+            assert!(check_value(conn, &question, 1));
+            assert!(check_value(conn, &question, 7));
+            assert!(!check_value(conn, &question, 4));
+            assert!(!check_value(conn, &question, 0));
+            assert!(!check_value(conn, &question, 11));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn numbers_stay_within_their_bounds() {
+        with_test_transaction(|conn| {
+            let (mut question, _) =
+                typed_question(conn, QuestionType::Numeric, 0);
+
+            assert!(check_value(conn, &question, i32::MIN));
+            assert!(check_value(conn, &question, i32::MAX));
+
+            question.scale_min = Some(-10);
+            assert!(check_value(conn, &question, -10));
+            assert!(!check_value(conn, &question, -11));
+
+            question.scale_max = Some(10);
+            assert!(check_value(conn, &question, 10));
+            assert!(!check_value(conn, &question, 11));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn choice_questions_take_options_and_not_values() {
+        with_test_transaction(|conn| {
+            let (choice, optionids) =
+                typed_question(conn, QuestionType::SingleChoice, 2);
+            let (yes_no, _) = typed_question(conn, QuestionType::YesNo, 0);
+            let (_, other_optionids) =
+                typed_question(conn, QuestionType::SingleChoice, 1);
+
+            assert!(!check_value(conn, &choice, 1));
+            assert_eq!(
+                check_answer(
+                    conn,
+                    &yes_no,
+                    Some(1),
+                    vec![optionids[0].clone()],
+                    vec![]
+                )
+                .unwrap_err()
+                .0,
+                "optionIds"
+            );
+            assert!(
+                check_options(conn, &choice, &[&other_optionids[0]]).is_err()
+            );
+            assert!(check_options(conn, &choice, &[&"not an id".to_owned()])
+                .is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn choices_follow_their_question_type() {
+        with_test_transaction(|conn| {
+            let (single, singleids) =
+                typed_question(conn, QuestionType::SingleChoice, 2);
+            let (multiple, multipleids) =
+                typed_question(conn, QuestionType::MultipleChoice, 2);
+            let (ranked, rankedids) =
+                typed_question(conn, QuestionType::Ranked, 2);
+            let (approval, approvalids) =
+                typed_question(conn, QuestionType::Approval, 2);
+
+            assert!(check_options(conn, &single, &[&singleids[0]]).is_ok());
+            assert!(check_options(
+                conn,
+                &single,
+                &[&singleids[0], &singleids[1]]
+            )
+            .is_err());
+            assert!(check_options(conn, &single, &[]).is_err());
+
+            // Picking an option twice counts once.
+            assert_eq!(
+                check_options(
+                    conn,
+                    &multiple,
+                    &[&multipleids[1], &multipleids[1], &multipleids[0]]
+                )
+                .unwrap(),
+                vec![
+                    (Uuid::parse_str(&multipleids[1]).unwrap(), 1),
+                    (Uuid::parse_str(&multipleids[0]).unwrap(), 1)
+                ]
+            );
+            assert!(check_options(conn, &multiple, &[]).is_err());
+
+            assert!(check_options(
+                conn,
+                &ranked,
+                &[&rankedids[1], &rankedids[0]]
+            )
+            .is_ok());
+            assert!(check_options(
+                conn,
+                &ranked,
+                &[&rankedids[0], &rankedids[0]]
+            )
+            .is_err());
+
+            assert_eq!(check_options(conn, &approval, &[]).unwrap(), vec![]);
+            assert_eq!(
+                check_options(
+                    conn,
+                    &approval,
+                    &approvalids.iter().collect::<Vec<_>>()
+                )
+                .unwrap()
+                .len(),
+                2
+            );
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn quadratic_votes_stay_within_the_credit_budget() {
+        with_test_transaction(|conn| {
+            let (mut question, optionids) =
+                typed_question(conn, QuestionType::Quadratic, 3);
+            let allocate =
+                |conn: &mut PgConnection,
+                 question: &Question,
+                 allocations: &[(usize, i32)]| {
+                    let allocations = allocations
+                        .iter()
+                        .map(|(i, count)| AllocationInput {
+                            option_id: optionids[*i].clone(),
+                            votes: *count,
+                        })
+                        .collect();
+
+                    check_answer(conn, question, None, vec![], allocations)
+                };
+
+            question.credit_budget = Some(10);
+            // 3² + 1² spends the whole budget; options without votes are
+            // left out.
+            assert_eq!(
+                allocate(conn, &question, &[(0, 3), (1, 1), (2, 0)])
+                    .unwrap()
+                    .len(),
+                2
+            );
+            assert!(allocate(conn, &question, &[(0, 3), (1, 2)]).is_err());
+            assert!(allocate(conn, &question, &[(0, i32::MAX); 3]).is_err());
+            assert!(allocate(conn, &question, &[(0, -1)]).is_err());
+            assert!(allocate(conn, &question, &[(0, 1), (0, 1)]).is_err());
+            assert!(allocate(conn, &question, &[(0, 0)]).is_err());
+            assert!(check_options(conn, &question, &[&optionids[0]]).is_err());
+            Ok(())
+        });
+    }
+}
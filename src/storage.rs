@@ -0,0 +1,138 @@
+use std::{fs, io, path::PathBuf};
+
+use once_cell::sync::Lazy;
+
+use crate::config::config;
+
+static STORAGE: Lazy<Box<dyn Storage>> =
+    Lazy::new(|| match config().upload.storage.as_str() {
+        "local" => Box::new(LocalStorage::new(
+            config().upload.dir.clone(),
+            config().upload.base_url.clone(),
+        )),
+        other => panic!("Unknown UPLOAD_STORAGE `{}`.", other),
+    });
+
+/// Where uploaded files are kept. Keys are generated by the server and only
+/// contain ASCII letters, digits, dashes and dots.
+pub trait Storage: Send + Sync {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+    /// Reads a file back, for backends the server serves itself.
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    /// Deleting a missing file is not an error.
+    fn delete(&self, key: &str) -> io::Result<()>;
+    /// Where clients download the file from.
+    fn url(&self, key: &str) -> String;
+}
+
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.as_ref()
+}
+
+/// Whether `key` could have been generated by the server.
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Keeps files in a directory, served by the `/uploads/{key}` route.
+pub struct LocalStorage {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(dir: PathBuf, base_url: String) -> Self {
+        Self { dir, base_url }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        if !is_valid_key(key) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid storage key.",
+            ));
+        }
+
+        Ok(self.dir.join(key))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key)?, bytes)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key)?)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base_url, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn accepts_generated_keys() {
+        assert!(is_valid_key("0b6f9d8e-6a4c-4c59-9a57-3f1e2b7f0c11.png"));
+        assert!(is_valid_key(
+            "0b6f9d8e-6a4c-4c59-9a57-3f1e2b7f0c11-thumb.webp"
+        ));
+    }
+
+    #[test]
+    fn refuses_paths_and_hidden_files() {
+        for key in [
+            "",
+            ".",
+            "..",
+            ".env",
+            "../secret.png",
+            "a/b.png",
+            "a\\b.png",
+            "/etc/passwd",
+            "a b.png",
+            "a%2Fb.png",
+            "caf\u{e9}.png",
+        ] {
+            assert!(!is_valid_key(key), "{:?} should be refused", key);
+        }
+    }
+
+    #[test]
+    fn stores_local_files() {
+        let dir =
+            env::temp_dir().join(format!("votodroid-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(dir.clone(), "/uploads".to_owned());
+
+        storage.put("a.png", b"image").unwrap();
+        assert_eq!(storage.get("a.png").unwrap(), b"image");
+        assert_eq!(storage.url("a.png"), "/uploads/a.png");
+        storage.delete("a.png").unwrap();
+        storage.delete("a.png").unwrap();
+        assert!(storage.get("a.png").is_err());
+        assert_eq!(
+            storage.put("../a.png", b"image").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
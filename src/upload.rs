@@ -0,0 +1,235 @@
+use std::io::Cursor;
+
+use actix_session::Session;
+use actix_web::{
+    error::{
+        ErrorBadRequest, ErrorInternalServerError, ErrorNotFound,
+        ErrorTooManyRequests, ErrorUnauthorized, ErrorUnsupportedMediaType,
+    },
+    http::header,
+    web, Error, HttpRequest, HttpResponse,
+};
+use image::{io::Reader, DynamicImage, ImageFormat, ImageOutputFormat, Limits};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::config,
+    database::get_pool,
+    models::image::{Image, ImageInput},
+    services,
+    storage::{is_valid_key, storage},
+};
+
+/// Uploads a user may have waiting to be attached to a question.
+const MAX_UNATTACHED: i64 = 20;
+const JPEG_QUALITY: u8 = 85;
+
+/// Stores the image in the request body, sent with its MIME type
+/// (`image/jpeg`, `image/png` or `image/webp`) as `Content-Type`. The image
+/// is re-encoded, which drops its metadata (EXIF included), and a thumbnail
+/// is made. Answers with the id to attach it to a question with.
+pub async fn upload_route(
+    req: HttpRequest,
+    body: web::Bytes,
+    session: Session,
+) -> Result<HttpResponse, Error> {
+    let user_id = session
+        .get::<Uuid>("userId")?
+        .ok_or_else(|| ErrorUnauthorized("User not logged in."))?;
+    let format = match req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+    {
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        Some("image/png") => ImageFormat::Png,
+        Some("image/webp") => ImageFormat::WebP,
+        _ => {
+            return Err(ErrorUnsupportedMediaType(
+                "Only JPEG, PNG and WebP images can be uploaded.",
+            ))
+        }
+    };
+
+    let image = web::block(move || {
+        let mut conn = get_pool()
+            .get()
+            .expect("Failed to get connection to database.");
+
+        services::user::get_by_id(&mut conn, user_id)
+            .map_err(|_| ErrorUnauthorized("User not logged in."))?;
+
+        let unattached =
+            services::image::count_unattached_by_user_id(&mut conn, user_id)
+                .map_err(ErrorInternalServerError)?;
+
+        // Checked again when storing, this saves decoding the image when
+        // it would be refused anyway.
+        if unattached >= MAX_UNATTACHED {
+            return Err(too_many_unattached());
+        }
+
+        store(&mut conn, user_id, &body, format)
+    })
+    .await??;
+
+    Ok(HttpResponse::Created().json(json!({
+        "id": image.id,
+        "url": storage().url(&image.storage_key),
+        "thumbnailUrl": storage().url(&image.thumbnail_key),
+        "width": image.width,
+        "height": image.height,
+    })))
+}
+
+/// Serves files kept by the local storage.
+pub async fn upload_file_route(
+    key: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let key = key.into_inner();
+
+    if !is_valid_key(&key) {
+        return Err(ErrorNotFound("No such file."));
+    }
+
+    let content_type = if key.ends_with(".jpg") {
+        "image/jpeg"
+    } else {
+        "image/png"
+    };
+    let bytes = web::block(move || storage().get(&key))
+        .await?
+        .map_err(|_| ErrorNotFound("No such file."))?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        // Keys are never reused.
+        .insert_header((
+            header::CACHE_CONTROL,
+            "public, max-age=31536000, immutable",
+        ))
+        .body(bytes))
+}
+
+fn store(
+    conn: &mut diesel::PgConnection,
+    user_id: Uuid,
+    body: &[u8],
+    format: ImageFormat,
+) -> Result<Image, Error> {
+    let config = &config().upload;
+
+    if body.len() > config.max_bytes {
+        return Err(ErrorBadRequest("Image is too large."));
+    }
+    if image::guess_format(body).ok() != Some(format) {
+        return Err(ErrorUnsupportedMediaType(
+            "Image does not match its content type.",
+        ));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(config.max_dimension);
+    limits.max_image_height = Some(config.max_dimension);
+
+    let mut reader = Reader::with_format(Cursor::new(body), format);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|e| ErrorBadRequest(format!("Invalid image: {}", e)))?;
+    let image = orient(image, body);
+
+    let thumbnail = if image.width().max(image.height()) > config.thumbnail_size
+    {
+        image.thumbnail(config.thumbnail_size, config.thumbnail_size)
+    } else {
+        image.clone()
+    };
+
+    // Photos stay JPEG; everything else may have transparency.
+    let (output, extension, content_type) = match format {
+        ImageFormat::Jpeg => {
+            (ImageOutputFormat::Jpeg(JPEG_QUALITY), "jpg", "image/jpeg")
+        }
+        _ => (ImageOutputFormat::Png, "png", "image/png"),
+    };
+    let encode = |image: &DynamicImage| {
+        let mut bytes = Cursor::new(vec![]);
+        image
+            .write_to(&mut bytes, output.clone())
+            .map(|_| bytes.into_inner())
+            .map_err(ErrorInternalServerError)
+    };
+    let bytes = encode(&image)?;
+    let thumbnail_bytes = encode(&thumbnail)?;
+
+    let id = Uuid::new_v4();
+    let storage_key = format!("{}.{}", id, extension);
+    let thumbnail_key = format!("{}-thumb.{}", id, extension);
+
+    storage()
+        .put(&storage_key, &bytes)
+        .and_then(|_| storage().put(&thumbnail_key, &thumbnail_bytes))
+        .map_err(ErrorInternalServerError)?;
+
+    let created = services::image::create_unattached(
+        conn,
+        ImageInput {
+            id,
+            user_id,
+            content_type: content_type.to_owned(),
+            width: image.width() as i32,
+            height: image.height() as i32,
+            size_bytes: bytes.len() as i32,
+            storage_key: storage_key.clone(),
+            thumbnail_key: thumbnail_key.clone(),
+        },
+        MAX_UNATTACHED,
+    );
+
+    let error = match created {
+        Ok(Some(image)) => return Ok(image),
+        Ok(None) => too_many_unattached(),
+        Err(e) => ErrorInternalServerError(e),
+    };
+
+    let _ = storage().delete(&storage_key);
+    let _ = storage().delete(&thumbnail_key);
+    Err(error)
+}
+
+fn too_many_unattached() -> Error {
+    ErrorTooManyRequests(
+        "Too many uploads are waiting to be attached to a question.",
+    )
+}
+
+/// Applies the EXIF orientation, which is lost when the image is
+/// re-encoded.
+fn orient(image: DynamicImage, body: &[u8]) -> DynamicImage {
+    let orientation = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(body))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        });
+
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Lets `/uploads` accept bodies up to `UPLOAD_MAX_BYTES`.
+pub fn payload_config() -> web::PayloadConfig {
+    web::PayloadConfig::new(config().upload.max_bytes)
+}
@@ -0,0 +1,187 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::config;
+
+/// The length, in code points, of the `VARCHAR(128)` columns holding
+/// questions and their options.
+pub const TEXT_COLUMN_LENGTH: usize = 128;
+
+static ALLOWED_CHARACTERS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!("^[{}]*$", config().question.allowed_characters))
+        .expect("QUESTION_ALLOWED_CHARACTERS must be a regex character class.")
+});
+
+/// Trims `text`, collapses its whitespace to single spaces and puts it in
+/// Unicode normalization form C, so equal looking texts are stored alike.
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .nfc()
+        .collect()
+}
+
+/// Like `normalize`, but keeps the line breaks of multi-line text such as
+/// markdown, only unifying them to `\n`.
+pub fn normalize_multiline(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .trim()
+        .nfc()
+        .collect()
+}
+
+/// The number of characters a reader sees (extended grapheme clusters), so
+/// an emoji or an accented letter counts once however it is encoded.
+pub fn length(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Checks that normalized `text` is `min` to `max` characters long, fits a
+/// column of `column_length` code points and only uses allowed characters.
+/// Control characters are never allowed. `what` names the text in errors.
+pub fn check(
+    text: &str,
+    what: &str,
+    min: usize,
+    max: usize,
+    column_length: usize,
+) -> Result<(), String> {
+    let length = length(text);
+
+    if length < min {
+        return Err(format!(
+            "{} must be at least {} characters long.",
+            what, min
+        ));
+    }
+    if length > max || text.chars().count() > column_length {
+        return Err(format!(
+            "{} cannot be longer than {} characters.",
+            what, max
+        ));
+    }
+    if text.chars().any(char::is_control) || !ALLOWED_CHARACTERS.is_match(text)
+    {
+        return Err(format!("{} contains invalid characters.", what));
+    }
+
+    Ok(())
+}
+
+/// Checks a normalized description's length. Any printable character is
+/// allowed, along with line breaks and tabs.
+pub fn check_description(text: &str) -> Result<(), String> {
+    check_multiline(
+        text,
+        "Description",
+        config().question.description_max_length,
+    )
+}
+
+/// Checks a normalized comment, which must not be empty but otherwise
+/// follows the rules of descriptions.
+pub fn check_comment(text: &str, max: usize) -> Result<(), String> {
+    if text.is_empty() {
+        return Err("Comment cannot be empty.".to_owned());
+    }
+
+    check_multiline(text, "Comment", max)
+}
+
+/// Checks a normalized report note, which follows the rules of
+/// descriptions.
+pub fn check_report_note(text: &str, max: usize) -> Result<(), String> {
+    check_multiline(text, "Note", max)
+}
+
+fn check_multiline(text: &str, what: &str, max: usize) -> Result<(), String> {
+    if length(text) > max {
+        return Err(format!(
+            "{} cannot be longer than {} characters.",
+            what, max
+        ));
+    }
+    if text
+        .chars()
+        .any(|c| c.is_control() && c != '\n' && c != '\t')
+    {
+        return Err(format!("{} contains invalid characters.", what));
+    }
+
+    Ok(())
+}
+
+/// Checks a normalized question against the configured rules.
+pub fn check_question(text: &str) -> Result<(), String> {
+    let config = &config().question;
+
+    check(
+        text,
+        "Question",
+        config.min_length,
+        config.max_length,
+        TEXT_COLUMN_LENGTH,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_whitespace_and_composition() {
+        assert_eq!(normalize("  Is   this\tfine?\n"), "Is this fine?");
+        assert_eq!(normalize("Cafe\u{301}?"), "Caf\u{e9}?");
+    }
+
+    #[test]
+    fn keeps_line_breaks_of_multiline_text() {
+        assert_eq!(normalize_multiline(" a\r\nb\rc \n"), "a\nb\nc");
+        assert_eq!(normalize_multiline("e\u{301}\n\n  x"), "\u{e9}\n\n  x");
+    }
+
+    #[test]
+    fn counts_what_readers_see() {
+        assert_eq!(length("abc"), 3);
+        assert_eq!(length("e\u{301}"), 1);
+        assert_eq!(length("\u{1F44D}\u{1F3FD}"), 1);
+        assert_eq!(length("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 1);
+    }
+
+    #[test]
+    fn checks_lengths() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+
+        assert!(check("abc", "Text", 4, 10, 128).is_err());
+        assert!(check("abcd", "Text", 4, 10, 128).is_ok());
+        assert!(check("abcdefghijk", "Text", 4, 10, 128).is_err());
+        // Two characters, but ten code points.
+        assert!(check(&family.repeat(2), "Text", 1, 10, 5).is_err());
+    }
+
+    #[test]
+    fn refuses_invisible_and_control_characters() {
+        assert_eq!(
+            check("ab\u{0}cd", "Text", 1, 10, 128),
+            Err("Text contains invalid characters.".to_owned())
+        );
+        assert!(check_question("Hid\u{200B}den question?").is_err());
+        assert!(check_question(
+            "Est-ce d\u{e9}j\u{e0} l'\u{e9}t\u{e9}? \u{1F31E}"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn allows_line_breaks_in_multiline_text() {
+        assert!(check_description("A line\nthen\ta tab").is_ok());
+        assert!(check_description("A bell\u{7}").is_err());
+        assert!(check_comment("", 10).is_err());
+        assert!(check_comment("Fine", 10).is_ok());
+        assert!(check_report_note("Far too long", 5).is_err());
+    }
+}
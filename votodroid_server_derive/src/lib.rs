@@ -20,6 +20,20 @@ fn impl_votodroid_response_object(ast: &syn::DeriveInput) -> TokenStream {
         panic!("VotodroidResponseObject must be a struct with at least one named field.")
     })();
     let first_field_name = first_field.ident.as_ref().unwrap();
+    let other_fields: Vec<_> = (|| {
+        if let syn::Data::Struct(s) = &ast.data {
+            if let syn::Fields::Named(f) = &s.fields {
+                return f
+                    .named
+                    .iter()
+                    .skip(1)
+                    .filter_map(|f| f.ident.as_ref())
+                    .filter(|i| *i != "errors")
+                    .collect();
+            }
+        }
+        vec![]
+    })();
     let object_name = (|| {
         if let syn::Type::Path(p) = &first_field.ty {
             if let syn::PathArguments::AngleBracketed(ref a) =
@@ -39,18 +53,21 @@ fn impl_votodroid_response_object(ast: &syn::DeriveInput) -> TokenStream {
                 #name {
                     #first_field_name: Some(arg),
                     errors: None,
+                    #(#other_fields: Default::default(),)*
                 }
             }
             pub fn from_error(field: String, message: String) -> #name {
                 #name {
                     #first_field_name: None,
                     errors: Some(vec![FieldError::new(field, message)]),
+                    #(#other_fields: Default::default(),)*
                 }
             }
             pub fn from_errors(errors: Vec<FieldError>) -> #name {
                 #name {
                     #first_field_name: None,
                     errors: Some(errors),
+                    #(#other_fields: Default::default(),)*
                 }
             }
         }
@@ -58,3 +75,90 @@ fn impl_votodroid_response_object(ast: &syn::DeriveInput) -> TokenStream {
 
     out.into()
 }
+
+#[proc_macro_derive(VotodroidDbEnum)]
+pub fn votodroid_db_enum_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+
+    impl_votodroid_db_enum(&ast)
+}
+
+fn impl_votodroid_db_enum(ast: &syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let variants: Vec<_> = (|| {
+        if let syn::Data::Enum(e) = &ast.data {
+            return e
+                .variants
+                .iter()
+                .map(|v| {
+                    if !matches!(v.fields, syn::Fields::Unit) {
+                        panic!("VotodroidDbEnum variants must be unit variants.")
+                    }
+                    &v.ident
+                })
+                .collect();
+        }
+        panic!("VotodroidDbEnum must be an enum.")
+    })();
+    let values: Vec<_> = variants
+        .iter()
+        .map(|v| to_snake_case(&v.to_string()))
+        .collect();
+    let bytes: Vec<_> = values
+        .iter()
+        .map(|v| syn::LitByteStr::new(v.as_bytes(), name.span()))
+        .collect();
+    let error = format!("Unrecognized {} variant", name);
+    let out = quote! {
+        impl #name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    #(#name::#variants => #values,)*
+                }
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg>
+            for #name
+        {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+            ) -> diesel::serialize::Result {
+                use std::io::Write;
+                out.write_all(self.as_str().as_bytes())?;
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg>
+            for #name
+        {
+            fn from_sql(
+                bytes: diesel::pg::PgValue<'_>,
+            ) -> diesel::deserialize::Result<Self> {
+                match bytes.as_bytes() {
+                    #(#bytes => Ok(#name::#variants),)*
+                    _ => Err(#error.into()),
+                }
+            }
+        }
+    };
+
+    out.into()
+}
+
+fn to_snake_case(s: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}